@@ -14,6 +14,29 @@ pub struct IndexerRule {
     pub matching_rule: MatchingRule,
     pub id: Option<u32>,
     pub name: Option<String>,
+    #[serde(default)]
+    pub payload_enrichment: PayloadEnrichment,
+}
+
+/// Data added to the payloads of matches on top of the block hash and receipt id
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PayloadEnrichment {
+    #[default]
+    None,
+    /// Signer, predecessor, receiver, actions with decoded args, status and logs of matched receipts
+    Actions,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize, PartialEq, Eq)]
@@ -22,8 +45,11 @@ pub enum IndexerRuleKind {
     Event,
     AnyBlock,
     Shard,
+    /// Matches once per transaction, after its whole receipt tree has executed,
+    /// when the matching rule matches any of its receipts. Only transactions whose signer
+    /// or receiver matches the account patterns of the rule are tracked.
+    Transaction,
 }
-// future: ComposedRuleKind for multiple actions or events
 
 #[derive(Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize, PartialEq, Eq)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -33,6 +59,45 @@ pub enum Status {
     Fail,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AccessKeyPermission {
+    Any,
+    FullAccess,
+    FunctionCall,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AccessKeyChange {
+    Any,
+    Added,
+    Removed,
+}
+
+/// Condition on the value found at `path` of a JSON document, e.g. the arguments of a function call.
+/// `path` is a small JSON-path subset: `$.receiver_id`, `msg.amount`, `token_ids[0]`, `token_ids[*]`
+#[derive(Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize, PartialEq, Eq)]
+pub struct JsonPredicate {
+    pub path: String,
+    pub operator: PredicateOperator,
+    #[serde(default)]
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PredicateOperator {
+    Equal,
+    NotEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+    Contains,
+    Exists,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize, PartialEq, Eq)]
 #[serde(tag = "rule", rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum MatchingRule {
@@ -51,4 +116,223 @@ pub enum MatchingRule {
         version: String,
         event: String,
     },
+    /// Matches when every nested rule matches the receipt
+    All { matching_rules: MatchingRules },
+    /// Matches when at least one nested rule matches the receipt
+    Any { matching_rules: MatchingRules },
+    /// Matches when none of the nested rules match the receipt
+    Not { matching_rules: MatchingRules },
+    /// Matches every block, regardless of its receipts
+    AnyBlock,
+    /// Matches account updates, which carry the resulting balance of the account
+    StateChangeAccountBalance { affected_account_id: String },
+    /// Matches contract data updates and deletions of keys starting with `key_prefix`
+    StateChangeContractData {
+        affected_account_id: String,
+        key_prefix: String,
+    },
+    /// Matches access keys being added to or removed from the account
+    StateChangeAccessKey {
+        affected_account_id: String,
+        change: AccessKeyChange,
+    },
+    ActionDeployContract {
+        affected_account_id: String,
+        status: Status,
+    },
+    ActionAddKey {
+        affected_account_id: String,
+        status: Status,
+        permission: AccessKeyPermission,
+    },
+    ActionDeleteKey {
+        affected_account_id: String,
+        status: Status,
+    },
+    ActionStake {
+        affected_account_id: String,
+        status: Status,
+    },
+    ActionCreateAccount {
+        affected_account_id: String,
+        status: Status,
+    },
+    ActionDeleteAccount {
+        affected_account_id: String,
+        status: Status,
+    },
+    ActionTransfer {
+        affected_account_id: String,
+        status: Status,
+    },
+    /// Same as `ActionFunctionCall`, the JSON arguments of the call must also satisfy every predicate
+    ActionFunctionCallArgs {
+        affected_account_id: String,
+        status: Status,
+        function: String,
+        args: Vec<JsonPredicate>,
+    },
+    /// Same as `Event`, the `data` of the logged event must also satisfy every predicate
+    EventData {
+        contract_account_id: String,
+        standard: String,
+        version: String,
+        event: String,
+        data: Vec<JsonPredicate>,
+    },
+}
+
+pub const MAX_COMPOSED_RULE_DEPTH: usize = 8;
+
+impl MatchingRule {
+    /// Checks the shape of composed rules and the predicates of leaf rules
+    pub fn validate(&self) -> Result<(), String> {
+        self.validate_at_depth(1)?;
+        // state change leaves are matched against the state changes of a shard and the other
+        // leaves against its receipts, so a composition of both could never match
+        match self.leaf_sources() {
+            (true, true) => {
+                Err("Composed rules can't mix state change rules with receipt rules".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the leaves of the rule match state changes and whether they match receipts
+    fn leaf_sources(&self) -> (bool, bool) {
+        match self {
+            MatchingRule::All { matching_rules }
+            | MatchingRule::Any { matching_rules }
+            | MatchingRule::Not { matching_rules } => matching_rules.iter().fold(
+                (false, false),
+                |(state_changes, receipts), matching_rule| {
+                    let (leaf_state_changes, leaf_receipts) = matching_rule.leaf_sources();
+                    (
+                        state_changes || leaf_state_changes,
+                        receipts || leaf_receipts,
+                    )
+                },
+            ),
+            MatchingRule::StateChangeAccountBalance { .. }
+            | MatchingRule::StateChangeContractData { .. }
+            | MatchingRule::StateChangeAccessKey { .. } => (true, false),
+            _ => (false, true),
+        }
+    }
+
+    fn validate_at_depth(&self, depth: usize) -> Result<(), String> {
+        match self {
+            MatchingRule::All { matching_rules }
+            | MatchingRule::Any { matching_rules }
+            | MatchingRule::Not { matching_rules } => {
+                if depth > MAX_COMPOSED_RULE_DEPTH {
+                    return Err(format!(
+                        "Composed rules can be nested at most {} levels deep",
+                        MAX_COMPOSED_RULE_DEPTH
+                    ));
+                }
+                if matching_rules.is_empty() {
+                    return Err("Composed rules must contain at least one rule".to_string());
+                }
+                matching_rules
+                    .iter()
+                    .try_for_each(|matching_rule| matching_rule.validate_at_depth(depth + 1))
+            }
+            MatchingRule::ActionFunctionCallArgs { args, .. } => validate_predicates(args),
+            MatchingRule::EventData { data, .. } => validate_predicates(data),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn validate_predicates(predicates: &[JsonPredicate]) -> Result<(), String> {
+    if predicates.is_empty() {
+        return Err("Predicates must contain at least one predicate".to_string());
+    }
+    match predicates
+        .iter()
+        .find(|predicate| predicate.path.trim_start_matches(['$', '.']).is_empty())
+    {
+        Some(predicate) => Err(format!("Predicate {:?} has an empty path", predicate)),
+        None => Ok(()),
+    }
+}
+
+/// Rules nested inside a composed [MatchingRule].
+/// Borsh is implemented by hand as the derived bounds can't express a recursive type.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(transparent)]
+pub struct MatchingRules(pub Vec<MatchingRule>);
+
+impl From<Vec<MatchingRule>> for MatchingRules {
+    fn from(matching_rules: Vec<MatchingRule>) -> Self {
+        Self(matching_rules)
+    }
+}
+
+impl std::ops::Deref for MatchingRules {
+    type Target = Vec<MatchingRule>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl BorshSerialize for MatchingRules {
+    fn serialize<W: borsh::maybestd::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> borsh::maybestd::io::Result<()> {
+        BorshSerialize::serialize(&self.0, writer)
+    }
+}
+
+#[cfg(not(feature = "near-sdk"))]
+impl BorshDeserialize for MatchingRules {
+    fn deserialize_reader<R: borsh::maybestd::io::Read>(
+        reader: &mut R,
+    ) -> borsh::maybestd::io::Result<Self> {
+        Ok(Self(BorshDeserialize::deserialize_reader(reader)?))
+    }
+}
+
+#[cfg(feature = "near-sdk")]
+impl BorshDeserialize for MatchingRules {
+    fn deserialize(buf: &mut &[u8]) -> borsh::maybestd::io::Result<Self> {
+        Ok(Self(BorshDeserialize::deserialize(buf)?))
+    }
+}
+
+#[cfg(all(test, not(feature = "near-sdk")))]
+mod tests {
+    use super::{AccessKeyChange, MatchingRule, Status};
+
+    #[test]
+    fn reject_compositions_mixing_state_changes_and_receipts() {
+        let access_key_added = MatchingRule::StateChangeAccessKey {
+            affected_account_id: "alice.near".to_string(),
+            change: AccessKeyChange::Added,
+        };
+        let action_any = MatchingRule::ActionAny {
+            affected_account_id: "alice.near".to_string(),
+            status: Status::Any,
+        };
+
+        assert!(MatchingRule::Any {
+            matching_rules: vec![access_key_added.clone(), access_key_added.clone()].into(),
+        }
+        .validate()
+        .is_ok());
+        assert!(MatchingRule::All {
+            matching_rules: vec![
+                access_key_added,
+                MatchingRule::Not {
+                    matching_rules: vec![action_any].into(),
+                },
+            ]
+            .into(),
+        }
+        .validate()
+        .is_err());
+    }
 }
@@ -1,6 +1,6 @@
 pub mod indexer_rule;
 
-use indexer_rule::{IndexerRule, IndexerRuleKind, MatchingRule, Status};
+use indexer_rule::{IndexerRule, IndexerRuleKind, MatchingRule, PayloadEnrichment, Status};
 pub fn near_social_indexer_rule() -> IndexerRule {
     let contract = "social.near";
     let method = "set";
@@ -14,5 +14,6 @@ pub fn near_social_indexer_rule() -> IndexerRule {
         matching_rule,
         id: None,
         name: None,
+        payload_enrichment: PayloadEnrichment::None,
     }
 }
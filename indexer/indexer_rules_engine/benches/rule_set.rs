@@ -0,0 +1,122 @@
+//! Compares reducing every indexer rule on its own with evaluating them all through a `RuleSet`.
+//! Download the fixture blocks first: `./download_test_blocks.sh 80854399 92476362 93085141 93659695`
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use indexer_rule_type::indexer_rule::{
+    IndexerRule, IndexerRuleKind, MatchingRule, PayloadEnrichment, Status,
+};
+use indexer_rules_engine::primitives::StreamerMessage;
+use indexer_rules_engine::rule_set::RuleSet;
+use indexer_rules_engine::types::indexer_rule_match::{ChainId, ExtractionMode};
+
+const FIXTURE_BLOCKS: [u64; 4] = [80854399, 92476362, 93085141, 93659695];
+const INDEXER_COUNTS: [usize; 3] = [100, 1_000, 5_000];
+
+fn read_local_streamer_message(block_height: u64) -> StreamerMessage {
+    let path = format!("../blocks/{}.json", block_height);
+    let json =
+        std::fs::read_to_string(&path).unwrap_or_else(|_| panic!("Missing fixture block {}", path));
+    serde_json::from_str(&json).unwrap()
+}
+
+/// Rules resembling a registry: mostly exact contracts, some `*.suffix` and some other wildcards.
+/// A few of them use accounts of the fixture blocks so that matches are built too.
+fn indexer_rules(count: usize, streamer_messages: &[StreamerMessage]) -> Vec<(usize, IndexerRule)> {
+    let block_accounts: Vec<String> = streamer_messages
+        .iter()
+        .flat_map(|streamer_message| streamer_message.shards.iter())
+        .flat_map(|shard| shard.receipt_execution_outcomes.iter())
+        .map(|receipt_execution_outcome| receipt_execution_outcome.receipt.receiver_id.to_string())
+        .collect();
+
+    (0..count)
+        .map(|index| {
+            let matching_rule = match index % 10 {
+                0 => MatchingRule::ActionAny {
+                    affected_account_id: block_accounts[index % block_accounts.len()].clone(),
+                    status: Status::Success,
+                },
+                1 => MatchingRule::ActionAny {
+                    affected_account_id: format!("*.indexer{}.near", index),
+                    status: Status::Any,
+                },
+                2 => MatchingRule::ActionFunctionCall {
+                    affected_account_id: format!("app{}.nea*.near", index),
+                    function: "*".to_string(),
+                    status: Status::Any,
+                },
+                3 => MatchingRule::Event {
+                    contract_account_id: format!("nft{}.near, *.nft{}.near", index, index),
+                    standard: "nep171".to_string(),
+                    version: "*".to_string(),
+                    event: "nft_mint".to_string(),
+                },
+                _ => MatchingRule::ActionAny {
+                    affected_account_id: format!("contract{}.near", index),
+                    status: Status::Any,
+                },
+            };
+            (
+                index,
+                IndexerRule {
+                    indexer_rule_kind: IndexerRuleKind::Action,
+                    matching_rule,
+                    id: None,
+                    name: None,
+                    payload_enrichment: PayloadEnrichment::None,
+                },
+            )
+        })
+        .collect()
+}
+
+fn reduce_matches(criterion: &mut Criterion) {
+    let streamer_messages: Vec<StreamerMessage> = FIXTURE_BLOCKS
+        .iter()
+        .map(|block_height| read_local_streamer_message(*block_height))
+        .collect();
+
+    let mut group = criterion.benchmark_group("reduce_indexer_rule_matches");
+    for indexer_count in INDEXER_COUNTS {
+        let rules = indexer_rules(indexer_count, &streamer_messages);
+
+        group.bench_with_input(
+            BenchmarkId::new("per_rule", indexer_count),
+            &rules,
+            |bencher, rules| {
+                bencher.iter(|| {
+                    for streamer_message in &streamer_messages {
+                        for (_, indexer_rule) in rules {
+                            indexer_rules_engine::reduce_indexer_rule_matches_sync(
+                                indexer_rule,
+                                streamer_message,
+                                ChainId::Mainnet,
+                                ExtractionMode::All,
+                            );
+                        }
+                    }
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("rule_set", indexer_count),
+            &rules,
+            |bencher, rules| {
+                bencher.iter(|| {
+                    let rule_set = RuleSet::new(rules.iter().cloned());
+                    for streamer_message in &streamer_messages {
+                        rule_set.reduce_indexer_rule_matches(
+                            streamer_message,
+                            ChainId::Mainnet,
+                            ExtractionMode::All,
+                        );
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, reduce_matches);
+criterion_main!(benches);
@@ -0,0 +1,502 @@
+use std::collections::HashMap;
+
+use crate::matcher;
+use crate::primitives::{
+    views::{
+        ActionView, ExecutionStatusView, ReceiptEnumView, StateChangeValueView,
+        StateChangeWithCauseView,
+    },
+    IndexerExecutionOutcomeWithReceipt, StreamerMessage,
+};
+use crate::state_changes_reducer;
+use crate::transaction_resolver::ReceiptTransactionHashes;
+use crate::types::events::Event;
+use crate::types::indexer_rule_match::{ChainId, ExtractionMode, IndexerRuleMatch};
+use indexer_rule_type::indexer_rule::{IndexerRule, IndexerRuleKind, MatchingRule, Status};
+
+/// Why a rule did or did not match a block, for each receipt, state change or transaction
+/// the rule is evaluated against
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct RuleExplanation {
+    pub block_height: u64,
+    pub block_hash: String,
+    /// Every match the rule produces for the block, including state change and block matches
+    pub matches: Vec<IndexerRuleMatch>,
+    /// Empty for state change rules, which are evaluated against the state changes instead
+    pub receipts: Vec<ReceiptTrace>,
+    /// Only traced for state change rules
+    pub state_changes: Vec<StateChangeTrace>,
+    /// Only traced for transaction rules
+    pub transactions: Vec<TransactionTrace>,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ReceiptTrace {
+    pub receipt_id: String,
+    pub receiver_id: String,
+    pub predecessor_id: String,
+    pub status: ExecutionStatusView,
+    pub method_names: Vec<String>,
+    pub events: Vec<Event>,
+    /// Leaf rules of composed rules are traced one by one
+    pub leaf_rules: Vec<LeafRuleTrace>,
+    pub matches: bool,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct LeafRuleTrace {
+    pub matching_rule: MatchingRule,
+    pub account_patterns: Vec<AccountPatternTrace>,
+    /// `None` for rules which don't filter on the execution status
+    pub status_matches: Option<bool>,
+    pub matches: bool,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct AccountPatternTrace {
+    pub pattern: String,
+    pub receiver_id_matches: bool,
+    pub predecessor_id_matches: bool,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct StateChangeTrace {
+    pub account_id: String,
+    /// `type` of the change, e.g. `access_key_update`
+    pub change_type: String,
+    pub receipt_id: Option<String>,
+    pub transaction_hash: Option<String>,
+    /// Leaf rules of composed rules are traced one by one
+    pub leaf_rules: Vec<StateChangeLeafRuleTrace>,
+    pub matches: bool,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct StateChangeLeafRuleTrace {
+    pub matching_rule: MatchingRule,
+    pub account_patterns: Vec<StateChangeAccountPatternTrace>,
+    pub matches: bool,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct StateChangeAccountPatternTrace {
+    pub pattern: String,
+    pub account_id_matches: bool,
+}
+
+/// Transaction of the block with its receipts executed in the same block. The transaction
+/// matches once its whole receipt tree has executed, usually in later blocks, if any of its
+/// receipts matches the rule.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct TransactionTrace {
+    pub transaction_hash: String,
+    pub signer_id: String,
+    pub receiver_id: String,
+    pub receipt_ids: Vec<String>,
+    pub matching_receipt_ids: Vec<String>,
+}
+
+pub fn explain(
+    indexer_rule: &IndexerRule,
+    streamer_message: &StreamerMessage,
+    chain_id: ChainId,
+) -> RuleExplanation {
+    let mut leaf_rules = vec![];
+    collect_leaf_rules(&indexer_rule.matching_rule, &mut leaf_rules);
+    let is_state_change_rule = matcher::is_state_change_rule(&indexer_rule.matching_rule);
+
+    RuleExplanation {
+        block_height: streamer_message.block.header.height,
+        block_hash: streamer_message.block.header.hash.to_string(),
+        matches: crate::reduce_indexer_rule_matches_sync(
+            indexer_rule,
+            streamer_message,
+            chain_id,
+            ExtractionMode::All,
+        ),
+        receipts: if is_state_change_rule {
+            vec![]
+        } else {
+            streamer_message
+                .shards
+                .iter()
+                .flat_map(|shard| shard.receipt_execution_outcomes.iter())
+                .map(|receipt_execution_outcome| {
+                    trace_receipt(indexer_rule, &leaf_rules, receipt_execution_outcome)
+                })
+                .collect()
+        },
+        state_changes: if is_state_change_rule {
+            streamer_message
+                .shards
+                .iter()
+                .flat_map(|shard| shard.state_changes.iter())
+                .map(|state_change| trace_state_change(indexer_rule, &leaf_rules, state_change))
+                .collect()
+        } else {
+            vec![]
+        },
+        transactions: match indexer_rule.indexer_rule_kind {
+            IndexerRuleKind::Transaction => trace_transactions(indexer_rule, streamer_message),
+            IndexerRuleKind::Action
+            | IndexerRuleKind::Event
+            | IndexerRuleKind::AnyBlock
+            | IndexerRuleKind::Shard => vec![],
+        },
+    }
+}
+
+fn trace_receipt(
+    indexer_rule: &IndexerRule,
+    leaf_rules: &[&MatchingRule],
+    receipt_execution_outcome: &IndexerExecutionOutcomeWithReceipt,
+) -> ReceiptTrace {
+    let receipt = &receipt_execution_outcome.receipt;
+    let outcome = &receipt_execution_outcome.execution_outcome.outcome;
+
+    let method_names = match &receipt.receipt {
+        ReceiptEnumView::Action { actions, .. } => actions
+            .iter()
+            .filter_map(|action| match action {
+                ActionView::FunctionCall { method_name, .. } => Some(method_name.clone()),
+                _ => None,
+            })
+            .collect(),
+        ReceiptEnumView::Data { .. } => vec![],
+    };
+
+    ReceiptTrace {
+        receipt_id: receipt.receipt_id.to_string(),
+        receiver_id: receipt.receiver_id.to_string(),
+        predecessor_id: receipt.predecessor_id.to_string(),
+        status: outcome.status.clone(),
+        method_names,
+        events: outcome
+            .logs
+            .iter()
+            .filter_map(|log| Event::from_log(log).ok())
+            .collect(),
+        leaf_rules: leaf_rules
+            .iter()
+            .map(|leaf_rule| trace_leaf_rule(leaf_rule, receipt_execution_outcome))
+            .collect(),
+        matches: matcher::matches(&indexer_rule.matching_rule, receipt_execution_outcome),
+    }
+}
+
+fn trace_leaf_rule(
+    matching_rule: &MatchingRule,
+    receipt_execution_outcome: &IndexerExecutionOutcomeWithReceipt,
+) -> LeafRuleTrace {
+    let (account_pattern, status) = leaf_rule_account_pattern_and_status(matching_rule);
+    let receipt = &receipt_execution_outcome.receipt;
+
+    LeafRuleTrace {
+        matching_rule: matching_rule.clone(),
+        account_patterns: account_pattern
+            .map(|account_pattern| {
+                split_account_patterns(account_pattern)
+                    .map(|pattern| AccountPatternTrace {
+                        pattern: pattern.to_string(),
+                        receiver_id_matches: matcher::match_account_id(
+                            pattern,
+                            receipt.receiver_id.as_str(),
+                        ),
+                        predecessor_id_matches: matcher::match_account_id(
+                            pattern,
+                            receipt.predecessor_id.as_str(),
+                        ),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        status_matches: status.map(|status| {
+            matcher::match_status(
+                status,
+                &receipt_execution_outcome.execution_outcome.outcome.status,
+            )
+        }),
+        matches: matcher::matches(matching_rule, receipt_execution_outcome),
+    }
+}
+
+fn trace_state_change(
+    indexer_rule: &IndexerRule,
+    leaf_rules: &[&MatchingRule],
+    state_change: &StateChangeWithCauseView,
+) -> StateChangeTrace {
+    let (account_id, change_type) = state_change_account_id_and_type(&state_change.value);
+    let (receipt_id, transaction_hash) =
+        state_changes_reducer::cause_receipt_id_and_transaction_hash(&state_change.cause);
+
+    StateChangeTrace {
+        account_id: account_id.to_string(),
+        change_type: change_type.to_string(),
+        receipt_id,
+        transaction_hash,
+        leaf_rules: leaf_rules
+            .iter()
+            .map(|leaf_rule| StateChangeLeafRuleTrace {
+                matching_rule: (*leaf_rule).clone(),
+                account_patterns: leaf_rule_account_pattern_and_status(leaf_rule)
+                    .0
+                    .map(|account_pattern| {
+                        split_account_patterns(account_pattern)
+                            .map(|pattern| StateChangeAccountPatternTrace {
+                                pattern: pattern.to_string(),
+                                account_id_matches: matcher::match_account_id(pattern, account_id),
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                matches: matcher::matches_state_change(leaf_rule, state_change),
+            })
+            .collect(),
+        matches: matcher::matches_state_change(&indexer_rule.matching_rule, state_change),
+    }
+}
+
+fn trace_transactions(
+    indexer_rule: &IndexerRule,
+    streamer_message: &StreamerMessage,
+) -> Vec<TransactionTrace> {
+    let receipt_transaction_hashes =
+        ReceiptTransactionHashes::from_block(streamer_message, HashMap::new());
+    let receipt_execution_outcomes: Vec<&IndexerExecutionOutcomeWithReceipt> = streamer_message
+        .shards
+        .iter()
+        .flat_map(|shard| shard.receipt_execution_outcomes.iter())
+        .collect();
+
+    streamer_message
+        .shards
+        .iter()
+        .filter_map(|shard| shard.chunk.as_ref())
+        .flat_map(|chunk| chunk.transactions.iter())
+        .map(|transaction| {
+            let transaction_hash = transaction.transaction.hash.to_string();
+            let receipts: Vec<&IndexerExecutionOutcomeWithReceipt> = receipt_execution_outcomes
+                .iter()
+                .filter(|receipt_execution_outcome| {
+                    receipt_transaction_hashes
+                        .get(&receipt_execution_outcome.receipt.receipt_id.to_string())
+                        == Some(&transaction_hash)
+                })
+                .copied()
+                .collect();
+
+            TransactionTrace {
+                signer_id: transaction.transaction.signer_id.to_string(),
+                receiver_id: transaction.transaction.receiver_id.to_string(),
+                receipt_ids: receipts
+                    .iter()
+                    .map(|receipt_execution_outcome| {
+                        receipt_execution_outcome.receipt.receipt_id.to_string()
+                    })
+                    .collect(),
+                matching_receipt_ids: receipts
+                    .iter()
+                    .filter(|receipt_execution_outcome| {
+                        matcher::matches(&indexer_rule.matching_rule, receipt_execution_outcome)
+                    })
+                    .map(|receipt_execution_outcome| {
+                        receipt_execution_outcome.receipt.receipt_id.to_string()
+                    })
+                    .collect(),
+                transaction_hash,
+            }
+        })
+        .collect()
+}
+
+fn split_account_patterns(account_pattern: &str) -> impl Iterator<Item = &str> {
+    account_pattern.split(',').map(|pattern| pattern.trim())
+}
+
+fn state_change_account_id_and_type(value: &StateChangeValueView) -> (&str, &'static str) {
+    match value {
+        StateChangeValueView::AccountUpdate { account_id, .. } => {
+            (account_id.as_str(), "account_update")
+        }
+        StateChangeValueView::AccountDeletion { account_id } => {
+            (account_id.as_str(), "account_deletion")
+        }
+        StateChangeValueView::AccessKeyUpdate { account_id, .. } => {
+            (account_id.as_str(), "access_key_update")
+        }
+        StateChangeValueView::AccessKeyDeletion { account_id, .. } => {
+            (account_id.as_str(), "access_key_deletion")
+        }
+        StateChangeValueView::DataUpdate { account_id, .. } => (account_id.as_str(), "data_update"),
+        StateChangeValueView::DataDeletion { account_id, .. } => {
+            (account_id.as_str(), "data_deletion")
+        }
+        StateChangeValueView::ContractCodeUpdate { account_id, .. } => {
+            (account_id.as_str(), "contract_code_update")
+        }
+        StateChangeValueView::ContractCodeDeletion { account_id } => {
+            (account_id.as_str(), "contract_code_deletion")
+        }
+    }
+}
+
+fn collect_leaf_rules<'a>(matching_rule: &'a MatchingRule, leaf_rules: &mut Vec<&'a MatchingRule>) {
+    match matching_rule {
+        MatchingRule::All { matching_rules }
+        | MatchingRule::Any { matching_rules }
+        | MatchingRule::Not { matching_rules } => matching_rules
+            .iter()
+            .for_each(|matching_rule| collect_leaf_rules(matching_rule, leaf_rules)),
+        _ => leaf_rules.push(matching_rule),
+    }
+}
+
+fn leaf_rule_account_pattern_and_status(
+    matching_rule: &MatchingRule,
+) -> (Option<&str>, Option<&Status>) {
+    match matching_rule {
+        MatchingRule::ActionAny {
+            affected_account_id,
+            status,
+        }
+        | MatchingRule::ActionFunctionCall {
+            affected_account_id,
+            status,
+            ..
+        }
+        | MatchingRule::ActionFunctionCallArgs {
+            affected_account_id,
+            status,
+            ..
+        }
+        | MatchingRule::ActionDeployContract {
+            affected_account_id,
+            status,
+        }
+        | MatchingRule::ActionAddKey {
+            affected_account_id,
+            status,
+            ..
+        }
+        | MatchingRule::ActionDeleteKey {
+            affected_account_id,
+            status,
+        }
+        | MatchingRule::ActionStake {
+            affected_account_id,
+            status,
+        }
+        | MatchingRule::ActionCreateAccount {
+            affected_account_id,
+            status,
+        }
+        | MatchingRule::ActionDeleteAccount {
+            affected_account_id,
+            status,
+        }
+        | MatchingRule::ActionTransfer {
+            affected_account_id,
+            status,
+        } => (Some(affected_account_id), Some(status)),
+        MatchingRule::Event {
+            contract_account_id,
+            ..
+        }
+        | MatchingRule::EventData {
+            contract_account_id,
+            ..
+        } => (Some(contract_account_id), None),
+        MatchingRule::StateChangeAccountBalance {
+            affected_account_id,
+        }
+        | MatchingRule::StateChangeContractData {
+            affected_account_id,
+            ..
+        }
+        | MatchingRule::StateChangeAccessKey {
+            affected_account_id,
+            ..
+        } => (Some(affected_account_id), None),
+        MatchingRule::AnyBlock
+        | MatchingRule::All { .. }
+        | MatchingRule::Any { .. }
+        | MatchingRule::Not { .. } => (None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{trace_receipt, trace_state_change};
+    use crate::test_utils::{outcome_with_actions, state_change, HASH};
+    use indexer_rule_type::indexer_rule::{
+        AccessKeyChange, IndexerRule, IndexerRuleKind, MatchingRule, PayloadEnrichment, Status,
+    };
+
+    #[test]
+    fn trace_function_call_on_other_account() {
+        let outcome = outcome_with_actions(serde_json::json!([{
+            "FunctionCall": {
+                "method_name": "ft_transfer",
+                "args": "e30=",
+                "gas": 0,
+                "deposit": "1"
+            }
+        }]));
+        let matching_rule = MatchingRule::ActionFunctionCall {
+            affected_account_id: "token.near, *.alice.near".to_string(),
+            function: "ft_transfer".to_string(),
+            status: Status::Success,
+        };
+        let indexer_rule = IndexerRule {
+            indexer_rule_kind: IndexerRuleKind::Action,
+            matching_rule: matching_rule.clone(),
+            id: None,
+            name: None,
+            payload_enrichment: PayloadEnrichment::None,
+        };
+
+        let trace = trace_receipt(&indexer_rule, &[&matching_rule], &outcome);
+
+        assert!(!trace.matches);
+        assert_eq!(trace.method_names, vec!["ft_transfer".to_string()]);
+        let leaf_rule = &trace.leaf_rules[0];
+        assert_eq!(leaf_rule.status_matches, Some(true));
+        assert_eq!(leaf_rule.account_patterns.len(), 2);
+        assert_eq!(leaf_rule.account_patterns[1].pattern, "*.alice.near");
+        assert!(!leaf_rule.account_patterns[1].receiver_id_matches);
+        assert!(!leaf_rule.account_patterns[1].predecessor_id_matches);
+    }
+
+    #[test]
+    fn trace_access_key_removal_of_other_account() {
+        let state_change = state_change(
+            serde_json::json!({ "type": "transaction_processing", "tx_hash": HASH }),
+            "access_key_deletion",
+            serde_json::json!({
+                "account_id": "bob.near",
+                "public_key": "ed25519:CE3QAXyVLeScmY9YeEyR3Tw9yXfjBPzFLzroTranYtVb"
+            }),
+        );
+        let matching_rule = MatchingRule::StateChangeAccessKey {
+            affected_account_id: "alice.near".to_string(),
+            change: AccessKeyChange::Removed,
+        };
+        let indexer_rule = IndexerRule {
+            indexer_rule_kind: IndexerRuleKind::Action,
+            matching_rule: matching_rule.clone(),
+            id: None,
+            name: None,
+            payload_enrichment: PayloadEnrichment::None,
+        };
+
+        let trace = trace_state_change(&indexer_rule, &[&matching_rule], &state_change);
+
+        assert!(!trace.matches);
+        assert_eq!(trace.account_id, "bob.near");
+        assert_eq!(trace.change_type, "access_key_deletion");
+        assert_eq!(trace.transaction_hash, Some(HASH.to_string()));
+        let leaf_rule = &trace.leaf_rules[0];
+        assert_eq!(leaf_rule.account_patterns[0].pattern, "alice.near");
+        assert!(!leaf_rule.account_patterns[0].account_id_matches);
+    }
+}
@@ -1,28 +1,83 @@
+pub mod explain;
 pub mod matcher;
 pub mod outcomes_reducer;
 mod outcomes_reducer_sync;
+pub mod predicates;
+pub mod primitives;
+pub mod rule_set;
+pub mod state_changes_reducer;
+#[cfg(all(feature = "stream", not(target_arch = "wasm32")))]
+pub mod stream;
+#[cfg(test)]
+mod test_utils;
+pub mod transaction_resolver;
+pub mod transaction_tracker;
 pub mod types;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
 
+use crate::primitives::StreamerMessage;
+pub use explain::explain;
 use indexer_rule_type::indexer_rule::{IndexerRule, MatchingRule};
-use near_lake_framework::near_indexer_primitives::StreamerMessage;
-use types::indexer_rule_match::{ChainId, IndexerRuleMatch};
+use types::indexer_rule_match::{ChainId, ExtractionMode, IndexerRuleMatch};
 
 pub async fn reduce_indexer_rule_matches(
     indexer_rule: &IndexerRule,
     streamer_message: &StreamerMessage,
     chain_id: ChainId,
+    extraction_mode: ExtractionMode,
 ) -> anyhow::Result<Vec<IndexerRuleMatch>> {
     Ok(match &indexer_rule.matching_rule {
+        MatchingRule::StateChangeAccountBalance { .. }
+        | MatchingRule::StateChangeContractData { .. }
+        | MatchingRule::StateChangeAccessKey { .. } => {
+            state_changes_reducer::reduce_indexer_rule_matches_from_state_changes(
+                indexer_rule,
+                streamer_message,
+                chain_id,
+                extraction_mode,
+            )
+        }
+        MatchingRule::All { .. } | MatchingRule::Any { .. } | MatchingRule::Not { .. }
+            if matcher::is_state_change_rule(&indexer_rule.matching_rule) =>
+        {
+            state_changes_reducer::reduce_indexer_rule_matches_from_state_changes(
+                indexer_rule,
+                streamer_message,
+                chain_id,
+                extraction_mode,
+            )
+        }
         MatchingRule::ActionAny { .. }
         | MatchingRule::ActionFunctionCall { .. }
-        | MatchingRule::Event { .. } => {
+        | MatchingRule::ActionFunctionCallArgs { .. }
+        | MatchingRule::ActionDeployContract { .. }
+        | MatchingRule::ActionAddKey { .. }
+        | MatchingRule::ActionDeleteKey { .. }
+        | MatchingRule::ActionStake { .. }
+        | MatchingRule::ActionCreateAccount { .. }
+        | MatchingRule::ActionDeleteAccount { .. }
+        | MatchingRule::ActionTransfer { .. }
+        | MatchingRule::Event { .. }
+        | MatchingRule::EventData { .. }
+        | MatchingRule::All { .. }
+        | MatchingRule::Any { .. }
+        | MatchingRule::Not { .. } => {
             outcomes_reducer::reduce_indexer_rule_matches_from_outcomes(
                 indexer_rule,
                 streamer_message,
                 chain_id,
+                extraction_mode,
             )
             .await?
         }
+        MatchingRule::AnyBlock => {
+            vec![outcomes_reducer::build_indexer_rule_match_for_block(
+                indexer_rule,
+                streamer_message,
+                chain_id,
+            )]
+        }
     })
 }
 
@@ -30,16 +85,57 @@ pub fn reduce_indexer_rule_matches_sync(
     indexer_rule: &IndexerRule,
     streamer_message: &StreamerMessage,
     chain_id: ChainId,
+    extraction_mode: ExtractionMode,
 ) -> Vec<IndexerRuleMatch> {
     match &indexer_rule.matching_rule {
+        MatchingRule::StateChangeAccountBalance { .. }
+        | MatchingRule::StateChangeContractData { .. }
+        | MatchingRule::StateChangeAccessKey { .. } => {
+            state_changes_reducer::reduce_indexer_rule_matches_from_state_changes(
+                indexer_rule,
+                streamer_message,
+                chain_id,
+                extraction_mode,
+            )
+        }
+        MatchingRule::All { .. } | MatchingRule::Any { .. } | MatchingRule::Not { .. }
+            if matcher::is_state_change_rule(&indexer_rule.matching_rule) =>
+        {
+            state_changes_reducer::reduce_indexer_rule_matches_from_state_changes(
+                indexer_rule,
+                streamer_message,
+                chain_id,
+                extraction_mode,
+            )
+        }
         MatchingRule::ActionAny { .. }
         | MatchingRule::ActionFunctionCall { .. }
-        | MatchingRule::Event { .. } => {
+        | MatchingRule::ActionFunctionCallArgs { .. }
+        | MatchingRule::ActionDeployContract { .. }
+        | MatchingRule::ActionAddKey { .. }
+        | MatchingRule::ActionDeleteKey { .. }
+        | MatchingRule::ActionStake { .. }
+        | MatchingRule::ActionCreateAccount { .. }
+        | MatchingRule::ActionDeleteAccount { .. }
+        | MatchingRule::ActionTransfer { .. }
+        | MatchingRule::Event { .. }
+        | MatchingRule::EventData { .. }
+        | MatchingRule::All { .. }
+        | MatchingRule::Any { .. }
+        | MatchingRule::Not { .. } => {
             outcomes_reducer_sync::reduce_indexer_rule_matches_from_outcomes(
                 indexer_rule,
                 streamer_message,
                 chain_id,
+                extraction_mode,
             )
         }
+        MatchingRule::AnyBlock => {
+            vec![outcomes_reducer::build_indexer_rule_match_for_block(
+                indexer_rule,
+                streamer_message,
+                chain_id,
+            )]
+        }
     }
 }
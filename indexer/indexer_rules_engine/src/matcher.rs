@@ -1,20 +1,47 @@
-use near_lake_framework::near_indexer_primitives::{
-    views::{ActionView, ExecutionStatusView, ReceiptEnumView},
+use crate::primitives::{
+    views::{
+        AccessKeyPermissionView, ActionView, ExecutionStatusView, ReceiptEnumView,
+        StateChangeCauseView, StateChangeValueView, StateChangeWithCauseView,
+    },
     IndexerExecutionOutcomeWithReceipt,
 };
 
+use crate::predicates;
 use crate::types::events::Event;
-use indexer_rule_type::indexer_rule::{MatchingRule, Status};
+use indexer_rule_type::indexer_rule::{
+    AccessKeyChange, AccessKeyPermission, JsonPredicate, MatchingRule, Status,
+};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use wildmatch::WildMatch;
 
 pub fn matches(
     matching_rule: &MatchingRule,
     receipt_execution_outcome: &IndexerExecutionOutcomeWithReceipt,
+) -> bool {
+    matches_compiled(
+        matching_rule,
+        receipt_execution_outcome,
+        &WildcardPatterns::default(),
+    )
+}
+
+/// Same as [matches], with the wildcard patterns of the rule taken from `patterns`
+pub fn matches_compiled(
+    matching_rule: &MatchingRule,
+    receipt_execution_outcome: &IndexerExecutionOutcomeWithReceipt,
+    patterns: &WildcardPatterns,
 ) -> bool {
     match matching_rule {
         MatchingRule::ActionAny {
             affected_account_id,
             status,
-        } => match_action_any(affected_account_id, status, receipt_execution_outcome),
+        } => match_action_any(
+            affected_account_id,
+            status,
+            receipt_execution_outcome,
+            patterns,
+        ),
         MatchingRule::ActionFunctionCall {
             affected_account_id,
             status,
@@ -23,7 +50,22 @@ pub fn matches(
             affected_account_id,
             status,
             function,
+            &[],
+            receipt_execution_outcome,
+            patterns,
+        ),
+        MatchingRule::ActionFunctionCallArgs {
+            affected_account_id,
+            status,
+            function,
+            args,
+        } => match_action_function_call(
+            affected_account_id,
+            status,
+            function,
+            args,
             receipt_execution_outcome,
+            patterns,
         ),
         MatchingRule::Event {
             contract_account_id,
@@ -35,8 +77,258 @@ pub fn matches(
             event,
             standard,
             version,
+            &[],
             receipt_execution_outcome,
+            patterns,
         ),
+        MatchingRule::EventData {
+            contract_account_id,
+            event,
+            standard,
+            version,
+            data,
+        } => match_event(
+            contract_account_id,
+            event,
+            standard,
+            version,
+            data,
+            receipt_execution_outcome,
+            patterns,
+        ),
+        MatchingRule::All { matching_rules } => matching_rules.iter().all(|matching_rule| {
+            matches_compiled(matching_rule, receipt_execution_outcome, patterns)
+        }),
+        MatchingRule::Any { matching_rules } => matching_rules.iter().any(|matching_rule| {
+            matches_compiled(matching_rule, receipt_execution_outcome, patterns)
+        }),
+        MatchingRule::Not { matching_rules } => !matching_rules.iter().any(|matching_rule| {
+            matches_compiled(matching_rule, receipt_execution_outcome, patterns)
+        }),
+        MatchingRule::AnyBlock => true,
+        MatchingRule::StateChangeAccountBalance { .. }
+        | MatchingRule::StateChangeContractData { .. }
+        | MatchingRule::StateChangeAccessKey { .. } => false,
+        MatchingRule::ActionDeployContract {
+            affected_account_id,
+            status,
+        } => match_action_kind(
+            affected_account_id,
+            status,
+            receipt_execution_outcome,
+            patterns,
+            |action| matches!(action, ActionView::DeployContract { .. }),
+        ),
+        MatchingRule::ActionAddKey {
+            affected_account_id,
+            status,
+            permission,
+        } => match_action_kind(
+            affected_account_id,
+            status,
+            receipt_execution_outcome,
+            patterns,
+            |action| match action {
+                ActionView::AddKey { access_key, .. } => {
+                    match_access_key_permission(permission, &access_key.permission)
+                }
+                _ => false,
+            },
+        ),
+        MatchingRule::ActionDeleteKey {
+            affected_account_id,
+            status,
+        } => match_action_kind(
+            affected_account_id,
+            status,
+            receipt_execution_outcome,
+            patterns,
+            |action| matches!(action, ActionView::DeleteKey { .. }),
+        ),
+        MatchingRule::ActionStake {
+            affected_account_id,
+            status,
+        } => match_action_kind(
+            affected_account_id,
+            status,
+            receipt_execution_outcome,
+            patterns,
+            |action| matches!(action, ActionView::Stake { .. }),
+        ),
+        MatchingRule::ActionCreateAccount {
+            affected_account_id,
+            status,
+        } => match_action_kind(
+            affected_account_id,
+            status,
+            receipt_execution_outcome,
+            patterns,
+            |action| matches!(action, ActionView::CreateAccount),
+        ),
+        MatchingRule::ActionDeleteAccount {
+            affected_account_id,
+            status,
+        } => match_action_kind(
+            affected_account_id,
+            status,
+            receipt_execution_outcome,
+            patterns,
+            |action| matches!(action, ActionView::DeleteAccount { .. }),
+        ),
+        MatchingRule::ActionTransfer {
+            affected_account_id,
+            status,
+        } => match_action_kind(
+            affected_account_id,
+            status,
+            receipt_execution_outcome,
+            patterns,
+            |action| matches!(action, ActionView::Transfer { .. }),
+        ),
+    }
+}
+
+pub fn matches_state_change(
+    matching_rule: &MatchingRule,
+    state_change: &StateChangeWithCauseView,
+) -> bool {
+    matches_state_change_compiled(matching_rule, state_change, &WildcardPatterns::default())
+}
+
+/// Same as [matches_state_change], with the wildcard patterns of the rule taken from `patterns`
+pub fn matches_state_change_compiled(
+    matching_rule: &MatchingRule,
+    state_change: &StateChangeWithCauseView,
+    patterns: &WildcardPatterns,
+) -> bool {
+    match matching_rule {
+        MatchingRule::StateChangeAccountBalance {
+            affected_account_id,
+        } => match &state_change.value {
+            StateChangeValueView::AccountUpdate { account_id, .. } => {
+                patterns.match_account_id(affected_account_id, account_id)
+            }
+            _ => false,
+        },
+        MatchingRule::StateChangeContractData {
+            affected_account_id,
+            key_prefix,
+        } => match &state_change.value {
+            StateChangeValueView::DataUpdate {
+                account_id, key, ..
+            }
+            | StateChangeValueView::DataDeletion { account_id, key } => {
+                patterns.match_account_id(affected_account_id, account_id)
+                    && AsRef::<[u8]>::as_ref(key).starts_with(key_prefix.as_bytes())
+            }
+            _ => false,
+        },
+        MatchingRule::StateChangeAccessKey {
+            affected_account_id,
+            change,
+        } => match (&state_change.value, change) {
+            (StateChangeValueView::AccessKeyUpdate { account_id, .. }, AccessKeyChange::Any)
+            | (
+                StateChangeValueView::AccessKeyDeletion { account_id, .. },
+                AccessKeyChange::Any | AccessKeyChange::Removed,
+            ) => patterns.match_account_id(affected_account_id, account_id),
+            (
+                StateChangeValueView::AccessKeyUpdate {
+                    account_id,
+                    access_key,
+                    ..
+                },
+                AccessKeyChange::Added,
+            ) => {
+                // nonce bumps and allowance charges of signed transactions are access key
+                // updates too, caused by processing the transaction rather than a receipt
+                let is_added_key = access_key.nonce == 0
+                    || matches!(
+                        state_change.cause,
+                        StateChangeCauseView::ReceiptProcessing { .. }
+                    );
+                is_added_key && patterns.match_account_id(affected_account_id, account_id)
+            }
+            _ => false,
+        },
+        MatchingRule::All { matching_rules } => matching_rules.iter().all(|matching_rule| {
+            matches_state_change_compiled(matching_rule, state_change, patterns)
+        }),
+        MatchingRule::Any { matching_rules } => matching_rules.iter().any(|matching_rule| {
+            matches_state_change_compiled(matching_rule, state_change, patterns)
+        }),
+        MatchingRule::Not { matching_rules } => !matching_rules.iter().any(|matching_rule| {
+            matches_state_change_compiled(matching_rule, state_change, patterns)
+        }),
+        MatchingRule::ActionAny { .. }
+        | MatchingRule::ActionFunctionCall { .. }
+        | MatchingRule::Event { .. }
+        | MatchingRule::EventData { .. }
+        | MatchingRule::AnyBlock
+        | MatchingRule::ActionDeployContract { .. }
+        | MatchingRule::ActionAddKey { .. }
+        | MatchingRule::ActionDeleteKey { .. }
+        | MatchingRule::ActionStake { .. }
+        | MatchingRule::ActionCreateAccount { .. }
+        | MatchingRule::ActionDeleteAccount { .. }
+        | MatchingRule::ActionTransfer { .. }
+        | MatchingRule::ActionFunctionCallArgs { .. } => false,
+    }
+}
+
+/// State change rules, and rules composed only of them, are matched against `shard.state_changes`
+/// instead of the receipt execution outcomes
+pub fn is_state_change_rule(matching_rule: &MatchingRule) -> bool {
+    match matching_rule {
+        MatchingRule::StateChangeAccountBalance { .. }
+        | MatchingRule::StateChangeContractData { .. }
+        | MatchingRule::StateChangeAccessKey { .. } => true,
+        MatchingRule::All { matching_rules }
+        | MatchingRule::Any { matching_rules }
+        | MatchingRule::Not { matching_rules } => matching_rules.iter().all(is_state_change_rule),
+        MatchingRule::ActionAny { .. }
+        | MatchingRule::ActionFunctionCall { .. }
+        | MatchingRule::Event { .. }
+        | MatchingRule::EventData { .. }
+        | MatchingRule::AnyBlock
+        | MatchingRule::ActionDeployContract { .. }
+        | MatchingRule::ActionAddKey { .. }
+        | MatchingRule::ActionDeleteKey { .. }
+        | MatchingRule::ActionStake { .. }
+        | MatchingRule::ActionCreateAccount { .. }
+        | MatchingRule::ActionDeleteAccount { .. }
+        | MatchingRule::ActionTransfer { .. }
+        | MatchingRule::ActionFunctionCallArgs { .. } => false,
+    }
+}
+
+/// Returns the leaf rule which made the receipt match, used to decide which payload to build.
+/// Rules nested under `Not` never produce a positive match so they are never returned.
+pub fn matched_leaf_rule<'a>(
+    matching_rule: &'a MatchingRule,
+    receipt_execution_outcome: &IndexerExecutionOutcomeWithReceipt,
+) -> Option<&'a MatchingRule> {
+    match matching_rule {
+        MatchingRule::All { matching_rules } => {
+            if matches(matching_rule, receipt_execution_outcome) {
+                matching_rules.iter().find_map(|matching_rule| {
+                    matched_leaf_rule(matching_rule, receipt_execution_outcome)
+                })
+            } else {
+                None
+            }
+        }
+        MatchingRule::Any { matching_rules } => matching_rules
+            .iter()
+            .find_map(|matching_rule| matched_leaf_rule(matching_rule, receipt_execution_outcome)),
+        MatchingRule::Not { .. } => None,
+        _ => {
+            if matches(matching_rule, receipt_execution_outcome) {
+                Some(matching_rule)
+            } else {
+                None
+            }
+        }
     }
 }
 
@@ -44,8 +336,9 @@ fn match_action_any(
     account_id: &str,
     status: &Status,
     outcome_with_receipt: &IndexerExecutionOutcomeWithReceipt,
+    patterns: &WildcardPatterns,
 ) -> bool {
-    if match_account(account_id, outcome_with_receipt) {
+    if patterns.match_account(account_id, outcome_with_receipt) {
         return match_status(
             status,
             &outcome_with_receipt.execution_outcome.outcome.status,
@@ -58,18 +351,40 @@ fn match_action_function_call(
     account_id: &str,
     status: &Status,
     function: &str,
+    args_predicates: &[JsonPredicate],
+    outcome_with_receipt: &IndexerExecutionOutcomeWithReceipt,
+    patterns: &WildcardPatterns,
+) -> bool {
+    match_action_kind(
+        account_id,
+        status,
+        outcome_with_receipt,
+        patterns,
+        |action| {
+            if let ActionView::FunctionCall {
+                method_name, args, ..
+            } = action
+            {
+                patterns.matches(function, method_name)
+                    && (args_predicates.is_empty()
+                        || predicates::matches_json_bytes(args_predicates, args))
+            } else {
+                false
+            }
+        },
+    )
+}
+
+fn match_action_kind(
+    account_id: &str,
+    status: &Status,
     outcome_with_receipt: &IndexerExecutionOutcomeWithReceipt,
+    patterns: &WildcardPatterns,
+    is_matching_action: impl Fn(&ActionView) -> bool,
 ) -> bool {
-    if match_account(account_id, outcome_with_receipt) {
+    if patterns.match_account(account_id, outcome_with_receipt) {
         if let ReceiptEnumView::Action { actions, .. } = &outcome_with_receipt.receipt.receipt {
-            let is_any_matching_function_call = actions.iter().any(|action| {
-                if let ActionView::FunctionCall { method_name, .. } = action {
-                    wildmatch::WildMatch::new(function).matches(method_name)
-                } else {
-                    false
-                }
-            });
-            if is_any_matching_function_call {
+            if actions.iter().any(is_matching_action) {
                 return match_status(
                     status,
                     &outcome_with_receipt.execution_outcome.outcome.status,
@@ -82,51 +397,264 @@ fn match_action_function_call(
     false
 }
 
+fn match_access_key_permission(
+    permission: &AccessKeyPermission,
+    access_key_permission: &AccessKeyPermissionView,
+) -> bool {
+    match permission {
+        AccessKeyPermission::Any => true,
+        AccessKeyPermission::FullAccess => {
+            matches!(access_key_permission, AccessKeyPermissionView::FullAccess)
+        }
+        AccessKeyPermission::FunctionCall => {
+            matches!(
+                access_key_permission,
+                AccessKeyPermissionView::FunctionCall { .. }
+            )
+        }
+    }
+}
+
 fn match_event(
     account_id: &str,
     event: &str,
     standard: &str,
     version: &str,
+    data_predicates: &[JsonPredicate],
     outcome_with_receipt: &IndexerExecutionOutcomeWithReceipt,
+    patterns: &WildcardPatterns,
 ) -> bool {
-    if match_account(account_id, outcome_with_receipt) {
-        outcome_with_receipt
-            .execution_outcome
-            .outcome
-            .logs
-            .iter()
-            .filter_map(|log| Event::from_log(log).ok())
-            .any(|near_event| {
-                vec![
-                    wildmatch::WildMatch::new(event).matches(&near_event.event),
-                    wildmatch::WildMatch::new(standard).matches(&near_event.standard),
-                    wildmatch::WildMatch::new(version).matches(&near_event.version),
-                ]
-                .into_iter()
-                .all(|val| val)
-            })
+    if patterns.match_account(account_id, outcome_with_receipt) {
+        matching_events_compiled(
+            event,
+            standard,
+            version,
+            data_predicates,
+            outcome_with_receipt,
+            patterns,
+        )
+        .next()
+        .is_some()
     } else {
         false
     }
 }
 
-fn match_account(
-    account_id: &str,
-    outcome_with_receipt: &IndexerExecutionOutcomeWithReceipt,
+/// Events logged by the receipt which satisfy the rule, used by the payload builders
+/// so the emitted events are the ones that matched
+pub fn matching_events<'a>(
+    event: &'a str,
+    standard: &'a str,
+    version: &'a str,
+    data_predicates: &'a [JsonPredicate],
+    outcome_with_receipt: &'a IndexerExecutionOutcomeWithReceipt,
+) -> impl Iterator<Item = Event> + 'a {
+    let mut patterns = WildcardPatterns::default();
+    for pattern in [event, standard, version] {
+        patterns.compile(pattern);
+    }
+    matching_events_compiled(
+        event,
+        standard,
+        version,
+        data_predicates,
+        outcome_with_receipt,
+        patterns,
+    )
+}
+
+/// Same as [matching_events], with the wildcard patterns of the rule taken from `patterns`
+fn matching_events_compiled<'a>(
+    event: &'a str,
+    standard: &'a str,
+    version: &'a str,
+    data_predicates: &'a [JsonPredicate],
+    outcome_with_receipt: &'a IndexerExecutionOutcomeWithReceipt,
+    patterns: impl Borrow<WildcardPatterns> + 'a,
+) -> impl Iterator<Item = Event> + 'a {
+    outcome_with_receipt
+        .execution_outcome
+        .outcome
+        .logs
+        .iter()
+        .filter_map(|log| Event::from_log(log).ok())
+        .filter(move |near_event| {
+            is_matching_event(
+                event,
+                standard,
+                version,
+                data_predicates,
+                near_event,
+                patterns.borrow(),
+            )
+        })
+}
+
+fn is_matching_event(
+    event: &str,
+    standard: &str,
+    version: &str,
+    data_predicates: &[JsonPredicate],
+    near_event: &Event,
+    patterns: &WildcardPatterns,
 ) -> bool {
-    match account_id {
-        x if x.contains(',') => x
-            .split(',')
-            .any(|sub_account_id| match_account(sub_account_id.trim(), outcome_with_receipt)),
-        _ => {
-            wildmatch::WildMatch::new(account_id).matches(&outcome_with_receipt.receipt.receiver_id)
-                || wildmatch::WildMatch::new(account_id)
-                    .matches(&outcome_with_receipt.receipt.predecessor_id)
+    patterns.matches(event, &near_event.event)
+        && patterns.matches(standard, &near_event.standard)
+        && patterns.matches(version, &near_event.version)
+        && (data_predicates.is_empty()
+            || near_event
+                .data
+                .as_ref()
+                .is_some_and(|data| predicates::matches_json(data_predicates, data)))
+}
+
+/// Wildcard patterns of matching rules compiled ahead of matching, e.g. once per [crate::rule_set::RuleSet].
+/// Patterns which were not compiled ahead are compiled every time they are matched.
+#[derive(Debug, Default)]
+pub struct WildcardPatterns {
+    compiled_patterns: HashMap<String, WildMatch>,
+}
+
+impl WildcardPatterns {
+    pub fn new<'a>(matching_rules: impl IntoIterator<Item = &'a MatchingRule>) -> Self {
+        let mut patterns = Self::default();
+        for matching_rule in matching_rules {
+            patterns.compile_rule(matching_rule);
         }
+        patterns
     }
+
+    fn compile_rule(&mut self, matching_rule: &MatchingRule) {
+        match matching_rule {
+            MatchingRule::ActionAny {
+                affected_account_id,
+                ..
+            }
+            | MatchingRule::ActionDeployContract {
+                affected_account_id,
+                ..
+            }
+            | MatchingRule::ActionAddKey {
+                affected_account_id,
+                ..
+            }
+            | MatchingRule::ActionDeleteKey {
+                affected_account_id,
+                ..
+            }
+            | MatchingRule::ActionStake {
+                affected_account_id,
+                ..
+            }
+            | MatchingRule::ActionCreateAccount {
+                affected_account_id,
+                ..
+            }
+            | MatchingRule::ActionDeleteAccount {
+                affected_account_id,
+                ..
+            }
+            | MatchingRule::ActionTransfer {
+                affected_account_id,
+                ..
+            }
+            | MatchingRule::StateChangeAccountBalance {
+                affected_account_id,
+            }
+            | MatchingRule::StateChangeContractData {
+                affected_account_id,
+                ..
+            }
+            | MatchingRule::StateChangeAccessKey {
+                affected_account_id,
+                ..
+            } => self.compile_account_ids(affected_account_id),
+            MatchingRule::ActionFunctionCall {
+                affected_account_id,
+                function,
+                ..
+            }
+            | MatchingRule::ActionFunctionCallArgs {
+                affected_account_id,
+                function,
+                ..
+            } => {
+                self.compile_account_ids(affected_account_id);
+                self.compile(function);
+            }
+            MatchingRule::Event {
+                contract_account_id,
+                event,
+                standard,
+                version,
+            }
+            | MatchingRule::EventData {
+                contract_account_id,
+                event,
+                standard,
+                version,
+                ..
+            } => {
+                self.compile_account_ids(contract_account_id);
+                self.compile(event);
+                self.compile(standard);
+                self.compile(version);
+            }
+            MatchingRule::All { matching_rules }
+            | MatchingRule::Any { matching_rules }
+            | MatchingRule::Not { matching_rules } => {
+                for matching_rule in matching_rules.iter() {
+                    self.compile_rule(matching_rule);
+                }
+            }
+            MatchingRule::AnyBlock => {}
+        }
+    }
+
+    fn compile_account_ids(&mut self, account_id_pattern: &str) {
+        for sub_account_id in account_id_pattern.split(',') {
+            self.compile(sub_account_id.trim());
+        }
+    }
+
+    fn compile(&mut self, pattern: &str) {
+        if !self.compiled_patterns.contains_key(pattern) {
+            self.compiled_patterns
+                .insert(pattern.to_string(), WildMatch::new(pattern));
+        }
+    }
+
+    fn matches(&self, pattern: &str, value: &str) -> bool {
+        match self.compiled_patterns.get(pattern) {
+            Some(compiled_pattern) => compiled_pattern.matches(value),
+            None => WildMatch::new(pattern).matches(value),
+        }
+    }
+
+    fn match_account(
+        &self,
+        account_id: &str,
+        outcome_with_receipt: &IndexerExecutionOutcomeWithReceipt,
+    ) -> bool {
+        self.match_account_id(account_id, &outcome_with_receipt.receipt.receiver_id)
+            || self.match_account_id(account_id, &outcome_with_receipt.receipt.predecessor_id)
+    }
+
+    fn match_account_id(&self, account_id_pattern: &str, account_id: &str) -> bool {
+        account_id_pattern
+            .split(',')
+            .any(|sub_account_id| self.matches(sub_account_id.trim(), account_id))
+    }
+}
+
+pub(crate) fn match_account_id(account_id_pattern: &str, account_id: &str) -> bool {
+    WildcardPatterns::default().match_account_id(account_id_pattern, account_id)
 }
 
-fn match_status(status: &Status, execution_outcome_status: &ExecutionStatusView) -> bool {
+pub(crate) fn match_status(
+    status: &Status,
+    execution_outcome_status: &ExecutionStatusView,
+) -> bool {
     match status {
         Status::Any => true,
         Status::Success => matches!(
@@ -139,3 +667,162 @@ fn match_status(status: &Status, execution_outcome_status: &ExecutionStatusView)
         ),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{matches, matching_events};
+    use crate::test_utils::outcome_with_actions;
+    use indexer_rule_type::indexer_rule::{
+        AccessKeyPermission, JsonPredicate, MatchingRule, PredicateOperator, Status,
+    };
+
+    #[test]
+    fn match_add_key_permission() {
+        let outcome = outcome_with_actions(serde_json::json!([{
+            "AddKey": {
+                "public_key": "ed25519:CE3QAXyVLeScmY9YeEyR3Tw9yXfjBPzFLzroTranYtVb",
+                "access_key": { "nonce": 0, "permission": "FullAccess" }
+            }
+        }]));
+
+        let full_access_rule = MatchingRule::ActionAddKey {
+            affected_account_id: "alice.near".to_string(),
+            status: Status::Success,
+            permission: AccessKeyPermission::FullAccess,
+        };
+        let function_call_rule = MatchingRule::ActionAddKey {
+            affected_account_id: "alice.near".to_string(),
+            status: Status::Success,
+            permission: AccessKeyPermission::FunctionCall,
+        };
+        let failed_rule = MatchingRule::ActionAddKey {
+            affected_account_id: "alice.near".to_string(),
+            status: Status::Fail,
+            permission: AccessKeyPermission::Any,
+        };
+
+        assert!(matches(&full_access_rule, &outcome));
+        assert!(!matches(&function_call_rule, &outcome));
+        assert!(!matches(&failed_rule, &outcome));
+    }
+
+    #[test]
+    fn match_action_kinds() {
+        let outcome = outcome_with_actions(serde_json::json!([
+            "CreateAccount",
+            { "Transfer": { "deposit": "1000000000000000000000000" } }
+        ]));
+
+        let create_account_rule = MatchingRule::ActionCreateAccount {
+            affected_account_id: "*.near".to_string(),
+            status: Status::Any,
+        };
+        let transfer_rule = MatchingRule::ActionTransfer {
+            affected_account_id: "bob.near".to_string(),
+            status: Status::Success,
+        };
+        let deploy_rule = MatchingRule::ActionDeployContract {
+            affected_account_id: "alice.near".to_string(),
+            status: Status::Any,
+        };
+        let other_account_rule = MatchingRule::ActionTransfer {
+            affected_account_id: "carol.near".to_string(),
+            status: Status::Any,
+        };
+
+        assert!(matches(&create_account_rule, &outcome));
+        assert!(matches(&transfer_rule, &outcome));
+        assert!(!matches(&deploy_rule, &outcome));
+        assert!(!matches(&other_account_rule, &outcome));
+    }
+
+    #[test]
+    fn match_function_call_args() {
+        let json_args_outcome = outcome_with_actions(serde_json::json!([{
+            "FunctionCall": {
+                "method_name": "ft_transfer",
+                "args": "eyJyZWNlaXZlcl9pZCI6IngubmVhciIsImFtb3VudCI6IjUifQ==",
+                "gas": 30000000000000u64,
+                "deposit": "1"
+            }
+        }]));
+        let binary_args_outcome = outcome_with_actions(serde_json::json!([{
+            "FunctionCall": {
+                "method_name": "ft_transfer",
+                "args": "AJ+Slg==",
+                "gas": 30000000000000u64,
+                "deposit": "1"
+            }
+        }]));
+
+        let args_rule = MatchingRule::ActionFunctionCallArgs {
+            affected_account_id: "alice.near".to_string(),
+            status: Status::Any,
+            function: "ft_*".to_string(),
+            args: vec![JsonPredicate {
+                path: "receiver_id".to_string(),
+                operator: PredicateOperator::Equal,
+                value: "x.near".to_string(),
+            }],
+        };
+        let other_receiver_rule = MatchingRule::ActionFunctionCallArgs {
+            affected_account_id: "alice.near".to_string(),
+            status: Status::Any,
+            function: "ft_*".to_string(),
+            args: vec![JsonPredicate {
+                path: "receiver_id".to_string(),
+                operator: PredicateOperator::Equal,
+                value: "y.near".to_string(),
+            }],
+        };
+
+        assert!(matches(&args_rule, &json_args_outcome));
+        assert!(!matches(&other_receiver_rule, &json_args_outcome));
+        assert!(!matches(&args_rule, &binary_args_outcome));
+    }
+
+    #[test]
+    fn match_event_data() {
+        let mut outcome = outcome_with_actions(serde_json::json!([]));
+        outcome.execution_outcome.outcome.logs = vec![
+            r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_transfer","data":[{"old_owner_id":"alice.near","new_owner_id":"bob.near","token_ids":["1"]}]}"#.to_string(),
+            r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_transfer","data":[{"old_owner_id":"alice.near","new_owner_id":"carol.near","token_ids":["2","3"]}]}"#.to_string(),
+        ];
+
+        let rule_for_predicates = |data: Vec<JsonPredicate>| MatchingRule::EventData {
+            contract_account_id: "alice.near".to_string(),
+            standard: "nep171".to_string(),
+            version: "*".to_string(),
+            event: "nft_transfer".to_string(),
+            data,
+        };
+        let token_predicate = JsonPredicate {
+            path: "[*].token_ids".to_string(),
+            operator: PredicateOperator::Contains,
+            value: "3".to_string(),
+        };
+        let missing_token_predicate = JsonPredicate {
+            path: "[*].token_ids".to_string(),
+            operator: PredicateOperator::Contains,
+            value: "4".to_string(),
+        };
+
+        assert!(matches(
+            &rule_for_predicates(vec![token_predicate.clone()]),
+            &outcome
+        ));
+        assert!(!matches(
+            &rule_for_predicates(vec![missing_token_predicate]),
+            &outcome
+        ));
+
+        let matched_event =
+            matching_events("nft_transfer", "nep171", "*", &[token_predicate], &outcome)
+                .next()
+                .unwrap();
+        assert_eq!(
+            matched_event.data.unwrap()[0]["new_owner_id"],
+            serde_json::json!("carol.near")
+        );
+    }
+}
@@ -1,17 +1,19 @@
 use futures::future::try_join_all;
 
 use crate::matcher;
-use crate::types::events::Event;
-use crate::types::indexer_rule_match::{ChainId, IndexerRuleMatch, IndexerRuleMatchPayload};
-use indexer_rule_type::indexer_rule::{IndexerRule, MatchingRule};
-use near_lake_framework::near_indexer_primitives::{
-    IndexerExecutionOutcomeWithReceipt, StreamerMessage,
+use crate::primitives::{IndexerExecutionOutcomeWithReceipt, StreamerMessage};
+use crate::types::indexer_rule_match::{
+    ChainId, ExtractionMode, IndexerRuleMatch, IndexerRuleMatchPayload,
 };
+use crate::types::receipts::ReceiptDetails;
+use crate::types::standard_events;
+use indexer_rule_type::indexer_rule::{IndexerRule, MatchingRule, PayloadEnrichment};
 
 pub async fn reduce_indexer_rule_matches_from_outcomes(
     indexer_rule: &IndexerRule,
     streamer_message: &StreamerMessage,
     chain_id: ChainId,
+    extraction_mode: ExtractionMode,
 ) -> anyhow::Result<Vec<IndexerRuleMatch>> {
     let build_indexer_rule_match_futures = streamer_message
         .shards
@@ -20,98 +22,158 @@ pub async fn reduce_indexer_rule_matches_from_outcomes(
             shard
                 .receipt_execution_outcomes
                 .iter()
-                // future: when extracting Actions, Events, etc this will be a filter operation
-                .find(|receipt_execution_outcome| {
+                .filter(|receipt_execution_outcome| {
                     matcher::matches(&indexer_rule.matching_rule, receipt_execution_outcome)
                 })
+                .take(extraction_mode.limit())
         })
         .map(|receipt_execution_outcome| {
-            build_indexer_rule_match(
+            build_indexer_rule_matches(
                 indexer_rule,
                 receipt_execution_outcome,
                 streamer_message.block.header.hash.to_string(),
                 streamer_message.block.header.height,
                 chain_id.clone(),
+                extraction_mode,
             )
         });
 
-    try_join_all(build_indexer_rule_match_futures).await
+    Ok(try_join_all(build_indexer_rule_match_futures)
+        .await?
+        .into_iter()
+        .flatten()
+        .collect())
 }
 
-async fn build_indexer_rule_match(
+/// Builds the single match emitted for a block by rules which don't look into its receipts
+pub fn build_indexer_rule_match_for_block(
+    indexer_rule: &IndexerRule,
+    streamer_message: &StreamerMessage,
+    chain_id: ChainId,
+) -> IndexerRuleMatch {
+    IndexerRuleMatch {
+        chain_id,
+        indexer_rule_id: indexer_rule.id,
+        indexer_rule_name: indexer_rule.name.clone(),
+        payload: IndexerRuleMatchPayload::Block {
+            block_hash: streamer_message.block.header.hash.to_string(),
+        },
+        block_height: streamer_message.block.header.height,
+    }
+}
+
+async fn build_indexer_rule_matches(
     indexer_rule: &IndexerRule,
     receipt_execution_outcome: &IndexerExecutionOutcomeWithReceipt,
     block_header_hash: String,
     block_height: u64,
     chain_id: ChainId,
-) -> anyhow::Result<IndexerRuleMatch> {
-    Ok(IndexerRuleMatch {
+    extraction_mode: ExtractionMode,
+) -> anyhow::Result<Vec<IndexerRuleMatch>> {
+    Ok(build_indexer_rule_match_payloads(
+        indexer_rule,
+        receipt_execution_outcome,
+        block_header_hash,
+        extraction_mode,
+    )
+    .into_iter()
+    .map(|payload| IndexerRuleMatch {
         chain_id: chain_id.clone(),
         indexer_rule_id: indexer_rule.id,
         indexer_rule_name: indexer_rule.name.clone(),
-        payload: build_indexer_rule_match_payload(
-            indexer_rule,
-            receipt_execution_outcome,
-            block_header_hash,
-        ),
+        payload,
         block_height,
     })
+    .collect())
 }
 
-fn build_indexer_rule_match_payload(
+fn build_indexer_rule_match_payloads(
     indexer_rule: &IndexerRule,
     receipt_execution_outcome: &IndexerExecutionOutcomeWithReceipt,
     block_header_hash: String,
-) -> IndexerRuleMatchPayload {
+    extraction_mode: ExtractionMode,
+) -> Vec<IndexerRuleMatchPayload> {
     // future enhancement will extract and enrich fields from block & context as
     //   specified in the indexer function config.
+    // Transaction hashes are resolved for the whole block by `ReceiptTransactionHashes::populate`
     let transaction_hash = None;
 
-    match &indexer_rule.matching_rule {
-        MatchingRule::ActionAny { .. } | MatchingRule::ActionFunctionCall { .. } => {
-            IndexerRuleMatchPayload::Actions {
-                block_hash: block_header_hash.to_string(),
-                receipt_id: receipt_execution_outcome.receipt.receipt_id.to_string(),
-                transaction_hash,
-            }
-        }
+    // composed rules build the payload of the nested rule which matched
+    let matching_rule =
+        matcher::matched_leaf_rule(&indexer_rule.matching_rule, receipt_execution_outcome)
+            .unwrap_or(&indexer_rule.matching_rule);
+
+    match matching_rule {
+        MatchingRule::ActionAny { .. }
+        | MatchingRule::ActionFunctionCall { .. }
+        | MatchingRule::All { .. }
+        | MatchingRule::Any { .. }
+        | MatchingRule::Not { .. }
+        | MatchingRule::AnyBlock
+        | MatchingRule::StateChangeAccountBalance { .. }
+        | MatchingRule::StateChangeContractData { .. }
+        | MatchingRule::StateChangeAccessKey { .. }
+        | MatchingRule::ActionDeployContract { .. }
+        | MatchingRule::ActionAddKey { .. }
+        | MatchingRule::ActionDeleteKey { .. }
+        | MatchingRule::ActionStake { .. }
+        | MatchingRule::ActionCreateAccount { .. }
+        | MatchingRule::ActionDeleteAccount { .. }
+        | MatchingRule::ActionTransfer { .. }
+        | MatchingRule::ActionFunctionCallArgs { .. } => vec![IndexerRuleMatchPayload::Actions {
+            block_hash: block_header_hash.to_string(),
+            receipt_id: receipt_execution_outcome.receipt.receipt_id.to_string(),
+            transaction_hash,
+            receipt_details: match indexer_rule.payload_enrichment {
+                PayloadEnrichment::Actions => Some(ReceiptDetails::from_receipt_execution_outcome(
+                    receipt_execution_outcome,
+                )),
+                PayloadEnrichment::None => None,
+            },
+        }],
         MatchingRule::Event {
             event,
             standard,
             version,
             ..
+        }
+        | MatchingRule::EventData {
+            event,
+            standard,
+            version,
+            ..
         } => {
-            let event = receipt_execution_outcome
-                .execution_outcome
-                .outcome
-                .logs
-                .iter()
-                .filter_map(|log| Event::from_log(log).ok())
-                .filter_map(|near_event| {
-                    if vec![
-                        wildmatch::WildMatch::new(event).matches(&near_event.event),
-                        wildmatch::WildMatch::new(standard).matches(&near_event.standard),
-                        wildmatch::WildMatch::new(version).matches(&near_event.version),
-                    ].into_iter().all(|val| val) {
-                        Some(near_event)
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<Event>>()
-                .first()
-                .expect("Failed to get the matched Event itself while building the IndexerRuleMatchPayload")
-                .clone();
-
-            IndexerRuleMatchPayload::Events {
-                block_hash: block_header_hash.to_string(),
-                receipt_id: receipt_execution_outcome.receipt.receipt_id.to_string(),
-                transaction_hash,
-                event: event.event.clone(),
-                standard: event.standard.clone(),
-                version: event.version.clone(),
-                data: event.data.as_ref().map(|data| data.to_string()),
-            }
+            let data_predicates = match matching_rule {
+                MatchingRule::EventData { data, .. } => data.as_slice(),
+                _ => &[],
+            };
+            // one payload per logged event which satisfies the rule
+            matcher::matching_events(
+                event,
+                standard,
+                version,
+                data_predicates,
+                receipt_execution_outcome,
+            )
+            .take(extraction_mode.limit())
+            .map(|event| {
+                let (transfers, validation_error) = standard_events::transfers_of_event(
+                    &event,
+                    receipt_execution_outcome.receipt.receiver_id.as_str(),
+                );
+                IndexerRuleMatchPayload::Events {
+                    block_hash: block_header_hash.to_string(),
+                    receipt_id: receipt_execution_outcome.receipt.receipt_id.to_string(),
+                    transaction_hash: transaction_hash.clone(),
+                    event: event.event,
+                    standard: event.standard,
+                    version: event.version,
+                    data: event.data.as_ref().map(|data| data.to_string()),
+                    transfers,
+                    validation_error,
+                }
+            })
+            .collect()
         }
     }
 }
@@ -119,9 +181,11 @@ fn build_indexer_rule_match_payload(
 #[cfg(test)]
 mod tests {
     use crate::outcomes_reducer::reduce_indexer_rule_matches_from_outcomes;
-    use crate::types::indexer_rule_match::{ChainId, IndexerRuleMatch};
-    use indexer_rule_type::indexer_rule::{IndexerRule, IndexerRuleKind, MatchingRule, Status};
-    use near_lake_framework::near_indexer_primitives::StreamerMessage;
+    use crate::primitives::StreamerMessage;
+    use crate::types::indexer_rule_match::{ChainId, ExtractionMode, IndexerRuleMatch};
+    use indexer_rule_type::indexer_rule::{
+        IndexerRule, IndexerRuleKind, MatchingRule, PayloadEnrichment, Status,
+    };
 
     fn read_local_file(path: &str) -> String {
         std::fs::read_to_string(path).unwrap()
@@ -142,6 +206,7 @@ mod tests {
             },
             id: None,
             name: None,
+            payload_enrichment: PayloadEnrichment::None,
         };
 
         let streamer_message = read_local_streamer_message(93085141);
@@ -149,6 +214,7 @@ mod tests {
             &wildcard_rule,
             &streamer_message,
             ChainId::Testnet,
+            ExtractionMode::FirstPerShard,
         )
         .await
         .unwrap();
@@ -166,6 +232,7 @@ mod tests {
             },
             id: None,
             name: None,
+            payload_enrichment: PayloadEnrichment::None,
         };
 
         let streamer_message = read_local_streamer_message(93085141);
@@ -173,11 +240,12 @@ mod tests {
             &wildcard_rule,
             &streamer_message,
             ChainId::Testnet,
+            ExtractionMode::FirstPerShard,
         )
         .await
         .unwrap();
 
-        assert_eq!(result.len(), 1); // There are two matches, until we add Extraction we are just matching the first one (block matching)
+        assert_eq!(result.len(), 1); // There are two matches, FirstPerShard only extracts the first one (block matching)
     }
 
     #[tokio::test]
@@ -190,6 +258,7 @@ mod tests {
             },
             id: None,
             name: None,
+            payload_enrichment: PayloadEnrichment::None,
         };
 
         let streamer_message = read_local_streamer_message(93085141);
@@ -197,11 +266,12 @@ mod tests {
             &wildcard_rule,
             &streamer_message,
             ChainId::Testnet,
+            ExtractionMode::FirstPerShard,
         )
         .await
         .unwrap();
 
-        assert_eq!(result.len(), 1); // see Extraction note in previous test
+        assert_eq!(result.len(), 1); // see FirstPerShard note in previous test
 
         let wildcard_rule = IndexerRule {
             indexer_rule_kind: IndexerRuleKind::Action,
@@ -211,17 +281,19 @@ mod tests {
             },
             id: None,
             name: None,
+            payload_enrichment: PayloadEnrichment::None,
         };
 
         let result: Vec<IndexerRuleMatch> = reduce_indexer_rule_matches_from_outcomes(
             &wildcard_rule,
             &streamer_message,
             ChainId::Testnet,
+            ExtractionMode::FirstPerShard,
         )
         .await
         .unwrap();
 
-        assert_eq!(result.len(), 1); // see Extraction note in previous test
+        assert_eq!(result.len(), 1); // see FirstPerShard note in previous test
     }
 
     #[tokio::test]
@@ -234,6 +306,7 @@ mod tests {
             },
             id: None,
             name: None,
+            payload_enrichment: PayloadEnrichment::None,
         };
 
         let streamer_message = read_local_streamer_message(93085141);
@@ -241,11 +314,12 @@ mod tests {
             &wildcard_rule,
             &streamer_message,
             ChainId::Testnet,
+            ExtractionMode::FirstPerShard,
         )
         .await
         .unwrap();
 
-        assert_eq!(result.len(), 1); // There are two matches, until we add Extraction we are just matching the first one (block matching)
+        assert_eq!(result.len(), 1); // There are two matches, FirstPerShard only extracts the first one (block matching)
     }
 
     #[tokio::test]
@@ -258,6 +332,164 @@ mod tests {
             },
             id: None,
             name: None,
+            payload_enrichment: PayloadEnrichment::None,
+        };
+
+        let streamer_message = read_local_streamer_message(93085141);
+        let result: Vec<IndexerRuleMatch> = reduce_indexer_rule_matches_from_outcomes(
+            &wildcard_rule,
+            &streamer_message,
+            ChainId::Testnet,
+            ExtractionMode::FirstPerShard,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.len(), 1); // There are two matches, FirstPerShard only extracts the first one (block matching)
+    }
+
+    #[tokio::test]
+    async fn match_all_with_not() {
+        let composed_rule = IndexerRule {
+            indexer_rule_kind: IndexerRuleKind::Action,
+            matching_rule: MatchingRule::All {
+                matching_rules: vec![
+                    MatchingRule::ActionAny {
+                        affected_account_id: "*.nearcrowd.near".to_string(),
+                        status: Status::Success,
+                    },
+                    MatchingRule::Not {
+                        matching_rules: vec![MatchingRule::ActionAny {
+                            affected_account_id: "notintheblockaccount.near".to_string(),
+                            status: Status::Any,
+                        }]
+                        .into(),
+                    },
+                ]
+                .into(),
+            },
+            id: None,
+            name: None,
+            payload_enrichment: PayloadEnrichment::None,
+        };
+
+        let streamer_message = read_local_streamer_message(93085141);
+        let result: Vec<IndexerRuleMatch> = reduce_indexer_rule_matches_from_outcomes(
+            &composed_rule,
+            &streamer_message,
+            ChainId::Testnet,
+            ExtractionMode::FirstPerShard,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.len(), 1); // see FirstPerShard note in previous test
+
+        let composed_rule = IndexerRule {
+            indexer_rule_kind: IndexerRuleKind::Action,
+            matching_rule: MatchingRule::All {
+                matching_rules: vec![
+                    MatchingRule::ActionAny {
+                        affected_account_id: "*.nearcrowd.near".to_string(),
+                        status: Status::Success,
+                    },
+                    MatchingRule::Not {
+                        matching_rules: vec![MatchingRule::ActionAny {
+                            affected_account_id: "*.nearcrowd.near".to_string(),
+                            status: Status::Any,
+                        }]
+                        .into(),
+                    },
+                ]
+                .into(),
+            },
+            id: None,
+            name: None,
+            payload_enrichment: PayloadEnrichment::None,
+        };
+
+        let result: Vec<IndexerRuleMatch> = reduce_indexer_rule_matches_from_outcomes(
+            &composed_rule,
+            &streamer_message,
+            ChainId::Testnet,
+            ExtractionMode::FirstPerShard,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn match_any() {
+        let composed_rule = IndexerRule {
+            indexer_rule_kind: IndexerRuleKind::Action,
+            matching_rule: MatchingRule::Any {
+                matching_rules: vec![
+                    MatchingRule::ActionAny {
+                        affected_account_id: "notintheblockaccount.near".to_string(),
+                        status: Status::Success,
+                    },
+                    MatchingRule::ActionAny {
+                        affected_account_id: "app.nearcrowd.near".to_string(),
+                        status: Status::Success,
+                    },
+                ]
+                .into(),
+            },
+            id: None,
+            name: None,
+            payload_enrichment: PayloadEnrichment::None,
+        };
+
+        let streamer_message = read_local_streamer_message(93085141);
+        let result: Vec<IndexerRuleMatch> = reduce_indexer_rule_matches_from_outcomes(
+            &composed_rule,
+            &streamer_message,
+            ChainId::Testnet,
+            ExtractionMode::FirstPerShard,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.len(), 1); // see FirstPerShard note in previous test
+    }
+
+    #[tokio::test]
+    async fn match_any_block_once_per_block() {
+        let any_block_rule = IndexerRule {
+            indexer_rule_kind: IndexerRuleKind::AnyBlock,
+            matching_rule: MatchingRule::AnyBlock,
+            id: None,
+            name: None,
+            payload_enrichment: PayloadEnrichment::None,
+        };
+
+        let streamer_message = read_local_streamer_message(93085141);
+        let result: Vec<IndexerRuleMatch> = crate::reduce_indexer_rule_matches(
+            &any_block_rule,
+            &streamer_message,
+            ChainId::Testnet,
+            ExtractionMode::FirstPerShard,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].block_height, 93085141);
+    }
+
+    #[tokio::test]
+    async fn extract_all_matching_receipts() {
+        let wildcard_rule = IndexerRule {
+            indexer_rule_kind: IndexerRuleKind::Action,
+            matching_rule: MatchingRule::ActionAny {
+                affected_account_id: "*.nearcrowd.near".to_string(),
+                status: Status::Success,
+            },
+            id: None,
+            name: None,
+            payload_enrichment: PayloadEnrichment::None,
         };
 
         let streamer_message = read_local_streamer_message(93085141);
@@ -265,10 +497,15 @@ mod tests {
             &wildcard_rule,
             &streamer_message,
             ChainId::Testnet,
+            ExtractionMode::All,
         )
         .await
         .unwrap();
 
-        assert_eq!(result.len(), 1); // There are two matches, until we add Extraction we are just matching the first one (block matching)
+        assert_eq!(result.len(), 2);
+        assert_ne!(
+            result[0].payload.receipt_id(),
+            result[1].payload.receipt_id()
+        );
     }
 }
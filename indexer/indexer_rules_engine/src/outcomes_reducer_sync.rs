@@ -1,15 +1,17 @@
 use crate::matcher;
-use crate::types::events::Event;
-use crate::types::indexer_rule_match::{ChainId, IndexerRuleMatch, IndexerRuleMatchPayload};
-use indexer_rule_type::indexer_rule::{IndexerRule, MatchingRule};
-use near_lake_framework::near_indexer_primitives::{
-    IndexerExecutionOutcomeWithReceipt, StreamerMessage,
+use crate::primitives::{IndexerExecutionOutcomeWithReceipt, StreamerMessage};
+use crate::types::indexer_rule_match::{
+    ChainId, ExtractionMode, IndexerRuleMatch, IndexerRuleMatchPayload,
 };
+use crate::types::receipts::ReceiptDetails;
+use crate::types::standard_events;
+use indexer_rule_type::indexer_rule::{IndexerRule, MatchingRule, PayloadEnrichment};
 
 pub fn reduce_indexer_rule_matches_from_outcomes(
     indexer_rule: &IndexerRule,
     streamer_message: &StreamerMessage,
     chain_id: ChainId,
+    extraction_mode: ExtractionMode,
 ) -> Vec<IndexerRuleMatch> {
     streamer_message
         .shards
@@ -18,97 +20,265 @@ pub fn reduce_indexer_rule_matches_from_outcomes(
             shard
                 .receipt_execution_outcomes
                 .iter()
-                // future: when extracting Actions, Events, etc this will be a filter operation
-                .find(|receipt_execution_outcome| {
+                .filter(|receipt_execution_outcome| {
                     matcher::matches(&indexer_rule.matching_rule, receipt_execution_outcome)
                 })
+                .take(extraction_mode.limit())
         })
-        .map(|receipt_execution_outcome| {
-            build_indexer_rule_match(
+        .flat_map(|receipt_execution_outcome| {
+            build_indexer_rule_matches(
                 indexer_rule,
                 receipt_execution_outcome,
                 streamer_message.block.header.hash.to_string(),
                 streamer_message.block.header.height,
                 chain_id.clone(),
+                extraction_mode,
             )
         })
         .collect()
 }
 
-fn build_indexer_rule_match(
+pub(crate) fn build_indexer_rule_matches(
     indexer_rule: &IndexerRule,
     receipt_execution_outcome: &IndexerExecutionOutcomeWithReceipt,
     block_header_hash: String,
     block_height: u64,
     chain_id: ChainId,
-) -> IndexerRuleMatch {
-    IndexerRuleMatch {
+    extraction_mode: ExtractionMode,
+) -> Vec<IndexerRuleMatch> {
+    build_indexer_rule_match_payloads(
+        indexer_rule,
+        receipt_execution_outcome,
+        block_header_hash,
+        extraction_mode,
+    )
+    .into_iter()
+    .map(|payload| IndexerRuleMatch {
         chain_id: chain_id.clone(),
         indexer_rule_id: indexer_rule.id,
         indexer_rule_name: indexer_rule.name.clone(),
-        payload: build_indexer_rule_match_payload(
-            indexer_rule,
-            receipt_execution_outcome,
-            block_header_hash,
-        ),
+        payload,
         block_height,
-    }
+    })
+    .collect()
 }
 
-fn build_indexer_rule_match_payload(
+fn build_indexer_rule_match_payloads(
     indexer_rule: &IndexerRule,
     receipt_execution_outcome: &IndexerExecutionOutcomeWithReceipt,
     block_header_hash: String,
-) -> IndexerRuleMatchPayload {
+    extraction_mode: ExtractionMode,
+) -> Vec<IndexerRuleMatchPayload> {
     // future enhancement will extract and enrich fields from block & context as
     //   specified in the indexer function config.
+    // Transaction hashes are resolved for the whole block by `ReceiptTransactionHashes::populate`
     let transaction_hash = None;
 
-    match &indexer_rule.matching_rule {
-        MatchingRule::ActionAny { .. } | MatchingRule::ActionFunctionCall { .. } => {
-            IndexerRuleMatchPayload::Actions {
-                block_hash: block_header_hash.to_string(),
-                receipt_id: receipt_execution_outcome.receipt.receipt_id.to_string(),
-                transaction_hash,
-            }
-        }
+    // composed rules build the payload of the nested rule which matched
+    let matching_rule =
+        matcher::matched_leaf_rule(&indexer_rule.matching_rule, receipt_execution_outcome)
+            .unwrap_or(&indexer_rule.matching_rule);
+
+    match matching_rule {
+        MatchingRule::ActionAny { .. }
+        | MatchingRule::ActionFunctionCall { .. }
+        | MatchingRule::All { .. }
+        | MatchingRule::Any { .. }
+        | MatchingRule::Not { .. }
+        | MatchingRule::AnyBlock
+        | MatchingRule::StateChangeAccountBalance { .. }
+        | MatchingRule::StateChangeContractData { .. }
+        | MatchingRule::StateChangeAccessKey { .. }
+        | MatchingRule::ActionDeployContract { .. }
+        | MatchingRule::ActionAddKey { .. }
+        | MatchingRule::ActionDeleteKey { .. }
+        | MatchingRule::ActionStake { .. }
+        | MatchingRule::ActionCreateAccount { .. }
+        | MatchingRule::ActionDeleteAccount { .. }
+        | MatchingRule::ActionTransfer { .. }
+        | MatchingRule::ActionFunctionCallArgs { .. } => vec![IndexerRuleMatchPayload::Actions {
+            block_hash: block_header_hash.to_string(),
+            receipt_id: receipt_execution_outcome.receipt.receipt_id.to_string(),
+            transaction_hash,
+            receipt_details: match indexer_rule.payload_enrichment {
+                PayloadEnrichment::Actions => Some(ReceiptDetails::from_receipt_execution_outcome(
+                    receipt_execution_outcome,
+                )),
+                PayloadEnrichment::None => None,
+            },
+        }],
         MatchingRule::Event {
             event,
             standard,
             version,
             ..
+        }
+        | MatchingRule::EventData {
+            event,
+            standard,
+            version,
+            ..
         } => {
-            let event = receipt_execution_outcome
-                .execution_outcome
-                .outcome
-                .logs
-                .iter()
-                .filter_map(|log| Event::from_log(log).ok())
-                .filter_map(|near_event| {
-                    if vec![
-                        wildmatch::WildMatch::new(event).matches(&near_event.event),
-                        wildmatch::WildMatch::new(standard).matches(&near_event.standard),
-                        wildmatch::WildMatch::new(version).matches(&near_event.version),
-                    ].into_iter().all(|val| val) {
-                        Some(near_event)
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<Event>>()
-                .first()
-                .expect("Failed to get the matched Event itself while building the IndexerRuleMatchPayload")
-                .clone();
+            let data_predicates = match matching_rule {
+                MatchingRule::EventData { data, .. } => data.as_slice(),
+                _ => &[],
+            };
+            // one payload per logged event which satisfies the rule
+            matcher::matching_events(
+                event,
+                standard,
+                version,
+                data_predicates,
+                receipt_execution_outcome,
+            )
+            .take(extraction_mode.limit())
+            .map(|event| {
+                let (transfers, validation_error) = standard_events::transfers_of_event(
+                    &event,
+                    receipt_execution_outcome.receipt.receiver_id.as_str(),
+                );
+                IndexerRuleMatchPayload::Events {
+                    block_hash: block_header_hash.to_string(),
+                    receipt_id: receipt_execution_outcome.receipt.receipt_id.to_string(),
+                    transaction_hash: transaction_hash.clone(),
+                    event: event.event,
+                    standard: event.standard,
+                    version: event.version,
+                    data: event.data.as_ref().map(|data| data.to_string()),
+                    transfers,
+                    validation_error,
+                }
+            })
+            .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::build_indexer_rule_match_payloads;
+    use crate::test_utils::outcome_with_actions;
+    use crate::types::indexer_rule_match::{ExtractionMode, IndexerRuleMatchPayload};
+    use indexer_rule_type::indexer_rule::{
+        IndexerRule, IndexerRuleKind, MatchingRule, PayloadEnrichment, Status,
+    };
+
+    #[test]
+    fn extract_one_payload_per_event() {
+        let mut outcome = outcome_with_actions(serde_json::json!([]));
+        outcome.execution_outcome.outcome.logs = vec![
+            r#"EVENT_JSON:{"standard":"nep141","version":"1.0.0","event":"ft_transfer","data":[{"old_owner_id":"alice.near","new_owner_id":"bob.near","amount":"1"}]}"#.to_string(),
+            "not an event".to_string(),
+            r#"EVENT_JSON:{"standard":"nep141","version":"1.0.0","event":"ft_transfer","data":[{"old_owner_id":"alice.near","new_owner_id":"carol.near","amount":"2"}]}"#.to_string(),
+        ];
+        let event_rule = IndexerRule {
+            indexer_rule_kind: IndexerRuleKind::Event,
+            matching_rule: MatchingRule::Event {
+                contract_account_id: "alice.near".to_string(),
+                standard: "nep141".to_string(),
+                version: "*".to_string(),
+                event: "ft_transfer".to_string(),
+            },
+            id: None,
+            name: None,
+            payload_enrichment: PayloadEnrichment::None,
+        };
+
+        let payloads = build_indexer_rule_match_payloads(
+            &event_rule,
+            &outcome,
+            "block".to_string(),
+            ExtractionMode::All,
+        );
+        assert_eq!(payloads.len(), 2);
+        assert!(matches!(
+            &payloads[1],
+            IndexerRuleMatchPayload::Events { data: Some(data), .. } if data.contains("carol.near")
+        ));
+        assert!(matches!(
+            &payloads[1],
+            IndexerRuleMatchPayload::Events { transfers, validation_error: None, .. }
+                if transfers[0].new_owner_id.as_deref() == Some("carol.near")
+        ));
+
+        let payloads = build_indexer_rule_match_payloads(
+            &event_rule,
+            &outcome,
+            "block".to_string(),
+            ExtractionMode::FirstPerShard,
+        );
+        assert_eq!(payloads.len(), 1);
+    }
 
-            IndexerRuleMatchPayload::Events {
-                block_hash: block_header_hash.to_string(),
-                receipt_id: receipt_execution_outcome.receipt.receipt_id.to_string(),
-                transaction_hash,
-                event: event.event.clone(),
-                standard: event.standard.clone(),
-                version: event.version.clone(),
-                data: event.data.as_ref().map(|data| data.to_string()),
+    #[test]
+    fn enrich_action_payloads() {
+        let outcome = outcome_with_actions(serde_json::json!([
+            {
+                "FunctionCall": {
+                    "method_name": "ft_transfer",
+                    "args": "eyJyZWNlaXZlcl9pZCI6IngubmVhciIsImFtb3VudCI6IjUifQ==",
+                    "gas": 30000000000000u64,
+                    "deposit": "1"
+                }
+            },
+            { "Transfer": { "deposit": "1000" } }
+        ]));
+        let mut function_call_rule = IndexerRule {
+            indexer_rule_kind: IndexerRuleKind::Action,
+            matching_rule: MatchingRule::ActionFunctionCall {
+                affected_account_id: "alice.near".to_string(),
+                function: "ft_transfer".to_string(),
+                status: Status::Success,
+            },
+            id: None,
+            name: None,
+            payload_enrichment: PayloadEnrichment::None,
+        };
+
+        let payloads = build_indexer_rule_match_payloads(
+            &function_call_rule,
+            &outcome,
+            "block".to_string(),
+            ExtractionMode::All,
+        );
+        assert!(matches!(
+            &payloads[0],
+            IndexerRuleMatchPayload::Actions {
+                receipt_details: None,
+                ..
             }
-        }
+        ));
+
+        function_call_rule.payload_enrichment = PayloadEnrichment::Actions;
+        let payloads = build_indexer_rule_match_payloads(
+            &function_call_rule,
+            &outcome,
+            "block".to_string(),
+            ExtractionMode::All,
+        );
+        let receipt_details = match &payloads[0] {
+            IndexerRuleMatchPayload::Actions {
+                receipt_details: Some(receipt_details),
+                ..
+            } => receipt_details,
+            payload => panic!("Expected enriched actions payload, got {:?}", payload),
+        };
+        assert_eq!(receipt_details.signer_id.as_deref(), Some("bob.near"));
+        assert_eq!(receipt_details.receiver_id, "alice.near");
+        assert_eq!(receipt_details.actions.len(), 2);
+
+        let function_call = &receipt_details.actions[0];
+        assert_eq!(function_call.method_name.as_deref(), Some("ft_transfer"));
+        assert_eq!(
+            function_call.args_json.as_deref(),
+            Some(r#"{"amount":"5","receiver_id":"x.near"}"#)
+        );
+        assert_eq!(function_call.deposit.as_deref(), Some("1"));
+        assert_eq!(function_call.gas, Some(30000000000000));
+
+        let transfer = &receipt_details.actions[1];
+        assert_eq!(transfer.action_kind, "Transfer");
+        assert_eq!(transfer.deposit.as_deref(), Some("1000"));
+        assert_eq!(transfer.method_name, None);
     }
 }
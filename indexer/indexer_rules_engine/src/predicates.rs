@@ -0,0 +1,201 @@
+use indexer_rule_type::indexer_rule::{JsonPredicate, PredicateOperator};
+use serde_json::Value;
+
+enum PathSegment<'a> {
+    Key(&'a str),
+    Index(usize),
+    Wildcard,
+}
+
+/// Function call args are raw bytes, when they are not JSON (e.g. borsh encoded) no predicate can match
+pub fn matches_json_bytes(predicates: &[JsonPredicate], bytes: &[u8]) -> bool {
+    match serde_json::from_slice::<Value>(bytes) {
+        Ok(value) => matches_json(predicates, &value),
+        Err(_) => false,
+    }
+}
+
+pub fn matches_json(predicates: &[JsonPredicate], value: &Value) -> bool {
+    predicates
+        .iter()
+        .all(|predicate| matches_json_predicate(predicate, value))
+}
+
+fn matches_json_predicate(predicate: &JsonPredicate, value: &Value) -> bool {
+    let found_values = resolve_path(value, &parse_path(&predicate.path));
+
+    match predicate.operator {
+        PredicateOperator::Exists => !found_values.is_empty(),
+        _ => found_values
+            .into_iter()
+            .any(|found_value| compare(&predicate.operator, found_value, &predicate.value)),
+    }
+}
+
+fn parse_path(path: &str) -> Vec<PathSegment<'_>> {
+    path.trim_start_matches('$')
+        .split('.')
+        .filter(|part| !part.is_empty())
+        .flat_map(|part| {
+            let mut segments = vec![];
+            let (key, indexes) = match part.find('[') {
+                Some(position) => part.split_at(position),
+                None => (part, ""),
+            };
+            if !key.is_empty() {
+                segments.push(PathSegment::Key(key));
+            }
+            for index in indexes.split(['[', ']']).filter(|index| !index.is_empty()) {
+                segments.push(match index {
+                    "*" => PathSegment::Wildcard,
+                    _ => match index.parse::<usize>() {
+                        Ok(index) => PathSegment::Index(index),
+                        Err(_) => PathSegment::Key(index.trim_matches(['"', '\''])),
+                    },
+                });
+            }
+            segments
+        })
+        .collect()
+}
+
+fn resolve_path<'a>(value: &'a Value, path: &[PathSegment]) -> Vec<&'a Value> {
+    path.iter().fold(vec![value], |values, segment| {
+        values
+            .into_iter()
+            .flat_map(|value| match (segment, value) {
+                (PathSegment::Key(key), Value::Object(map)) => map.get(*key).into_iter().collect(),
+                // plain numeric segments like `token_ids.0` index into arrays
+                (PathSegment::Key(key), Value::Array(array)) => key
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| array.get(index))
+                    .into_iter()
+                    .collect(),
+                (PathSegment::Index(index), Value::Array(array)) => {
+                    array.get(*index).into_iter().collect()
+                }
+                (PathSegment::Wildcard, Value::Array(array)) => array.iter().collect(),
+                (PathSegment::Wildcard, Value::Object(map)) => map.values().collect(),
+                _ => vec![],
+            })
+            .collect()
+    })
+}
+
+fn compare(operator: &PredicateOperator, found_value: &Value, expected: &str) -> bool {
+    match operator {
+        PredicateOperator::Equal => scalar_to_string(found_value) == expected,
+        PredicateOperator::NotEqual => scalar_to_string(found_value) != expected,
+        PredicateOperator::GreaterThan => {
+            compare_numbers(found_value, expected).is_some_and(std::cmp::Ordering::is_gt)
+        }
+        PredicateOperator::GreaterThanOrEqual => {
+            compare_numbers(found_value, expected).is_some_and(std::cmp::Ordering::is_ge)
+        }
+        PredicateOperator::LessThan => {
+            compare_numbers(found_value, expected).is_some_and(std::cmp::Ordering::is_lt)
+        }
+        PredicateOperator::LessThanOrEqual => {
+            compare_numbers(found_value, expected).is_some_and(std::cmp::Ordering::is_le)
+        }
+        PredicateOperator::Contains => match found_value {
+            Value::Array(array) => array
+                .iter()
+                .any(|element| scalar_to_string(element) == expected),
+            Value::String(string) => string.contains(expected),
+            _ => false,
+        },
+        PredicateOperator::Exists => true,
+    }
+}
+
+fn scalar_to_string(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.clone(),
+        _ => value.to_string(),
+    }
+}
+
+/// NEAR amounts are passed as strings and overflow f64 precision, so integers are compared as u128
+fn compare_numbers(found_value: &Value, expected: &str) -> Option<std::cmp::Ordering> {
+    let found = scalar_to_string(found_value);
+    match (found.parse::<u128>(), expected.trim().parse::<u128>()) {
+        (Ok(found), Ok(expected)) => Some(found.cmp(&expected)),
+        _ => found
+            .parse::<f64>()
+            .ok()
+            .zip(expected.trim().parse::<f64>().ok())
+            .and_then(|(found, expected)| found.partial_cmp(&expected)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches_json, matches_json_bytes};
+    use indexer_rule_type::indexer_rule::{JsonPredicate, PredicateOperator};
+
+    fn predicate(path: &str, operator: PredicateOperator, value: &str) -> JsonPredicate {
+        JsonPredicate {
+            path: path.to_string(),
+            operator,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn match_string_and_amount() {
+        let args = serde_json::json!({
+            "receiver_id": "x.near",
+            "amount": "2000000000000000000000000",
+            "msg": { "token_ids": ["1", "2"] }
+        });
+
+        assert!(matches_json(
+            &[
+                predicate("$.receiver_id", PredicateOperator::Equal, "x.near"),
+                predicate(
+                    "amount",
+                    PredicateOperator::GreaterThan,
+                    "1000000000000000000000000"
+                ),
+            ],
+            &args
+        ));
+        assert!(!matches_json(
+            &[predicate(
+                "amount",
+                PredicateOperator::LessThanOrEqual,
+                "1000000000000000000000000"
+            )],
+            &args
+        ));
+        assert!(matches_json(
+            &[predicate("msg.token_ids", PredicateOperator::Contains, "2")],
+            &args
+        ));
+        assert!(matches_json(
+            &[predicate("msg.token_ids[*]", PredicateOperator::Equal, "1")],
+            &args
+        ));
+        assert!(matches_json(
+            &[predicate("msg.token_ids[1]", PredicateOperator::Equal, "2")],
+            &args
+        ));
+        assert!(!matches_json(
+            &[predicate("memo", PredicateOperator::Exists, "")],
+            &args
+        ));
+    }
+
+    #[test]
+    fn binary_args_never_match() {
+        let predicates = [predicate("receiver_id", PredicateOperator::Exists, "")];
+
+        assert!(!matches_json_bytes(&predicates, &[0, 159, 146, 150]));
+        assert!(matches_json_bytes(
+            &predicates,
+            br#"{"receiver_id":"x.near"}"#
+        ));
+    }
+}
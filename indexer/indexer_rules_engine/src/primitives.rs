@@ -0,0 +1,422 @@
+//! NEAR block types the engine matches against.
+//!
+//! Natively these are the `near-indexer-primitives` types near-lake-framework streams. Those pull in
+//! networking crates which don't build for `wasm32-unknown-unknown`, so the wasm build deserializes
+//! blocks into the mirrors below instead, which are only built for wasm. They share the JSON layout
+//! of the views they mirror, while block and chunk headers only keep the fields the engine reads.
+//! Their Borsh layout differs, e.g. execution errors are kept as raw JSON, so the wasm build only
+//! exchanges blocks and matches as JSON.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use near_indexer_primitives::*;
+
+#[cfg(target_arch = "wasm32")]
+pub use wasm_primitives::*;
+
+#[cfg(target_arch = "wasm32")]
+mod wasm_primitives {
+    pub use near_primitives_core::hash::CryptoHash;
+
+    pub mod types {
+        pub use near_primitives_core::types::*;
+    }
+
+    pub mod views {
+        use borsh::{BorshDeserialize, BorshSerialize};
+        use near_primitives_core::serialize::{base64_format, dec_format, option_base64_format};
+        use serde::{Deserialize, Serialize};
+
+        use super::types::{AccountId, Balance, BlockHeight, Gas, Nonce, StorageUsage};
+        use super::CryptoHash;
+
+        /// Keys are only compared and displayed by the engine, so their curve and bytes aren't parsed
+        #[derive(
+            BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
+        )]
+        #[serde(transparent)]
+        pub struct PublicKey(pub String);
+
+        impl std::fmt::Display for PublicKey {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        #[derive(
+            BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
+        )]
+        #[serde(transparent)]
+        pub struct Signature(pub String);
+
+        /// Value the engine doesn't look into, kept as the JSON it was received as
+        #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+        #[serde(transparent)]
+        pub struct RawJson(pub serde_json::Value);
+
+        pub type TxExecutionError = RawJson;
+
+        impl BorshSerialize for RawJson {
+            fn serialize<W: borsh::maybestd::io::Write>(
+                &self,
+                writer: &mut W,
+            ) -> borsh::maybestd::io::Result<()> {
+                BorshSerialize::serialize(&self.0.to_string(), writer)
+            }
+        }
+
+        impl BorshDeserialize for RawJson {
+            fn deserialize_reader<R: borsh::maybestd::io::Read>(
+                reader: &mut R,
+            ) -> borsh::maybestd::io::Result<Self> {
+                let error = String::deserialize_reader(reader)?;
+                serde_json::from_str(&error).map(Self).map_err(|e| {
+                    borsh::maybestd::io::Error::new(borsh::maybestd::io::ErrorKind::InvalidData, e)
+                })
+            }
+        }
+
+        #[derive(Serialize, Deserialize, Debug, Clone)]
+        pub struct BlockView {
+            pub author: AccountId,
+            pub header: BlockHeaderView,
+        }
+
+        #[derive(Serialize, Deserialize, Debug, Clone)]
+        pub struct BlockHeaderView {
+            pub height: BlockHeight,
+            pub prev_height: Option<BlockHeight>,
+            pub hash: CryptoHash,
+            pub prev_hash: CryptoHash,
+            #[serde(with = "dec_format")]
+            pub timestamp_nanosec: u64,
+        }
+
+        #[derive(Serialize, Deserialize, Debug, Clone)]
+        pub struct ChunkHeaderView {
+            pub chunk_hash: CryptoHash,
+            pub shard_id: u64,
+        }
+
+        #[derive(
+            BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
+        )]
+        pub enum AccessKeyPermissionView {
+            FunctionCall {
+                #[serde(with = "dec_format")]
+                allowance: Option<Balance>,
+                receiver_id: String,
+                method_names: Vec<String>,
+            },
+            FullAccess,
+        }
+
+        #[derive(
+            BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
+        )]
+        pub struct AccessKeyView {
+            pub nonce: Nonce,
+            pub permission: AccessKeyPermissionView,
+        }
+
+        #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+        pub struct AccountView {
+            #[serde(with = "dec_format")]
+            pub amount: Balance,
+            #[serde(with = "dec_format")]
+            pub locked: Balance,
+            pub code_hash: CryptoHash,
+            pub storage_usage: StorageUsage,
+            #[serde(default)]
+            pub storage_paid_at: BlockHeight,
+        }
+
+        #[derive(
+            BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
+        )]
+        pub struct DelegateAction {
+            pub sender_id: AccountId,
+            pub receiver_id: AccountId,
+            pub actions: Vec<RawJson>,
+            pub nonce: Nonce,
+            pub max_block_height: BlockHeight,
+            pub public_key: PublicKey,
+        }
+
+        #[derive(
+            BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
+        )]
+        pub enum ActionView {
+            CreateAccount,
+            DeployContract {
+                #[serde(with = "base64_format")]
+                code: Vec<u8>,
+            },
+            FunctionCall {
+                method_name: String,
+                #[serde(with = "base64_format")]
+                args: Vec<u8>,
+                gas: Gas,
+                #[serde(with = "dec_format")]
+                deposit: Balance,
+            },
+            Transfer {
+                #[serde(with = "dec_format")]
+                deposit: Balance,
+            },
+            Stake {
+                #[serde(with = "dec_format")]
+                stake: Balance,
+                public_key: PublicKey,
+            },
+            AddKey {
+                public_key: PublicKey,
+                access_key: AccessKeyView,
+            },
+            DeleteKey {
+                public_key: PublicKey,
+            },
+            DeleteAccount {
+                beneficiary_id: AccountId,
+            },
+            Delegate {
+                delegate_action: DelegateAction,
+                signature: Signature,
+            },
+        }
+
+        #[derive(
+            BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
+        )]
+        pub struct SignedTransactionView {
+            pub signer_id: AccountId,
+            pub public_key: PublicKey,
+            pub nonce: Nonce,
+            pub receiver_id: AccountId,
+            pub actions: Vec<ActionView>,
+            pub signature: Signature,
+            pub hash: CryptoHash,
+        }
+
+        #[derive(
+            BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
+        )]
+        pub enum ExecutionStatusView {
+            Unknown,
+            Failure(TxExecutionError),
+            SuccessValue(#[serde(with = "base64_format")] Vec<u8>),
+            SuccessReceiptId(CryptoHash),
+        }
+
+        #[derive(
+            BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
+        )]
+        pub struct CostGasUsed {
+            pub cost_category: String,
+            pub cost: String,
+            #[serde(with = "dec_format")]
+            pub gas_used: Gas,
+        }
+
+        #[derive(
+            BorshSerialize,
+            BorshDeserialize,
+            Serialize,
+            Deserialize,
+            Debug,
+            Clone,
+            PartialEq,
+            Eq,
+            Default,
+        )]
+        pub struct ExecutionMetadataView {
+            pub version: u32,
+            pub gas_profile: Option<Vec<CostGasUsed>>,
+        }
+
+        #[derive(
+            BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
+        )]
+        pub struct ExecutionOutcomeView {
+            pub logs: Vec<String>,
+            pub receipt_ids: Vec<CryptoHash>,
+            pub gas_burnt: Gas,
+            #[serde(with = "dec_format")]
+            pub tokens_burnt: Balance,
+            pub executor_id: AccountId,
+            pub status: ExecutionStatusView,
+            #[serde(default)]
+            pub metadata: ExecutionMetadataView,
+        }
+
+        #[derive(
+            BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
+        )]
+        pub enum Direction {
+            Left,
+            Right,
+        }
+
+        #[derive(
+            BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
+        )]
+        pub struct MerklePathItem {
+            pub hash: CryptoHash,
+            pub direction: Direction,
+        }
+
+        #[derive(
+            BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
+        )]
+        pub struct ExecutionOutcomeWithIdView {
+            pub proof: Vec<MerklePathItem>,
+            pub block_hash: CryptoHash,
+            pub id: CryptoHash,
+            pub outcome: ExecutionOutcomeView,
+        }
+
+        #[derive(
+            BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
+        )]
+        pub struct DataReceiverView {
+            pub data_id: CryptoHash,
+            pub receiver_id: AccountId,
+        }
+
+        #[derive(
+            BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
+        )]
+        pub enum ReceiptEnumView {
+            Action {
+                signer_id: AccountId,
+                signer_public_key: PublicKey,
+                #[serde(with = "dec_format")]
+                gas_price: Balance,
+                output_data_receivers: Vec<DataReceiverView>,
+                input_data_ids: Vec<CryptoHash>,
+                actions: Vec<ActionView>,
+            },
+            Data {
+                data_id: CryptoHash,
+                #[serde(with = "option_base64_format")]
+                data: Option<Vec<u8>>,
+            },
+        }
+
+        #[derive(
+            BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
+        )]
+        pub struct ReceiptView {
+            pub predecessor_id: AccountId,
+            pub receiver_id: AccountId,
+            pub receipt_id: CryptoHash,
+            pub receipt: ReceiptEnumView,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "snake_case", tag = "type")]
+        pub enum StateChangeCauseView {
+            NotWritableToDisk,
+            InitialState,
+            TransactionProcessing { tx_hash: CryptoHash },
+            ActionReceiptProcessingStarted { receipt_hash: CryptoHash },
+            ActionReceiptGasReward { receipt_hash: CryptoHash },
+            ReceiptProcessing { receipt_hash: CryptoHash },
+            PostponedReceipt { receipt_hash: CryptoHash },
+            UpdatedDelayedReceipts,
+            ValidatorAccountsUpdate,
+            Migration,
+            Resharding,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "snake_case", tag = "type", content = "change")]
+        pub enum StateChangeValueView {
+            AccountUpdate {
+                account_id: AccountId,
+                #[serde(flatten)]
+                account: AccountView,
+            },
+            AccountDeletion {
+                account_id: AccountId,
+            },
+            AccessKeyUpdate {
+                account_id: AccountId,
+                public_key: PublicKey,
+                access_key: AccessKeyView,
+            },
+            AccessKeyDeletion {
+                account_id: AccountId,
+                public_key: PublicKey,
+            },
+            DataUpdate {
+                account_id: AccountId,
+                #[serde(rename = "key_base64", with = "base64_format")]
+                key: Vec<u8>,
+                #[serde(rename = "value_base64", with = "base64_format")]
+                value: Vec<u8>,
+            },
+            DataDeletion {
+                account_id: AccountId,
+                #[serde(rename = "key_base64", with = "base64_format")]
+                key: Vec<u8>,
+            },
+            ContractCodeUpdate {
+                account_id: AccountId,
+                #[serde(rename = "code_base64", with = "base64_format")]
+                code: Vec<u8>,
+            },
+            ContractCodeDeletion {
+                account_id: AccountId,
+            },
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        pub struct StateChangeWithCauseView {
+            pub cause: StateChangeCauseView,
+            #[serde(flatten)]
+            pub value: StateChangeValueView,
+        }
+
+        pub type StateChangesView = Vec<StateChangeWithCauseView>;
+    }
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    pub struct StreamerMessage {
+        pub block: views::BlockView,
+        pub shards: Vec<IndexerShard>,
+    }
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    pub struct IndexerChunkView {
+        pub author: types::AccountId,
+        pub header: views::ChunkHeaderView,
+        pub transactions: Vec<IndexerTransactionWithOutcome>,
+        pub receipts: Vec<views::ReceiptView>,
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+    pub struct IndexerTransactionWithOutcome {
+        pub transaction: views::SignedTransactionView,
+        pub outcome: IndexerExecutionOutcomeWithOptionalReceipt,
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+    pub struct IndexerExecutionOutcomeWithOptionalReceipt {
+        pub execution_outcome: views::ExecutionOutcomeWithIdView,
+        pub receipt: Option<views::ReceiptView>,
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+    pub struct IndexerExecutionOutcomeWithReceipt {
+        pub execution_outcome: views::ExecutionOutcomeWithIdView,
+        pub receipt: views::ReceiptView,
+    }
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    pub struct IndexerShard {
+        pub shard_id: types::ShardId,
+        pub chunk: Option<IndexerChunkView>,
+        pub receipt_execution_outcomes: Vec<IndexerExecutionOutcomeWithReceipt>,
+        pub state_changes: views::StateChangesView,
+    }
+}
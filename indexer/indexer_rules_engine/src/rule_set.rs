@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::matcher::{self, WildcardPatterns};
+use crate::primitives::StreamerMessage;
+use crate::types::indexer_rule_match::{ChainId, ExtractionMode, IndexerRuleMatch};
+use crate::{outcomes_reducer, outcomes_reducer_sync, state_changes_reducer};
+use indexer_rule_type::indexer_rule::{IndexerRule, MatchingRule};
+use wildmatch::WildMatch;
+
+/// Rules of many indexers compiled to be evaluated together in a single pass over a block.
+///
+/// Receipt rules are indexed by the account patterns they require: exact account ids are looked up in
+/// a map, `*.suffix` patterns are grouped by suffix and other wildcards are compiled once. Only the
+/// rules found through the receipt's receiver and predecessor accounts are then run by the matcher,
+/// with every wildcard pattern of the rules compiled once.
+pub struct RuleSet<K> {
+    rules: Vec<(K, IndexerRule)>,
+    patterns: WildcardPatterns,
+    exact_accounts: HashMap<String, Vec<usize>>,
+    wildcard_suffixes: HashMap<String, Vec<usize>>,
+    wildcard_patterns: Vec<(WildMatch, usize)>,
+    /// Receipt rules without an account pattern to index them by, e.g. `Not`, run for every receipt
+    unindexed_rules: Vec<usize>,
+    state_change_rules: Vec<usize>,
+    block_rules: Vec<usize>,
+}
+
+impl<K: Clone + Eq + Hash> RuleSet<K> {
+    pub fn new(rules: impl IntoIterator<Item = (K, IndexerRule)>) -> Self {
+        let rules: Vec<(K, IndexerRule)> = rules.into_iter().collect();
+        let mut rule_set = Self {
+            rules: vec![],
+            patterns: WildcardPatterns::new(
+                rules
+                    .iter()
+                    .map(|(_, indexer_rule)| &indexer_rule.matching_rule),
+            ),
+            exact_accounts: HashMap::new(),
+            wildcard_suffixes: HashMap::new(),
+            wildcard_patterns: vec![],
+            unindexed_rules: vec![],
+            state_change_rules: vec![],
+            block_rules: vec![],
+        };
+
+        for (index, (_, indexer_rule)) in rules.iter().enumerate() {
+            let matching_rule = &indexer_rule.matching_rule;
+            if let MatchingRule::AnyBlock = matching_rule {
+                rule_set.block_rules.push(index);
+            } else if matcher::is_state_change_rule(matching_rule) {
+                rule_set.state_change_rules.push(index);
+            } else {
+                match account_patterns(matching_rule) {
+                    Some(patterns) => {
+                        for pattern in patterns {
+                            rule_set.index_account_pattern(pattern, index);
+                        }
+                    }
+                    None => rule_set.unindexed_rules.push(index),
+                }
+            }
+        }
+
+        rule_set.rules = rules;
+        rule_set
+    }
+
+    fn index_account_pattern(&mut self, pattern: &str, index: usize) {
+        let is_wildcard = |c: char| c == '*' || c == '?';
+        if !pattern.contains(is_wildcard) {
+            self.exact_accounts
+                .entry(pattern.to_string())
+                .or_default()
+                .push(index);
+            return;
+        }
+
+        match pattern.strip_prefix('*') {
+            Some(suffix) if suffix.starts_with('.') && !suffix.contains(is_wildcard) => self
+                .wildcard_suffixes
+                .entry(suffix.to_string())
+                .or_default()
+                .push(index),
+            _ => self
+                .wildcard_patterns
+                .push((WildMatch::new(pattern), index)),
+        }
+    }
+
+    fn collect_candidate_rules(&self, account_id: &str, candidate_rules: &mut Vec<usize>) {
+        if let Some(indexes) = self.exact_accounts.get(account_id) {
+            candidate_rules.extend(indexes);
+        }
+        for (position, _) in account_id.match_indices('.') {
+            if let Some(indexes) = self.wildcard_suffixes.get(&account_id[position..]) {
+                candidate_rules.extend(indexes);
+            }
+        }
+        candidate_rules.extend(
+            self.wildcard_patterns
+                .iter()
+                .filter(|(pattern, _)| pattern.matches(account_id))
+                .map(|(_, index)| index),
+        );
+    }
+
+    /// Same matches as running `reduce_indexer_rule_matches_sync` for every rule, keyed by indexer.
+    /// Indexers without matches are left out.
+    pub fn reduce_indexer_rule_matches(
+        &self,
+        streamer_message: &StreamerMessage,
+        chain_id: ChainId,
+        extraction_mode: ExtractionMode,
+    ) -> HashMap<K, Vec<IndexerRuleMatch>> {
+        let mut matches: HashMap<K, Vec<IndexerRuleMatch>> = HashMap::new();
+        let block_hash = streamer_message.block.header.hash.to_string();
+        let block_height = streamer_message.block.header.height;
+
+        let mut candidate_rules = vec![];
+        let mut matches_in_shard = vec![0; self.rules.len()];
+        for shard in &streamer_message.shards {
+            matches_in_shard.iter_mut().for_each(|count| *count = 0);
+
+            for receipt_execution_outcome in &shard.receipt_execution_outcomes {
+                candidate_rules.clear();
+                self.collect_candidate_rules(
+                    receipt_execution_outcome.receipt.receiver_id.as_str(),
+                    &mut candidate_rules,
+                );
+                self.collect_candidate_rules(
+                    receipt_execution_outcome.receipt.predecessor_id.as_str(),
+                    &mut candidate_rules,
+                );
+                candidate_rules.extend(&self.unindexed_rules);
+                candidate_rules.sort_unstable();
+                candidate_rules.dedup();
+
+                for &index in &candidate_rules {
+                    let (key, indexer_rule) = &self.rules[index];
+                    if matches_in_shard[index] < extraction_mode.limit()
+                        && matcher::matches_compiled(
+                            &indexer_rule.matching_rule,
+                            receipt_execution_outcome,
+                            &self.patterns,
+                        )
+                    {
+                        matches_in_shard[index] += 1;
+                        matches.entry(key.clone()).or_default().extend(
+                            outcomes_reducer_sync::build_indexer_rule_matches(
+                                indexer_rule,
+                                receipt_execution_outcome,
+                                block_hash.clone(),
+                                block_height,
+                                chain_id.clone(),
+                                extraction_mode,
+                            ),
+                        );
+                    }
+                }
+            }
+
+            for state_change in &shard.state_changes {
+                for &index in &self.state_change_rules {
+                    let (key, indexer_rule) = &self.rules[index];
+                    if matches_in_shard[index] < extraction_mode.limit()
+                        && matcher::matches_state_change_compiled(
+                            &indexer_rule.matching_rule,
+                            state_change,
+                            &self.patterns,
+                        )
+                    {
+                        matches_in_shard[index] += 1;
+                        matches.entry(key.clone()).or_default().push(
+                            state_changes_reducer::build_indexer_rule_match(
+                                indexer_rule,
+                                state_change,
+                                block_hash.clone(),
+                                block_height,
+                                chain_id.clone(),
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+
+        for &index in &self.block_rules {
+            let (key, indexer_rule) = &self.rules[index];
+            matches.entry(key.clone()).or_default().push(
+                outcomes_reducer::build_indexer_rule_match_for_block(
+                    indexer_rule,
+                    streamer_message,
+                    chain_id.clone(),
+                ),
+            );
+        }
+
+        matches
+    }
+}
+
+/// Account patterns at least one of which a receipt must match for the rule to match it
+pub(crate) fn account_patterns(matching_rule: &MatchingRule) -> Option<Vec<&str>> {
+    match matching_rule {
+        MatchingRule::ActionAny {
+            affected_account_id,
+            ..
+        }
+        | MatchingRule::ActionFunctionCall {
+            affected_account_id,
+            ..
+        }
+        | MatchingRule::ActionFunctionCallArgs {
+            affected_account_id,
+            ..
+        }
+        | MatchingRule::ActionDeployContract {
+            affected_account_id,
+            ..
+        }
+        | MatchingRule::ActionAddKey {
+            affected_account_id,
+            ..
+        }
+        | MatchingRule::ActionDeleteKey {
+            affected_account_id,
+            ..
+        }
+        | MatchingRule::ActionStake {
+            affected_account_id,
+            ..
+        }
+        | MatchingRule::ActionCreateAccount {
+            affected_account_id,
+            ..
+        }
+        | MatchingRule::ActionDeleteAccount {
+            affected_account_id,
+            ..
+        }
+        | MatchingRule::ActionTransfer {
+            affected_account_id,
+            ..
+        }
+        | MatchingRule::Event {
+            contract_account_id: affected_account_id,
+            ..
+        }
+        | MatchingRule::EventData {
+            contract_account_id: affected_account_id,
+            ..
+        } => Some(
+            affected_account_id
+                .split(',')
+                .map(|pattern| pattern.trim())
+                .collect(),
+        ),
+        // every nested rule must match, so the patterns of any one of them are required
+        MatchingRule::All { matching_rules } => matching_rules.iter().find_map(account_patterns),
+        MatchingRule::Any { matching_rules } => matching_rules
+            .iter()
+            .map(account_patterns)
+            .collect::<Option<Vec<_>>>()
+            .map(|patterns| patterns.concat()),
+        MatchingRule::Not { .. }
+        | MatchingRule::AnyBlock
+        | MatchingRule::StateChangeAccountBalance { .. }
+        | MatchingRule::StateChangeContractData { .. }
+        | MatchingRule::StateChangeAccessKey { .. } => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RuleSet;
+    use crate::primitives::StreamerMessage;
+    use crate::test_utils::{outcome_with_actions, streamer_message_with_outcomes};
+    use crate::types::indexer_rule_match::{ChainId, ExtractionMode};
+    use indexer_rule_type::indexer_rule::{
+        IndexerRule, IndexerRuleKind, MatchingRule, PayloadEnrichment, Status,
+    };
+
+    fn streamer_message_with_receivers(receiver_ids: &[&str]) -> StreamerMessage {
+        streamer_message_with_outcomes(
+            receiver_ids
+                .iter()
+                .map(|receiver_id| {
+                    let mut outcome = outcome_with_actions(serde_json::json!(["CreateAccount"]));
+                    outcome.receipt.receiver_id = receiver_id.parse().unwrap();
+                    outcome
+                })
+                .collect(),
+        )
+    }
+
+    fn indexer_rule(matching_rule: MatchingRule) -> IndexerRule {
+        IndexerRule {
+            indexer_rule_kind: IndexerRuleKind::Action,
+            matching_rule,
+            id: None,
+            name: None,
+            payload_enrichment: PayloadEnrichment::None,
+        }
+    }
+
+    fn action_any(affected_account_id: &str) -> MatchingRule {
+        MatchingRule::ActionAny {
+            affected_account_id: affected_account_id.to_string(),
+            status: Status::Any,
+        }
+    }
+
+    #[test]
+    fn match_same_as_reducing_each_rule() {
+        let streamer_message = streamer_message_with_receivers(&[
+            "app.nearcrowd.near",
+            "a.b.nearcrowd.near",
+            "token.sweat",
+            "alice.near",
+        ]);
+        let rules = vec![
+            ("exact", indexer_rule(action_any("token.sweat"))),
+            ("suffix", indexer_rule(action_any("*.nearcrowd.near"))),
+            ("wildcard", indexer_rule(action_any("app.nea*owd.near"))),
+            (
+                "csv",
+                indexer_rule(action_any("notintheblock.near, *.sweat")),
+            ),
+            ("predecessor", indexer_rule(action_any("bob.near"))),
+            ("no_match", indexer_rule(action_any("*.aurora"))),
+            (
+                "not",
+                indexer_rule(MatchingRule::Not {
+                    matching_rules: vec![action_any("*.near")].into(),
+                }),
+            ),
+            (
+                "all",
+                indexer_rule(MatchingRule::All {
+                    matching_rules: vec![
+                        action_any("*.near"),
+                        MatchingRule::Not {
+                            matching_rules: vec![action_any("alice.near")].into(),
+                        },
+                    ]
+                    .into(),
+                }),
+            ),
+            ("any_block", indexer_rule(MatchingRule::AnyBlock)),
+        ];
+        let rule_set = RuleSet::new(rules.clone());
+
+        for extraction_mode in [ExtractionMode::FirstPerShard, ExtractionMode::All] {
+            let mut matches_by_indexer = rule_set.reduce_indexer_rule_matches(
+                &streamer_message,
+                ChainId::Mainnet,
+                extraction_mode,
+            );
+            if extraction_mode == ExtractionMode::All {
+                assert_eq!(matches_by_indexer["suffix"].len(), 2);
+            }
+
+            for (key, indexer_rule) in &rules {
+                let expected = crate::reduce_indexer_rule_matches_sync(
+                    indexer_rule,
+                    &streamer_message,
+                    ChainId::Mainnet,
+                    extraction_mode,
+                );
+                let actual = matches_by_indexer.remove(key).unwrap_or_default();
+                assert_eq!(
+                    serde_json::to_value(&actual).unwrap(),
+                    serde_json::to_value(&expected).unwrap(),
+                    "{} {:?}",
+                    key,
+                    extraction_mode
+                );
+            }
+        }
+    }
+}
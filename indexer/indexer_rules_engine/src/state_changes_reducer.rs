@@ -0,0 +1,215 @@
+use crate::matcher;
+use crate::primitives::{
+    views::{StateChangeCauseView, StateChangeWithCauseView},
+    StreamerMessage,
+};
+use crate::types::indexer_rule_match::{
+    ChainId, ExtractionMode, IndexerRuleMatch, IndexerRuleMatchPayload,
+};
+use indexer_rule_type::indexer_rule::IndexerRule;
+
+pub fn reduce_indexer_rule_matches_from_state_changes(
+    indexer_rule: &IndexerRule,
+    streamer_message: &StreamerMessage,
+    chain_id: ChainId,
+    extraction_mode: ExtractionMode,
+) -> Vec<IndexerRuleMatch> {
+    streamer_message
+        .shards
+        .iter()
+        .flat_map(|shard| {
+            shard
+                .state_changes
+                .iter()
+                .filter(|state_change| {
+                    matcher::matches_state_change(&indexer_rule.matching_rule, state_change)
+                })
+                .take(extraction_mode.limit())
+        })
+        .map(|state_change| {
+            build_indexer_rule_match(
+                indexer_rule,
+                state_change,
+                streamer_message.block.header.hash.to_string(),
+                streamer_message.block.header.height,
+                chain_id.clone(),
+            )
+        })
+        .collect()
+}
+
+pub(crate) fn build_indexer_rule_match(
+    indexer_rule: &IndexerRule,
+    state_change: &StateChangeWithCauseView,
+    block_header_hash: String,
+    block_height: u64,
+    chain_id: ChainId,
+) -> IndexerRuleMatch {
+    IndexerRuleMatch {
+        chain_id,
+        indexer_rule_id: indexer_rule.id,
+        indexer_rule_name: indexer_rule.name.clone(),
+        payload: build_indexer_rule_match_payload(state_change, block_header_hash),
+        block_height,
+    }
+}
+
+fn build_indexer_rule_match_payload(
+    state_change: &StateChangeWithCauseView,
+    block_header_hash: String,
+) -> IndexerRuleMatchPayload {
+    let (receipt_id, transaction_hash) = cause_receipt_id_and_transaction_hash(&state_change.cause);
+
+    IndexerRuleMatchPayload::StateChanges {
+        block_hash: block_header_hash,
+        receipt_id,
+        transaction_hash,
+    }
+}
+
+/// Receipt or transaction whose processing caused the state change, if any
+pub(crate) fn cause_receipt_id_and_transaction_hash(
+    cause: &StateChangeCauseView,
+) -> (Option<String>, Option<String>) {
+    match cause {
+        StateChangeCauseView::TransactionProcessing { tx_hash } => {
+            (None, Some(tx_hash.to_string()))
+        }
+        StateChangeCauseView::ActionReceiptProcessingStarted { receipt_hash }
+        | StateChangeCauseView::ActionReceiptGasReward { receipt_hash }
+        | StateChangeCauseView::ReceiptProcessing { receipt_hash }
+        | StateChangeCauseView::PostponedReceipt { receipt_hash } => {
+            (Some(receipt_hash.to_string()), None)
+        }
+        StateChangeCauseView::NotWritableToDisk
+        | StateChangeCauseView::InitialState
+        | StateChangeCauseView::UpdatedDelayedReceipts
+        | StateChangeCauseView::ValidatorAccountsUpdate
+        | StateChangeCauseView::Migration
+        | StateChangeCauseView::Resharding => (None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::build_indexer_rule_match_payload;
+    use crate::matcher::matches_state_change;
+    use crate::primitives::views::StateChangeWithCauseView;
+    use crate::test_utils::{state_change, HASH};
+    use crate::types::indexer_rule_match::IndexerRuleMatchPayload;
+    use indexer_rule_type::indexer_rule::{AccessKeyChange, MatchingRule};
+
+    const PUBLIC_KEY: &str = "ed25519:CE3QAXyVLeScmY9YeEyR3Tw9yXfjBPzFLzroTranYtVb";
+    const RECEIPT_HASH: &str = "5ruF5K1Cg4RKtKYmsYvH6hbxHufvxTDMFLX5qVNtsqdq";
+
+    fn access_key_deletion() -> StateChangeWithCauseView {
+        state_change(
+            serde_json::json!({ "type": "transaction_processing", "tx_hash": HASH }),
+            "access_key_deletion",
+            serde_json::json!({ "account_id": "alice.near", "public_key": PUBLIC_KEY }),
+        )
+    }
+
+    fn access_key_update(cause: serde_json::Value, nonce: u64) -> StateChangeWithCauseView {
+        state_change(
+            cause,
+            "access_key_update",
+            serde_json::json!({
+                "account_id": "alice.near",
+                "public_key": PUBLIC_KEY,
+                "access_key": { "nonce": nonce, "permission": "FullAccess" }
+            }),
+        )
+    }
+
+    fn data_update() -> StateChangeWithCauseView {
+        state_change(
+            serde_json::json!({ "type": "receipt_processing", "receipt_hash": RECEIPT_HASH }),
+            "data_update",
+            serde_json::json!({
+                "account_id": "token.near",
+                "key_base64": "dGFjY291bnRz",
+                "value_base64": "AQ=="
+            }),
+        )
+    }
+
+    #[test]
+    fn match_access_key_removal() {
+        let state_change = access_key_deletion();
+
+        let removed_rule = MatchingRule::StateChangeAccessKey {
+            affected_account_id: "*.near".to_string(),
+            change: AccessKeyChange::Removed,
+        };
+        let added_rule = MatchingRule::StateChangeAccessKey {
+            affected_account_id: "alice.near".to_string(),
+            change: AccessKeyChange::Added,
+        };
+
+        assert!(matches_state_change(&removed_rule, &state_change));
+        assert!(!matches_state_change(&added_rule, &state_change));
+    }
+
+    #[test]
+    fn match_access_key_addition_without_nonce_bumps() {
+        let added_rule = MatchingRule::StateChangeAccessKey {
+            affected_account_id: "alice.near".to_string(),
+            change: AccessKeyChange::Added,
+        };
+        let any_rule = MatchingRule::StateChangeAccessKey {
+            affected_account_id: "alice.near".to_string(),
+            change: AccessKeyChange::Any,
+        };
+        let add_key = access_key_update(
+            serde_json::json!({ "type": "receipt_processing", "receipt_hash": RECEIPT_HASH }),
+            93659695000000,
+        );
+        let nonce_bump = access_key_update(
+            serde_json::json!({ "type": "transaction_processing", "tx_hash": HASH }),
+            93659695000001,
+        );
+
+        assert!(matches_state_change(&added_rule, &add_key));
+        assert!(!matches_state_change(&added_rule, &nonce_bump));
+        assert!(matches_state_change(&any_rule, &nonce_bump));
+    }
+
+    #[test]
+    fn match_contract_data_key_prefix() {
+        let state_change = data_update();
+
+        let matching_prefix_rule = MatchingRule::StateChangeContractData {
+            affected_account_id: "token.near".to_string(),
+            key_prefix: "t".to_string(),
+        };
+        let other_prefix_rule = MatchingRule::StateChangeContractData {
+            affected_account_id: "token.near".to_string(),
+            key_prefix: "s".to_string(),
+        };
+        let account_balance_rule = MatchingRule::StateChangeAccountBalance {
+            affected_account_id: "token.near".to_string(),
+        };
+
+        assert!(matches_state_change(&matching_prefix_rule, &state_change));
+        assert!(!matches_state_change(&other_prefix_rule, &state_change));
+        assert!(!matches_state_change(&account_balance_rule, &state_change));
+    }
+
+    #[test]
+    fn payload_includes_cause() {
+        let payload = build_indexer_rule_match_payload(&access_key_deletion(), "block".to_string());
+        assert!(matches!(
+            payload,
+            IndexerRuleMatchPayload::StateChanges {
+                receipt_id: None,
+                transaction_hash: Some(_),
+                ..
+            }
+        ));
+
+        let payload = build_indexer_rule_match_payload(&data_update(), "block".to_string());
+        assert_eq!(payload.receipt_id(), Some(RECEIPT_HASH.to_string()));
+        assert_eq!(payload.transaction_hash(), None);
+    }
+}
@@ -0,0 +1,144 @@
+use std::hash::Hash;
+use std::sync::Arc;
+
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+
+use crate::primitives::StreamerMessage;
+use crate::rule_set::RuleSet;
+use crate::types::indexer_rule_match::{ChainId, ExtractionMode, IndexerRuleMatch};
+
+/// Matches a stream of blocks, e.g. the one from `near_lake_framework::streamer`, against a [RuleSet].
+///
+/// Up to `concurrency` blocks, at least one, are evaluated at the same time on the tokio blocking pool,
+/// while the matches are still yielded in the order of the blocks, keyed by the indexer they belong to.
+/// The first error of `streamer_messages` is yielded as is and the blocks after it keep flowing,
+/// use `try_take_while` or similar to stop on it. Infallible streams can be adapted with `.map(Ok)`.
+pub fn indexer_rule_matches<K, E>(
+    rule_set: Arc<RuleSet<K>>,
+    streamer_messages: impl Stream<Item = Result<StreamerMessage, E>>,
+    chain_id: ChainId,
+    extraction_mode: ExtractionMode,
+    concurrency: usize,
+) -> impl Stream<Item = Result<(K, IndexerRuleMatch), E>>
+where
+    K: Clone + Eq + Hash + Send + Sync + 'static,
+{
+    streamer_messages
+        .map_ok(move |streamer_message| {
+            let rule_set = rule_set.clone();
+            let chain_id = chain_id.clone();
+            tokio::task::spawn_blocking(move || {
+                rule_set.reduce_indexer_rule_matches(&streamer_message, chain_id, extraction_mode)
+            })
+        })
+        .map(|block_matches| async move {
+            match block_matches {
+                Ok(task) => match task.await {
+                    Ok(matches_by_indexer) => Ok(matches_by_indexer),
+                    // the rules engine panicking is a bug, not an error of the stream
+                    Err(join_error) => std::panic::resume_unwind(join_error.into_panic()),
+                },
+                Err(e) => Err(e),
+            }
+        })
+        // no block would ever be polled with a concurrency of 0
+        .buffered(concurrency.max(1))
+        .map_ok(|matches_by_indexer| {
+            stream::iter(
+                matches_by_indexer
+                    .into_iter()
+                    .flat_map(|(key, indexer_rule_matches)| {
+                        indexer_rule_matches
+                            .into_iter()
+                            .map(move |indexer_rule_match| Ok((key.clone(), indexer_rule_match)))
+                    }),
+            )
+        })
+        .try_flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures::stream::{self, StreamExt};
+
+    use super::indexer_rule_matches;
+    use crate::rule_set::RuleSet;
+    use crate::test_utils::streamer_message_with_outcomes;
+    use crate::types::indexer_rule_match::{ChainId, ExtractionMode};
+    use indexer_rule_type::indexer_rule::{
+        IndexerRule, IndexerRuleKind, MatchingRule, PayloadEnrichment,
+    };
+
+    fn streamer_message(block_height: u64) -> crate::primitives::StreamerMessage {
+        let mut streamer_message = streamer_message_with_outcomes(vec![]);
+        streamer_message.block.header.height = block_height;
+        streamer_message
+    }
+
+    fn every_block_rule_set() -> Arc<RuleSet<&'static str>> {
+        Arc::new(RuleSet::new([(
+            "every_block",
+            IndexerRule {
+                indexer_rule_kind: IndexerRuleKind::AnyBlock,
+                matching_rule: MatchingRule::AnyBlock,
+                id: None,
+                name: None,
+                payload_enrichment: PayloadEnrichment::None,
+            },
+        )]))
+    }
+
+    #[tokio::test]
+    async fn yield_matches_in_block_order() {
+        let rule_set = every_block_rule_set();
+        let streamer_messages = stream::iter([
+            Ok(streamer_message(1)),
+            Ok(streamer_message(2)),
+            Err("lake error"),
+            Ok(streamer_message(3)),
+        ]);
+
+        let matches: Vec<_> = indexer_rule_matches(
+            rule_set,
+            streamer_messages,
+            ChainId::Mainnet,
+            ExtractionMode::All,
+            2,
+        )
+        .map(|indexer_rule_match| {
+            indexer_rule_match
+                .map(|(key, indexer_rule_match)| (key, indexer_rule_match.block_height))
+        })
+        .collect()
+        .await;
+
+        assert_eq!(
+            matches,
+            vec![
+                Ok(("every_block", 1)),
+                Ok(("every_block", 2)),
+                Err("lake error"),
+                Ok(("every_block", 3)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn evaluate_blocks_one_by_one_without_concurrency() {
+        let streamer_messages = stream::iter([Ok::<_, ()>(streamer_message(1))]);
+
+        let matches: Vec<_> = indexer_rule_matches(
+            every_block_rule_set(),
+            streamer_messages,
+            ChainId::Mainnet,
+            ExtractionMode::All,
+            0,
+        )
+        .collect()
+        .await;
+
+        assert_eq!(matches.len(), 1);
+    }
+}
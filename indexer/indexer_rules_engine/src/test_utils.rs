@@ -0,0 +1,90 @@
+//! Fixtures shared by the unit tests, each test overrides the fields it checks
+use crate::primitives::views::StateChangeWithCauseView;
+use crate::primitives::{IndexerExecutionOutcomeWithReceipt, StreamerMessage};
+
+pub(crate) const HASH: &str = "11111111111111111111111111111111";
+
+/// Successful receipt of `actions` sent by bob.near to alice.near
+pub(crate) fn outcome_with_actions(
+    actions: serde_json::Value,
+) -> IndexerExecutionOutcomeWithReceipt {
+    serde_json::from_value(serde_json::json!({
+        "execution_outcome": {
+            "proof": [],
+            "block_hash": "5ruF5K1Cg4RKtKYmsYvH6hbxHufvxTDMFLX5qVNtsqdq",
+            "id": "9ZvmRqDwAXtTCqtyMCpTGwZ8VBsJnNnhTB2vHDSbvhUZ",
+            "outcome": {
+                "logs": [],
+                "receipt_ids": [],
+                "gas_burnt": 0,
+                "tokens_burnt": "0",
+                "executor_id": "alice.near",
+                "status": { "SuccessValue": "" },
+                "metadata": { "version": 1, "gas_profile": null }
+            }
+        },
+        "receipt": {
+            "predecessor_id": "bob.near",
+            "receiver_id": "alice.near",
+            "receipt_id": "9ZvmRqDwAXtTCqtyMCpTGwZ8VBsJnNnhTB2vHDSbvhUZ",
+            "receipt": {
+                "Action": {
+                    "signer_id": "bob.near",
+                    "signer_public_key": "ed25519:CE3QAXyVLeScmY9YeEyR3Tw9yXfjBPzFLzroTranYtVb",
+                    "gas_price": "100000000",
+                    "output_data_receivers": [],
+                    "input_data_ids": [],
+                    "actions": actions
+                }
+            }
+        }
+    }))
+    .unwrap()
+}
+
+/// Block with a single shard, without chunk, which executed the receipts
+pub(crate) fn streamer_message_with_outcomes(
+    receipt_execution_outcomes: Vec<IndexerExecutionOutcomeWithReceipt>,
+) -> StreamerMessage {
+    serde_json::from_value(serde_json::json!({
+        "block": {
+            "author": "validator.near",
+            "header": {
+                "height": 1, "prev_height": 0, "epoch_id": HASH, "next_epoch_id": HASH,
+                "hash": HASH, "prev_hash": HASH, "prev_state_root": HASH,
+                "chunk_receipts_root": HASH, "chunk_headers_root": HASH, "chunk_tx_root": HASH,
+                "outcome_root": HASH, "chunks_included": 1, "challenges_root": HASH,
+                "timestamp": 0, "timestamp_nanosec": "0", "random_value": HASH,
+                "validator_proposals": [], "chunk_mask": [true], "gas_price": "0",
+                "block_ordinal": 1, "rent_paid": "0", "validator_reward": "0",
+                "total_supply": "0", "challenges_result": [], "last_final_block": HASH,
+                "last_ds_final_block": HASH, "next_bp_hash": HASH, "block_merkle_root": HASH,
+                "epoch_sync_data_hash": null, "approvals": [],
+                "signature": format!("ed25519:{}", "1".repeat(64)),
+                "latest_protocol_version": 1
+            },
+            "chunks": []
+        },
+        "shards": [{
+            "shard_id": 0,
+            "chunk": null,
+            "receipt_execution_outcomes": receipt_execution_outcomes,
+            "state_changes": []
+        }]
+    }))
+    .unwrap()
+}
+
+/// State change of type `change_type`, e.g. `access_key_update`, with its `cause` and `change`
+pub(crate) fn state_change(
+    cause: serde_json::Value,
+    change_type: &str,
+    change: serde_json::Value,
+) -> StateChangeWithCauseView {
+    serde_json::from_value(serde_json::json!({
+        "cause": cause,
+        "type": change_type,
+        "change": change
+    }))
+    .unwrap()
+}
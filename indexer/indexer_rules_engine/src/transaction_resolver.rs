@@ -0,0 +1,290 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::primitives::{CryptoHash, StreamerMessage};
+use crate::types::indexer_rule_match::{IndexerRuleMatch, ReceiptIdString, TransactionHashString};
+
+/// What created a receipt executed in a later block, as kept in the receipts watching list
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReceiptOrigin {
+    Transaction(TransactionHashString),
+    /// Receipt whose transaction was not resolved when its block was indexed, its own origin
+    /// is in the watching list
+    Receipt(ReceiptIdString),
+}
+
+const TRANSACTION_ORIGIN_PREFIX: &str = "transaction:";
+const RECEIPT_ORIGIN_PREFIX: &str = "receipt:";
+
+impl fmt::Display for ReceiptOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReceiptOrigin::Transaction(transaction_hash) => {
+                write!(f, "{}{}", TRANSACTION_ORIGIN_PREFIX, transaction_hash)
+            }
+            ReceiptOrigin::Receipt(receipt_id) => {
+                write!(f, "{}{}", RECEIPT_ORIGIN_PREFIX, receipt_id)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for ReceiptOrigin {
+    type Err = anyhow::Error;
+
+    fn from_str(origin: &str) -> Result<Self, Self::Err> {
+        if let Some(transaction_hash) = origin.strip_prefix(TRANSACTION_ORIGIN_PREFIX) {
+            Ok(ReceiptOrigin::Transaction(transaction_hash.to_string()))
+        } else if let Some(receipt_id) = origin.strip_prefix(RECEIPT_ORIGIN_PREFIX) {
+            Ok(ReceiptOrigin::Receipt(receipt_id.to_string()))
+        } else {
+            anyhow::bail!("Unknown receipt origin {}", origin)
+        }
+    }
+}
+
+/// Hash of the transaction which originated each receipt executed or created in a block.
+///
+/// Receipts converted from a transaction of the same block resolve from the block itself, receipts
+/// created in earlier blocks resolve from the hashes passed in by the caller, which are usually read
+/// from the receipts watching list in `storage`.
+#[derive(Debug, Default, Clone)]
+pub struct ReceiptTransactionHashes {
+    transaction_hashes: HashMap<ReceiptIdString, TransactionHashString>,
+    outgoing_receipts: Vec<(ReceiptIdString, ReceiptOrigin)>,
+}
+
+impl ReceiptTransactionHashes {
+    pub fn from_block(
+        streamer_message: &StreamerMessage,
+        previous_blocks_transaction_hashes: HashMap<ReceiptIdString, TransactionHashString>,
+    ) -> Self {
+        let transactions = streamer_message
+            .shards
+            .iter()
+            .filter_map(|shard| shard.chunk.as_ref())
+            .flat_map(|chunk| chunk.transactions.iter())
+            .map(|transaction| {
+                (
+                    transaction.transaction.hash.to_string(),
+                    receipt_ids_to_strings(
+                        &transaction.outcome.execution_outcome.outcome.receipt_ids,
+                    ),
+                )
+            })
+            .collect();
+        let executed_receipts = streamer_message
+            .shards
+            .iter()
+            .flat_map(|shard| shard.receipt_execution_outcomes.iter())
+            .map(|receipt_execution_outcome| {
+                (
+                    receipt_execution_outcome.receipt.receipt_id.to_string(),
+                    receipt_ids_to_strings(
+                        &receipt_execution_outcome
+                            .execution_outcome
+                            .outcome
+                            .receipt_ids,
+                    ),
+                )
+            })
+            .collect();
+
+        Self::resolve(
+            previous_blocks_transaction_hashes,
+            transactions,
+            executed_receipts,
+        )
+    }
+
+    /// Transactions and executed receipts are paired with the receipts they created
+    fn resolve(
+        previous_blocks_transaction_hashes: HashMap<ReceiptIdString, TransactionHashString>,
+        transactions: Vec<(TransactionHashString, Vec<ReceiptIdString>)>,
+        executed_receipts: Vec<(ReceiptIdString, Vec<ReceiptIdString>)>,
+    ) -> Self {
+        let mut transaction_hashes = previous_blocks_transaction_hashes;
+
+        for (transaction_hash, receipt_ids) in &transactions {
+            for receipt_id in receipt_ids {
+                transaction_hashes.insert(receipt_id.clone(), transaction_hash.clone());
+            }
+        }
+
+        // only receipts converted from transactions are executed in the block which created them,
+        // so a single pass resolves the receipts created by every executed receipt
+        let mut parent_receipt_ids = HashMap::new();
+        for (receipt_id, child_receipt_ids) in &executed_receipts {
+            let transaction_hash = transaction_hashes.get(receipt_id).cloned();
+            for child_receipt_id in child_receipt_ids {
+                match &transaction_hash {
+                    Some(transaction_hash) => {
+                        transaction_hashes
+                            .insert(child_receipt_id.clone(), transaction_hash.clone());
+                    }
+                    None => {
+                        parent_receipt_ids.insert(child_receipt_id, receipt_id);
+                    }
+                }
+            }
+        }
+
+        // receipts created here and executed in later blocks need to be persisted to resolve then,
+        // through the receipt which created them if its own transaction is not known yet
+        let executed_receipt_ids: HashSet<&ReceiptIdString> = executed_receipts
+            .iter()
+            .map(|(receipt_id, _)| receipt_id)
+            .collect();
+        let outgoing_receipts = transactions
+            .iter()
+            .chain(executed_receipts.iter())
+            .flat_map(|(_, receipt_ids)| receipt_ids.iter())
+            .filter(|receipt_id| !executed_receipt_ids.contains(receipt_id))
+            .filter_map(|receipt_id| {
+                let origin = match transaction_hashes.get(receipt_id) {
+                    Some(transaction_hash) => ReceiptOrigin::Transaction(transaction_hash.clone()),
+                    None => ReceiptOrigin::Receipt(parent_receipt_ids.get(receipt_id)?.to_string()),
+                };
+                Some((receipt_id.clone(), origin))
+            })
+            .collect();
+
+        Self {
+            transaction_hashes,
+            outgoing_receipts,
+        }
+    }
+
+    pub fn get(&self, receipt_id: &str) -> Option<&TransactionHashString> {
+        self.transaction_hashes.get(receipt_id)
+    }
+
+    /// Receipts created in the block which are executed in later blocks
+    pub fn outgoing_receipts(&self) -> &[(ReceiptIdString, ReceiptOrigin)] {
+        &self.outgoing_receipts
+    }
+
+    /// Sets the transaction hash of every match which has a resolvable receipt
+    pub fn populate(&self, indexer_rule_matches: &mut [IndexerRuleMatch]) {
+        for indexer_rule_match in indexer_rule_matches.iter_mut() {
+            if indexer_rule_match.payload.transaction_hash().is_none() {
+                if let Some(transaction_hash) = indexer_rule_match
+                    .payload
+                    .receipt_id()
+                    .and_then(|receipt_id| self.get(&receipt_id).cloned())
+                {
+                    indexer_rule_match
+                        .payload
+                        .set_transaction_hash(transaction_hash);
+                }
+            }
+        }
+    }
+}
+
+/// Receipts executed in the block which were not created by one of its transactions, their
+/// origins have to be read from the receipts watching list
+pub fn receipts_from_previous_blocks(streamer_message: &StreamerMessage) -> Vec<ReceiptIdString> {
+    let converted_receipts: HashSet<String> = streamer_message
+        .shards
+        .iter()
+        .filter_map(|shard| shard.chunk.as_ref())
+        .flat_map(|chunk| chunk.transactions.iter())
+        .flat_map(|transaction| {
+            transaction
+                .outcome
+                .execution_outcome
+                .outcome
+                .receipt_ids
+                .iter()
+        })
+        .map(|receipt_id| receipt_id.to_string())
+        .collect();
+
+    streamer_message
+        .shards
+        .iter()
+        .flat_map(|shard| shard.receipt_execution_outcomes.iter())
+        .map(|receipt_execution_outcome| receipt_execution_outcome.receipt.receipt_id.to_string())
+        .filter(|receipt_id| !converted_receipts.contains(receipt_id))
+        .collect()
+}
+
+fn receipt_ids_to_strings(receipt_ids: &[CryptoHash]) -> Vec<ReceiptIdString> {
+    receipt_ids
+        .iter()
+        .map(|receipt_id| receipt_id.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReceiptOrigin, ReceiptTransactionHashes};
+    use std::collections::HashMap;
+
+    fn receipt_ids(receipt_ids: &[&str]) -> Vec<String> {
+        receipt_ids
+            .iter()
+            .map(|receipt_id| receipt_id.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn resolve_receipts_of_block_and_watching_list() {
+        let previous_blocks_transaction_hashes =
+            HashMap::from([("old_receipt".to_string(), "old_tx".to_string())]);
+
+        let receipt_transaction_hashes = ReceiptTransactionHashes::resolve(
+            previous_blocks_transaction_hashes,
+            vec![
+                ("local_tx".to_string(), receipt_ids(&["local_receipt"])),
+                ("remote_tx".to_string(), receipt_ids(&["remote_receipt"])),
+            ],
+            vec![
+                ("local_receipt".to_string(), receipt_ids(&["local_child"])),
+                ("old_receipt".to_string(), receipt_ids(&["old_child"])),
+                (
+                    "unknown_receipt".to_string(),
+                    receipt_ids(&["unknown_child"]),
+                ),
+            ],
+        );
+
+        assert_eq!(
+            receipt_transaction_hashes.get("local_receipt"),
+            Some(&"local_tx".to_string())
+        );
+        assert_eq!(
+            receipt_transaction_hashes.get("old_receipt"),
+            Some(&"old_tx".to_string())
+        );
+        assert_eq!(receipt_transaction_hashes.get("unknown_receipt"), None);
+
+        let mut outgoing_receipts = receipt_transaction_hashes.outgoing_receipts().to_vec();
+        outgoing_receipts.sort();
+        assert_eq!(
+            outgoing_receipts,
+            vec![
+                (
+                    "local_child".to_string(),
+                    ReceiptOrigin::Transaction("local_tx".to_string())
+                ),
+                (
+                    "old_child".to_string(),
+                    ReceiptOrigin::Transaction("old_tx".to_string())
+                ),
+                (
+                    "remote_receipt".to_string(),
+                    ReceiptOrigin::Transaction("remote_tx".to_string())
+                ),
+                (
+                    "unknown_child".to_string(),
+                    ReceiptOrigin::Receipt("unknown_receipt".to_string())
+                ),
+            ]
+        );
+        for (_, origin) in outgoing_receipts {
+            assert_eq!(origin.to_string().parse::<ReceiptOrigin>().unwrap(), origin);
+        }
+    }
+}
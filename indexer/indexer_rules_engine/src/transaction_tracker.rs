@@ -0,0 +1,339 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::matcher;
+use crate::primitives::{
+    views::SignedTransactionView, IndexerExecutionOutcomeWithReceipt, StreamerMessage,
+};
+use crate::rule_set;
+use crate::transaction_resolver::ReceiptTransactionHashes;
+use crate::types::indexer_rule_match::{
+    ChainId, IndexerRuleMatch, IndexerRuleMatchPayload, ReceiptIdString, TransactionHashString,
+};
+use crate::types::transactions::TransactionDetails;
+use indexer_rule_type::indexer_rule::IndexerRule;
+use wildmatch::WildMatch;
+
+/// Transaction whose receipt tree is still executing, persisted between blocks by the caller
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct PendingTransaction {
+    pub details: TransactionDetails,
+    /// Receipts created by the transaction or its receipts which haven't been executed yet
+    pub pending_receipt_ids: Vec<ReceiptIdString>,
+}
+
+impl PendingTransaction {
+    fn add_receipt(&mut self, receipt_execution_outcome: &IndexerExecutionOutcomeWithReceipt) {
+        let receipt_id = receipt_execution_outcome.receipt.receipt_id.to_string();
+        self.pending_receipt_ids
+            .retain(|pending_receipt_id| pending_receipt_id != &receipt_id);
+        self.pending_receipt_ids.extend(
+            receipt_execution_outcome
+                .execution_outcome
+                .outcome
+                .receipt_ids
+                .iter()
+                .map(|receipt_id| receipt_id.to_string()),
+        );
+        self.details.add_receipt(receipt_execution_outcome);
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.pending_receipt_ids.is_empty()
+    }
+}
+
+/// Accounts the signer or receiver of a transaction has to match for any of the transaction
+/// rules to match it, other transactions are not tracked
+#[derive(Debug)]
+pub struct TransactionFilter {
+    /// `None` when a rule has no account pattern to filter by, e.g. `Not`
+    account_patterns: Option<Vec<WildMatch>>,
+}
+
+impl TransactionFilter {
+    pub fn new<'a>(indexer_rules: impl IntoIterator<Item = &'a IndexerRule>) -> Self {
+        Self {
+            account_patterns: indexer_rules
+                .into_iter()
+                .map(|indexer_rule| rule_set::account_patterns(&indexer_rule.matching_rule))
+                .collect::<Option<Vec<_>>>()
+                .map(|account_patterns| {
+                    account_patterns
+                        .concat()
+                        .into_iter()
+                        .map(WildMatch::new)
+                        .collect()
+                }),
+        }
+    }
+
+    pub fn matches(&self, transaction: &SignedTransactionView) -> bool {
+        match &self.account_patterns {
+            Some(account_patterns) => account_patterns.iter().any(|account_pattern| {
+                account_pattern.matches(transaction.signer_id.as_str())
+                    || account_pattern.matches(transaction.receiver_id.as_str())
+            }),
+            None => true,
+        }
+    }
+}
+
+/// Transactions started in earlier blocks which have receipts executed in this block,
+/// their [PendingTransaction] has to be loaded before calling [track_transactions]
+pub fn pending_transaction_hashes(
+    streamer_message: &StreamerMessage,
+    receipt_transaction_hashes: &ReceiptTransactionHashes,
+) -> HashSet<TransactionHashString> {
+    let block_transactions: HashSet<String> = streamer_message
+        .shards
+        .iter()
+        .filter_map(|shard| shard.chunk.as_ref())
+        .flat_map(|chunk| chunk.transactions.iter())
+        .map(|transaction| transaction.transaction.hash.to_string())
+        .collect();
+
+    streamer_message
+        .shards
+        .iter()
+        .flat_map(|shard| shard.receipt_execution_outcomes.iter())
+        .filter_map(|receipt_execution_outcome| {
+            receipt_transaction_hashes
+                .get(&receipt_execution_outcome.receipt.receipt_id.to_string())
+        })
+        .filter(|transaction_hash| !block_transactions.contains(*transaction_hash))
+        .cloned()
+        .collect()
+}
+
+/// Adds the transactions of the block passing `transaction_filter` and the receipts it executed to
+/// `pending_transactions`, then removes and returns the transactions whose receipt tree has completely executed.
+/// Receipts of transactions missing from `pending_transactions`, e.g. started before tracking did, are skipped.
+pub fn track_transactions(
+    streamer_message: &StreamerMessage,
+    receipt_transaction_hashes: &ReceiptTransactionHashes,
+    transaction_filter: &TransactionFilter,
+    pending_transactions: &mut HashMap<TransactionHashString, PendingTransaction>,
+) -> Vec<TransactionDetails> {
+    for transaction in streamer_message
+        .shards
+        .iter()
+        .filter_map(|shard| shard.chunk.as_ref())
+        .flat_map(|chunk| chunk.transactions.iter())
+        .filter(|transaction| transaction_filter.matches(&transaction.transaction))
+    {
+        pending_transactions.insert(
+            transaction.transaction.hash.to_string(),
+            PendingTransaction {
+                details: TransactionDetails::from_indexer_tx(transaction.clone()),
+                pending_receipt_ids: transaction
+                    .outcome
+                    .execution_outcome
+                    .outcome
+                    .receipt_ids
+                    .iter()
+                    .map(|receipt_id| receipt_id.to_string())
+                    .collect(),
+            },
+        );
+    }
+
+    for receipt_execution_outcome in streamer_message
+        .shards
+        .iter()
+        .flat_map(|shard| shard.receipt_execution_outcomes.iter())
+    {
+        if let Some(pending_transaction) = receipt_transaction_hashes
+            .get(&receipt_execution_outcome.receipt.receipt_id.to_string())
+            .and_then(|transaction_hash| pending_transactions.get_mut(transaction_hash))
+        {
+            pending_transaction.add_receipt(receipt_execution_outcome);
+        }
+    }
+
+    let completed_transaction_hashes: Vec<TransactionHashString> = pending_transactions
+        .iter()
+        .filter(|(_, pending_transaction)| pending_transaction.is_complete())
+        .map(|(transaction_hash, _)| transaction_hash.clone())
+        .collect();
+
+    completed_transaction_hashes
+        .iter()
+        .filter_map(|transaction_hash| pending_transactions.remove(transaction_hash))
+        .map(|pending_transaction| pending_transaction.details)
+        .collect()
+}
+
+/// One match per completed transaction with at least one receipt matching the rule
+pub fn reduce_transaction_matches(
+    indexer_rule: &IndexerRule,
+    completed_transactions: &[TransactionDetails],
+    streamer_message: &StreamerMessage,
+    chain_id: ChainId,
+) -> Vec<IndexerRuleMatch> {
+    completed_transactions
+        .iter()
+        .filter(|transaction| {
+            transaction
+                .receipt_execution_outcomes()
+                .any(|receipt_execution_outcome| {
+                    matcher::matches(&indexer_rule.matching_rule, &receipt_execution_outcome)
+                })
+        })
+        .map(|transaction| IndexerRuleMatch {
+            chain_id: chain_id.clone(),
+            indexer_rule_id: indexer_rule.id,
+            indexer_rule_name: indexer_rule.name.clone(),
+            payload: IndexerRuleMatchPayload::Transaction {
+                block_hash: streamer_message.block.header.hash.to_string(),
+                transaction_hash: transaction.transaction.hash.to_string(),
+                transaction: transaction.clone(),
+            },
+            block_height: streamer_message.block.header.height,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{
+        reduce_transaction_matches, track_transactions, PendingTransaction, TransactionFilter,
+    };
+    use crate::test_utils::{outcome_with_actions, streamer_message_with_outcomes, HASH};
+    use crate::transaction_resolver::ReceiptTransactionHashes;
+    use crate::types::indexer_rule_match::{ChainId, IndexerRuleMatchPayload};
+    use crate::types::transactions::TransactionDetails;
+    use indexer_rule_type::indexer_rule::{
+        IndexerRule, IndexerRuleKind, MatchingRule, PayloadEnrichment, Status,
+    };
+
+    const FIRST_RECEIPT_ID: &str = "9ZvmRqDwAXtTCqtyMCpTGwZ8VBsJnNnhTB2vHDSbvhUZ";
+    const SECOND_RECEIPT_ID: &str = "5ruF5K1Cg4RKtKYmsYvH6hbxHufvxTDMFLX5qVNtsqdq";
+
+    fn pending_transaction() -> PendingTransaction {
+        let transaction_outcome = outcome_with_actions(serde_json::json!([]));
+        PendingTransaction {
+            details: TransactionDetails {
+                transaction: serde_json::from_value(serde_json::json!({
+                    "signer_id": "bob.near",
+                    "public_key": "ed25519:CE3QAXyVLeScmY9YeEyR3Tw9yXfjBPzFLzroTranYtVb",
+                    "nonce": 1,
+                    "receiver_id": "alice.near",
+                    "actions": [],
+                    "signature": format!("ed25519:{}", "1".repeat(64)),
+                    "hash": HASH
+                }))
+                .unwrap(),
+                receipts: vec![],
+                execution_outcomes: vec![transaction_outcome.execution_outcome],
+            },
+            pending_receipt_ids: vec![FIRST_RECEIPT_ID.to_string()],
+        }
+    }
+
+    fn transaction_rule(matching_rule: MatchingRule) -> IndexerRule {
+        IndexerRule {
+            indexer_rule_kind: IndexerRuleKind::Transaction,
+            matching_rule,
+            id: None,
+            name: None,
+            payload_enrichment: PayloadEnrichment::None,
+        }
+    }
+
+    fn action_any(affected_account_id: &str) -> MatchingRule {
+        MatchingRule::ActionAny {
+            affected_account_id: affected_account_id.to_string(),
+            status: Status::Success,
+        }
+    }
+
+    #[test]
+    fn filter_transactions_by_signer_and_receiver() {
+        let transaction = pending_transaction().details.transaction;
+        let transaction_filter = |matching_rules: Vec<MatchingRule>| {
+            let indexer_rules: Vec<IndexerRule> =
+                matching_rules.into_iter().map(transaction_rule).collect();
+            TransactionFilter::new(&indexer_rules)
+        };
+
+        assert!(transaction_filter(vec![action_any("bob.near")]).matches(&transaction));
+        assert!(transaction_filter(vec![
+            action_any("carol.near"),
+            action_any("*.alice.near, alice.near")
+        ])
+        .matches(&transaction));
+        assert!(!transaction_filter(vec![action_any("app.alice.near")]).matches(&transaction));
+        assert!(!transaction_filter(vec![]).matches(&transaction));
+        assert!(transaction_filter(vec![MatchingRule::Not {
+            matching_rules: vec![action_any("bob.near")].into(),
+        }])
+        .matches(&transaction));
+    }
+
+    #[test]
+    fn complete_transaction_spanning_blocks() {
+        let transaction_filter = TransactionFilter::new(&[transaction_rule(action_any("*.near"))]);
+        let mut pending_transactions = HashMap::from([(HASH.to_string(), pending_transaction())]);
+
+        // the first receipt calls a contract, which creates the second receipt for the next block
+        let mut first_receipt = outcome_with_actions(serde_json::json!(["CreateAccount"]));
+        first_receipt.execution_outcome.outcome.receipt_ids =
+            vec![SECOND_RECEIPT_ID.parse().unwrap()];
+        let first_block = streamer_message_with_outcomes(vec![first_receipt]);
+        let receipt_transaction_hashes = ReceiptTransactionHashes::from_block(
+            &first_block,
+            HashMap::from([(FIRST_RECEIPT_ID.to_string(), HASH.to_string())]),
+        );
+        assert!(track_transactions(
+            &first_block,
+            &receipt_transaction_hashes,
+            &transaction_filter,
+            &mut pending_transactions
+        )
+        .is_empty());
+        assert_eq!(
+            pending_transactions[HASH].pending_receipt_ids,
+            vec![SECOND_RECEIPT_ID.to_string()]
+        );
+
+        let mut second_receipt = outcome_with_actions(serde_json::json!(["CreateAccount"]));
+        second_receipt.receipt.receipt_id = SECOND_RECEIPT_ID.parse().unwrap();
+        second_receipt.receipt.receiver_id = "app.alice.near".parse().unwrap();
+        let second_block = streamer_message_with_outcomes(vec![second_receipt]);
+        let receipt_transaction_hashes = ReceiptTransactionHashes::from_block(
+            &second_block,
+            HashMap::from([(SECOND_RECEIPT_ID.to_string(), HASH.to_string())]),
+        );
+        let completed_transactions = track_transactions(
+            &second_block,
+            &receipt_transaction_hashes,
+            &transaction_filter,
+            &mut pending_transactions,
+        );
+        assert!(pending_transactions.is_empty());
+        assert_eq!(completed_transactions.len(), 1);
+        assert_eq!(completed_transactions[0].receipts.len(), 2);
+        assert_eq!(completed_transactions[0].execution_outcomes.len(), 3);
+
+        let matches = reduce_transaction_matches(
+            &transaction_rule(action_any("app.alice.near")),
+            &completed_transactions,
+            &second_block,
+            ChainId::Mainnet,
+        );
+        assert_eq!(matches.len(), 1);
+        assert!(matches!(
+            &matches[0].payload,
+            IndexerRuleMatchPayload::Transaction { transaction_hash, .. } if transaction_hash == HASH
+        ));
+        assert!(reduce_transaction_matches(
+            &transaction_rule(action_any("carol.near")),
+            &completed_transactions,
+            &second_block,
+            ChainId::Mainnet,
+        )
+        .is_empty());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::types::indexer_rule_match::ChainId;
+
+/// Where the blocks, RPC nodes and explorer of a chain live. Mainnet and testnet are built in,
+/// other networks such as localnets are described by a JSON profile.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct ChainProfile {
+    pub chain_id: ChainId,
+    pub lake_bucket: String,
+    pub lake_region: String,
+    /// Blocks more than 5 epochs old are only served by archival nodes
+    pub archival_rpc_url: String,
+    /// Delta lake index of the accounts receiving actions, chains without one can only back fill
+    /// `AnyBlock` indexers
+    #[serde(default)]
+    pub index_files: Option<IndexFiles>,
+    /// `{transaction_hash}` is replaced by the hash of the transaction
+    pub explorer_transaction_url: String,
+    /// `{block_hash}` is replaced by the hash of the block
+    pub explorer_block_url: String,
+}
+
+/// Where the index files the historical backfill looks up matching blocks in live
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct IndexFiles {
+    pub bucket: String,
+    /// Folder of the action receipt index files and their `latest_block.json` metadata
+    pub actions_folder: String,
+}
+
+impl ChainProfile {
+    pub fn mainnet() -> Self {
+        Self {
+            chain_id: ChainId::Mainnet,
+            lake_bucket: "near-lake-data-mainnet".to_string(),
+            lake_region: "eu-central-1".to_string(),
+            archival_rpc_url: "https://archival-rpc.mainnet.near.org".to_string(),
+            index_files: Some(IndexFiles {
+                bucket: "near-delta-lake".to_string(),
+                actions_folder: "silver/accounts/action_receipt_actions/metadata".to_string(),
+            }),
+            explorer_transaction_url: "https://explorer.near.org/transactions/{transaction_hash}"
+                .to_string(),
+            explorer_block_url: "https://explorer.near.org/block/{block_hash}".to_string(),
+        }
+    }
+
+    pub fn testnet() -> Self {
+        Self {
+            chain_id: ChainId::Testnet,
+            lake_bucket: "near-lake-data-testnet".to_string(),
+            lake_region: "eu-central-1".to_string(),
+            archival_rpc_url: "https://archival-rpc.testnet.near.org".to_string(),
+            index_files: None,
+            explorer_transaction_url:
+                "https://explorer.testnet.near.org/transactions/{transaction_hash}".to_string(),
+            explorer_block_url: "https://explorer.testnet.near.org/block/{block_hash}".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChainProfile;
+    use crate::types::indexer_rule_match::{ChainId, IndexerRuleMatch, IndexerRuleMatchPayload};
+
+    #[test]
+    fn explorer_link_from_profile() {
+        let localnet: ChainProfile = serde_json::from_value(serde_json::json!({
+            "chain_id": { "Custom": "localnet" },
+            "lake_bucket": "near-lake-data-localnet",
+            "lake_region": "eu-central-1",
+            "archival_rpc_url": "http://127.0.0.1:3030",
+            "explorer_transaction_url": "http://127.0.0.1:3000/transactions/{transaction_hash}",
+            "explorer_block_url": "http://127.0.0.1:3000/blocks/{block_hash}"
+        }))
+        .unwrap();
+        let mut indexer_rule_match = IndexerRuleMatch {
+            chain_id: localnet.chain_id.clone(),
+            indexer_rule_id: None,
+            indexer_rule_name: None,
+            payload: IndexerRuleMatchPayload::Actions {
+                block_hash: "block".to_string(),
+                receipt_id: "receipt".to_string(),
+                transaction_hash: None,
+                receipt_details: None,
+            },
+            block_height: 1,
+        };
+
+        assert_eq!(localnet.chain_id.to_string(), "localnet");
+        assert!(localnet.index_files.is_none());
+        assert_eq!(
+            indexer_rule_match.explorer_link(&localnet),
+            "http://127.0.0.1:3000/blocks/block"
+        );
+
+        indexer_rule_match
+            .payload
+            .set_transaction_hash("tx".to_string());
+        assert_eq!(
+            indexer_rule_match.explorer_link(&localnet),
+            "http://127.0.0.1:3000/transactions/tx#receipt"
+        );
+        assert_eq!(
+            indexer_rule_match.explorer_link(&ChainProfile::mainnet()),
+            "https://explorer.near.org/transactions/tx#receipt"
+        );
+        assert!(matches!(ChainProfile::testnet().chain_id, ChainId::Testnet));
+    }
+}
@@ -1,5 +1,10 @@
 use std::fmt;
 
+use crate::types::chain_profile::ChainProfile;
+use crate::types::receipts::ReceiptDetails;
+use crate::types::standard_events::Transfer;
+use crate::types::transactions::TransactionDetails;
+
 pub type TransactionHashString = String;
 pub type ReceiptIdString = String;
 pub type BlockHashString = String;
@@ -21,42 +26,20 @@ pub struct IndexerRuleMatch {
 }
 
 impl IndexerRuleMatch {
-    pub fn explorer_link(&self) -> String {
-        match self.chain_id {
-            ChainId::Testnet => {
-                if let Some(tx_hash) = self.payload.transaction_hash() {
-                    if let Some(receipt_id) = self.payload.receipt_id() {
-                        format!(
-                            "https://explorer.testnet.near.org/transactions/{}#{}",
-                            tx_hash, receipt_id,
-                        )
-                    } else {
-                        format!("https://explorer.testnet.near.org/transactions/{}", tx_hash)
-                    }
-                } else {
-                    format!(
-                        "https://explorer.testnet.near.org/block/{}",
-                        self.payload.block_hash()
-                    )
-                }
-            }
-            ChainId::Mainnet => {
-                if let Some(tx_hash) = self.payload.transaction_hash() {
-                    if let Some(receipt_id) = self.payload.receipt_id() {
-                        format!(
-                            "https://explorer.near.org/transactions/{}#{}",
-                            tx_hash, receipt_id,
-                        )
-                    } else {
-                        format!("https://explorer.near.org/transactions/{}", tx_hash)
-                    }
-                } else {
-                    format!(
-                        "https://explorer.near.org/block/{}",
-                        self.payload.block_hash()
-                    )
+    pub fn explorer_link(&self, chain_profile: &ChainProfile) -> String {
+        match self.payload.transaction_hash() {
+            Some(tx_hash) => {
+                let transaction_url = chain_profile
+                    .explorer_transaction_url
+                    .replace("{transaction_hash}", &tx_hash);
+                match self.payload.receipt_id() {
+                    Some(receipt_id) => format!("{}#{}", transaction_url, receipt_id),
+                    None => transaction_url,
                 }
             }
+            None => chain_profile
+                .explorer_block_url
+                .replace("{block_hash}", &self.payload.block_hash()),
         }
     }
 }
@@ -74,6 +57,9 @@ pub enum IndexerRuleMatchPayload {
         block_hash: BlockHashString,
         receipt_id: ReceiptIdString,
         transaction_hash: Option<TransactionHashString>,
+        /// Only set for rules opting in with `PayloadEnrichment::Actions`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        receipt_details: Option<ReceiptDetails>,
     },
     Events {
         block_hash: BlockHashString,
@@ -83,12 +69,28 @@ pub enum IndexerRuleMatchPayload {
         standard: String,
         version: String,
         data: Option<String>,
+        /// Tokens moved by NEP-141, NEP-171 and NEP-245 events, empty for other events
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        transfers: Vec<Transfer>,
+        /// Why the `data` of a standard event doesn't follow its standard
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        validation_error: Option<String>,
     },
     StateChanges {
         block_hash: BlockHashString,
         receipt_id: Option<ReceiptIdString>,
         transaction_hash: Option<TransactionHashString>,
     },
+    Block {
+        block_hash: BlockHashString,
+    },
+    /// Transaction matched by an `IndexerRuleKind::Transaction` rule, `block_hash` is the block
+    /// which executed the last receipt of the transaction
+    Transaction {
+        block_hash: BlockHashString,
+        transaction_hash: TransactionHashString,
+        transaction: TransactionDetails,
+    },
 }
 
 impl IndexerRuleMatchPayload {
@@ -96,7 +98,9 @@ impl IndexerRuleMatchPayload {
         match self {
             Self::Actions { block_hash, .. }
             | Self::Events { block_hash, .. }
-            | Self::StateChanges { block_hash, .. } => block_hash.to_string(),
+            | Self::StateChanges { block_hash, .. }
+            | Self::Transaction { block_hash, .. }
+            | Self::Block { block_hash } => block_hash.to_string(),
         }
     }
 
@@ -106,6 +110,7 @@ impl IndexerRuleMatchPayload {
                 Some(receipt_id.to_string())
             }
             Self::StateChanges { receipt_id, .. } => receipt_id.clone(),
+            Self::Block { .. } | Self::Transaction { .. } => None,
         }
     }
 
@@ -120,6 +125,45 @@ impl IndexerRuleMatchPayload {
             | Self::StateChanges {
                 transaction_hash, ..
             } => transaction_hash.clone(),
+            Self::Transaction {
+                transaction_hash, ..
+            } => Some(transaction_hash.clone()),
+            Self::Block { .. } => None,
+        }
+    }
+
+    pub fn set_transaction_hash(&mut self, hash: TransactionHashString) {
+        match self {
+            Self::Actions {
+                transaction_hash, ..
+            }
+            | Self::Events {
+                transaction_hash, ..
+            }
+            | Self::StateChanges {
+                transaction_hash, ..
+            } => *transaction_hash = Some(hash),
+            Self::Block { .. } | Self::Transaction { .. } => {}
+        }
+    }
+}
+
+/// How many matches the reducers extract from a block
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExtractionMode {
+    /// First matching receipt or state change of each shard, enough to tell whether the block matches
+    #[default]
+    FirstPerShard,
+    /// Every matching receipt and state change, with one match per matching event log
+    All,
+}
+
+impl ExtractionMode {
+    /// Matches taken from each shard, and events taken from each receipt
+    pub fn limit(&self) -> usize {
+        match self {
+            ExtractionMode::FirstPerShard => 1,
+            ExtractionMode::All => usize::MAX,
         }
     }
 }
@@ -135,12 +179,15 @@ impl IndexerRuleMatchPayload {
 pub enum ChainId {
     Mainnet,
     Testnet,
+    /// Any other network, e.g. a localnet, described by its [ChainProfile]
+    Custom(String),
 }
 impl fmt::Display for ChainId {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             ChainId::Mainnet => write!(f, "mainnet"),
             ChainId::Testnet => write!(f, "testnet"),
+            ChainId::Custom(name) => write!(f, "{}", name),
         }
     }
 }
@@ -1,3 +1,6 @@
+pub mod chain_profile;
 pub mod events;
 pub mod indexer_rule_match;
+pub mod receipts;
+pub mod standard_events;
 pub mod transactions;
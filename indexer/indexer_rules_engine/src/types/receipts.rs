@@ -0,0 +1,106 @@
+use crate::primitives::{
+    views::{ActionView, ExecutionStatusView, ReceiptEnumView},
+    IndexerExecutionOutcomeWithReceipt,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+/// Matched receipt as added to `IndexerRuleMatchPayload::Actions` by `PayloadEnrichment::Actions`
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone)]
+pub struct ReceiptDetails {
+    /// `None` for data receipts
+    pub signer_id: Option<String>,
+    pub predecessor_id: String,
+    pub receiver_id: String,
+    pub actions: Vec<ActionDetails>,
+    pub status: ExecutionStatusView,
+    pub logs: Vec<String>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone)]
+pub struct ActionDetails {
+    pub action_kind: String,
+    pub method_name: Option<String>,
+    /// Function call args when they are JSON
+    pub args_json: Option<String>,
+    pub args_base64: Option<String>,
+    /// Attached deposit of function calls and transfers, in yoctoNEAR
+    pub deposit: Option<String>,
+    pub gas: Option<u64>,
+}
+
+impl ReceiptDetails {
+    pub fn from_receipt_execution_outcome(
+        receipt_execution_outcome: &IndexerExecutionOutcomeWithReceipt,
+    ) -> Self {
+        let receipt = &receipt_execution_outcome.receipt;
+        let outcome = &receipt_execution_outcome.execution_outcome.outcome;
+        let (signer_id, actions) = match &receipt.receipt {
+            ReceiptEnumView::Action {
+                signer_id, actions, ..
+            } => (
+                Some(signer_id.to_string()),
+                actions
+                    .iter()
+                    .map(ActionDetails::from_action_view)
+                    .collect(),
+            ),
+            ReceiptEnumView::Data { .. } => (None, vec![]),
+        };
+
+        Self {
+            signer_id,
+            predecessor_id: receipt.predecessor_id.to_string(),
+            receiver_id: receipt.receiver_id.to_string(),
+            actions,
+            status: outcome.status.clone(),
+            logs: outcome.logs.clone(),
+        }
+    }
+}
+
+impl ActionDetails {
+    fn from_action_view(action: &ActionView) -> Self {
+        let action_kind = match action {
+            ActionView::CreateAccount => "CreateAccount",
+            ActionView::DeployContract { .. } => "DeployContract",
+            ActionView::FunctionCall { .. } => "FunctionCall",
+            ActionView::Transfer { .. } => "Transfer",
+            ActionView::Stake { .. } => "Stake",
+            ActionView::AddKey { .. } => "AddKey",
+            ActionView::DeleteKey { .. } => "DeleteKey",
+            ActionView::DeleteAccount { .. } => "DeleteAccount",
+            ActionView::Delegate { .. } => "Delegate",
+        };
+        let mut action_details = Self {
+            action_kind: action_kind.to_string(),
+            method_name: None,
+            args_json: None,
+            args_base64: None,
+            deposit: None,
+            gas: None,
+        };
+
+        match action {
+            ActionView::FunctionCall {
+                method_name,
+                args,
+                gas,
+                deposit,
+            } => {
+                action_details.method_name = Some(method_name.clone());
+                action_details.args_json = serde_json::from_slice::<serde_json::Value>(args)
+                    .ok()
+                    .map(|args| args.to_string());
+                action_details.args_base64 = Some(base64::encode(args));
+                action_details.deposit = Some(deposit.to_string());
+                action_details.gas = Some(*gas);
+            }
+            ActionView::Transfer { deposit } => {
+                action_details.deposit = Some(deposit.to_string());
+            }
+            _ => {}
+        }
+        action_details
+    }
+}
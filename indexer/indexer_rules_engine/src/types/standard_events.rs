@@ -0,0 +1,439 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::types::events::Event;
+
+/// Events of the fungible (NEP-141), non-fungible (NEP-171) and multi token (NEP-245) standards
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum StandardEvent {
+    FtMint(Vec<FtMintData>),
+    FtTransfer(Vec<FtTransferData>),
+    FtBurn(Vec<FtBurnData>),
+    NftMint(Vec<NftMintData>),
+    NftTransfer(Vec<NftTransferData>),
+    NftBurn(Vec<NftBurnData>),
+    MtMint(Vec<MtMintData>),
+    MtTransfer(Vec<MtTransferData>),
+    MtBurn(Vec<MtBurnData>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FtMintData {
+    pub owner_id: String,
+    pub amount: String,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FtTransferData {
+    pub old_owner_id: String,
+    pub new_owner_id: String,
+    pub amount: String,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FtBurnData {
+    pub owner_id: String,
+    pub amount: String,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct NftMintData {
+    pub owner_id: String,
+    pub token_ids: Vec<String>,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct NftTransferData {
+    pub authorized_id: Option<String>,
+    pub old_owner_id: String,
+    pub new_owner_id: String,
+    pub token_ids: Vec<String>,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct NftBurnData {
+    pub authorized_id: Option<String>,
+    pub owner_id: String,
+    pub token_ids: Vec<String>,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MtMintData {
+    pub owner_id: String,
+    pub token_ids: Vec<String>,
+    pub amounts: Vec<String>,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MtTransferData {
+    pub authorized_id: Option<String>,
+    pub old_owner_id: String,
+    pub new_owner_id: String,
+    pub token_ids: Vec<String>,
+    pub amounts: Vec<String>,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MtBurnData {
+    pub authorized_id: Option<String>,
+    pub owner_id: String,
+    pub token_ids: Vec<String>,
+    pub amounts: Vec<String>,
+    pub memo: Option<String>,
+}
+
+/// Movement of tokens, mints have no `old_owner_id` and burns have no `new_owner_id`
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Transfer {
+    pub standard: String,
+    pub contract_account_id: String,
+    /// `None` for fungible tokens
+    pub token_id: Option<String>,
+    pub amount: String,
+    pub old_owner_id: Option<String>,
+    pub new_owner_id: Option<String>,
+    pub memo: Option<String>,
+}
+
+impl StandardEvent {
+    /// `Ok(None)` for events which are not part of the supported standards
+    pub fn from_event(event: &Event) -> Result<Option<Self>, String> {
+        let standard_event = match (event.standard.as_str(), event.event.as_str()) {
+            ("nep141", "ft_mint") => Self::FtMint(decode_data(event)?),
+            ("nep141", "ft_transfer") => Self::FtTransfer(decode_data(event)?),
+            ("nep141", "ft_burn") => Self::FtBurn(decode_data(event)?),
+            ("nep171", "nft_mint") => Self::NftMint(decode_data(event)?),
+            ("nep171", "nft_transfer") => Self::NftTransfer(decode_data(event)?),
+            ("nep171", "nft_burn") => Self::NftBurn(decode_data(event)?),
+            ("nep245", "mt_mint") => Self::MtMint(decode_data(event)?),
+            ("nep245", "mt_transfer") => Self::MtTransfer(decode_data(event)?),
+            ("nep245", "mt_burn") => Self::MtBurn(decode_data(event)?),
+            _ => return Ok(None),
+        };
+        standard_event.validate()?;
+
+        Ok(Some(standard_event))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let amounts_and_token_ids: Vec<(&[String], &[String])> = match self {
+            Self::FtMint(data) => data
+                .iter()
+                .map(|data| (std::slice::from_ref(&data.amount), &[][..]))
+                .collect(),
+            Self::FtTransfer(data) => data
+                .iter()
+                .map(|data| (std::slice::from_ref(&data.amount), &[][..]))
+                .collect(),
+            Self::FtBurn(data) => data
+                .iter()
+                .map(|data| (std::slice::from_ref(&data.amount), &[][..]))
+                .collect(),
+            Self::NftMint(_) | Self::NftTransfer(_) | Self::NftBurn(_) => vec![],
+            Self::MtMint(data) => data
+                .iter()
+                .map(|data| (&data.amounts[..], &data.token_ids[..]))
+                .collect(),
+            Self::MtTransfer(data) => data
+                .iter()
+                .map(|data| (&data.amounts[..], &data.token_ids[..]))
+                .collect(),
+            Self::MtBurn(data) => data
+                .iter()
+                .map(|data| (&data.amounts[..], &data.token_ids[..]))
+                .collect(),
+        };
+
+        for (amounts, token_ids) in amounts_and_token_ids {
+            if !token_ids.is_empty() && amounts.len() != token_ids.len() {
+                return Err(format!(
+                    "{} amounts for {} token ids",
+                    amounts.len(),
+                    token_ids.len()
+                ));
+            }
+            if let Some(amount) = amounts
+                .iter()
+                .find(|amount| amount.parse::<u128>().is_err())
+            {
+                return Err(format!("Invalid amount {:?}", amount));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn transfers(&self, contract_account_id: &str) -> Vec<Transfer> {
+        let transfer = |standard: &str,
+                        token_id: Option<&String>,
+                        amount: &str,
+                        old_owner_id: Option<&String>,
+                        new_owner_id: Option<&String>,
+                        memo: &Option<String>| Transfer {
+            standard: standard.to_string(),
+            contract_account_id: contract_account_id.to_string(),
+            token_id: token_id.cloned(),
+            amount: amount.to_string(),
+            old_owner_id: old_owner_id.cloned(),
+            new_owner_id: new_owner_id.cloned(),
+            memo: memo.clone(),
+        };
+
+        match self {
+            Self::FtMint(data) => data
+                .iter()
+                .map(|data| {
+                    transfer(
+                        "nep141",
+                        None,
+                        &data.amount,
+                        None,
+                        Some(&data.owner_id),
+                        &data.memo,
+                    )
+                })
+                .collect(),
+            Self::FtTransfer(data) => data
+                .iter()
+                .map(|data| {
+                    transfer(
+                        "nep141",
+                        None,
+                        &data.amount,
+                        Some(&data.old_owner_id),
+                        Some(&data.new_owner_id),
+                        &data.memo,
+                    )
+                })
+                .collect(),
+            Self::FtBurn(data) => data
+                .iter()
+                .map(|data| {
+                    transfer(
+                        "nep141",
+                        None,
+                        &data.amount,
+                        Some(&data.owner_id),
+                        None,
+                        &data.memo,
+                    )
+                })
+                .collect(),
+            Self::NftMint(data) => data
+                .iter()
+                .flat_map(|data| {
+                    data.token_ids.iter().map(|token_id| {
+                        transfer(
+                            "nep171",
+                            Some(token_id),
+                            "1",
+                            None,
+                            Some(&data.owner_id),
+                            &data.memo,
+                        )
+                    })
+                })
+                .collect(),
+            Self::NftTransfer(data) => data
+                .iter()
+                .flat_map(|data| {
+                    data.token_ids.iter().map(|token_id| {
+                        transfer(
+                            "nep171",
+                            Some(token_id),
+                            "1",
+                            Some(&data.old_owner_id),
+                            Some(&data.new_owner_id),
+                            &data.memo,
+                        )
+                    })
+                })
+                .collect(),
+            Self::NftBurn(data) => data
+                .iter()
+                .flat_map(|data| {
+                    data.token_ids.iter().map(|token_id| {
+                        transfer(
+                            "nep171",
+                            Some(token_id),
+                            "1",
+                            Some(&data.owner_id),
+                            None,
+                            &data.memo,
+                        )
+                    })
+                })
+                .collect(),
+            Self::MtMint(data) => data
+                .iter()
+                .flat_map(|data| {
+                    data.token_ids
+                        .iter()
+                        .zip(&data.amounts)
+                        .map(|(token_id, amount)| {
+                            transfer(
+                                "nep245",
+                                Some(token_id),
+                                amount,
+                                None,
+                                Some(&data.owner_id),
+                                &data.memo,
+                            )
+                        })
+                })
+                .collect(),
+            Self::MtTransfer(data) => data
+                .iter()
+                .flat_map(|data| {
+                    data.token_ids
+                        .iter()
+                        .zip(&data.amounts)
+                        .map(|(token_id, amount)| {
+                            transfer(
+                                "nep245",
+                                Some(token_id),
+                                amount,
+                                Some(&data.old_owner_id),
+                                Some(&data.new_owner_id),
+                                &data.memo,
+                            )
+                        })
+                })
+                .collect(),
+            Self::MtBurn(data) => data
+                .iter()
+                .flat_map(|data| {
+                    data.token_ids
+                        .iter()
+                        .zip(&data.amounts)
+                        .map(|(token_id, amount)| {
+                            transfer(
+                                "nep245",
+                                Some(token_id),
+                                amount,
+                                Some(&data.owner_id),
+                                None,
+                                &data.memo,
+                            )
+                        })
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Transfers of a standard event and the reason it is malformed, used by the payload builders
+pub(crate) fn transfers_of_event(
+    event: &Event,
+    contract_account_id: &str,
+) -> (Vec<Transfer>, Option<String>) {
+    match StandardEvent::from_event(event) {
+        Ok(Some(standard_event)) => (standard_event.transfers(contract_account_id), None),
+        Ok(None) => (vec![], None),
+        Err(validation_error) => (vec![], Some(validation_error)),
+    }
+}
+
+fn decode_data<T: DeserializeOwned>(event: &Event) -> Result<Vec<T>, String> {
+    let data = event
+        .data
+        .as_ref()
+        .ok_or_else(|| format!("{} {} event has no data", event.standard, event.event))?;
+    serde_json::from_value(data.clone()).map_err(|err| {
+        format!(
+            "Malformed {} {} event data: {}",
+            event.standard, event.event, err
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{transfers_of_event, StandardEvent, Transfer};
+    use crate::types::events::Event;
+
+    fn event(log: &str) -> Event {
+        Event::from_log(log).unwrap()
+    }
+
+    #[test]
+    fn decode_nft_transfer_to_transfers() {
+        let nft_transfer = event(
+            r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_transfer","data":[{"old_owner_id":"alice.near","new_owner_id":"bob.near","token_ids":["1","2"]}]}"#,
+        );
+
+        let (transfers, validation_error) = transfers_of_event(&nft_transfer, "nft.near");
+
+        assert_eq!(validation_error, None);
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(
+            transfers[1],
+            Transfer {
+                standard: "nep171".to_string(),
+                contract_account_id: "nft.near".to_string(),
+                token_id: Some("2".to_string()),
+                amount: "1".to_string(),
+                old_owner_id: Some("alice.near".to_string()),
+                new_owner_id: Some("bob.near".to_string()),
+                memo: None,
+            }
+        );
+    }
+
+    #[test]
+    fn decode_ft_mint_and_mt_burn() {
+        let ft_mint = event(
+            r#"EVENT_JSON:{"standard":"nep141","version":"1.0.0","event":"ft_mint","data":[{"owner_id":"alice.near","amount":"100","memo":"airdrop"}]}"#,
+        );
+        let (transfers, _) = transfers_of_event(&ft_mint, "token.near");
+        assert_eq!(transfers[0].old_owner_id, None);
+        assert_eq!(transfers[0].new_owner_id.as_deref(), Some("alice.near"));
+        assert_eq!(transfers[0].memo.as_deref(), Some("airdrop"));
+
+        let mt_burn = event(
+            r#"EVENT_JSON:{"standard":"nep245","version":"1.0.0","event":"mt_burn","data":[{"owner_id":"alice.near","token_ids":["a","b"],"amounts":["1","5"]}]}"#,
+        );
+        let (transfers, _) = transfers_of_event(&mt_burn, "mt.near");
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[1].token_id.as_deref(), Some("b"));
+        assert_eq!(transfers[1].amount, "5");
+        assert_eq!(transfers[1].new_owner_id, None);
+    }
+
+    #[test]
+    fn report_malformed_standard_events() {
+        let missing_field = event(
+            r#"EVENT_JSON:{"standard":"nep141","version":"1.0.0","event":"ft_transfer","data":[{"old_owner_id":"alice.near","amount":"1"}]}"#,
+        );
+        let invalid_amount = event(
+            r#"EVENT_JSON:{"standard":"nep141","version":"1.0.0","event":"ft_burn","data":[{"owner_id":"alice.near","amount":"-1"}]}"#,
+        );
+        let mismatched_amounts = event(
+            r#"EVENT_JSON:{"standard":"nep245","version":"1.0.0","event":"mt_mint","data":[{"owner_id":"alice.near","token_ids":["a","b"],"amounts":["1"]}]}"#,
+        );
+        let other_standard = event(
+            r#"EVENT_JSON:{"standard":"nep999","version":"1.0.0","event":"ft_transfer","data":"anything"}"#,
+        );
+
+        assert!(StandardEvent::from_event(&missing_field)
+            .unwrap_err()
+            .contains("new_owner_id"));
+        assert_eq!(
+            StandardEvent::from_event(&invalid_amount).unwrap_err(),
+            "Invalid amount \"-1\""
+        );
+        assert_eq!(
+            transfers_of_event(&mismatched_amounts, "mt.near"),
+            (vec![], Some("1 amounts for 2 token ids".to_string()))
+        );
+        assert_eq!(StandardEvent::from_event(&other_standard), Ok(None));
+    }
+}
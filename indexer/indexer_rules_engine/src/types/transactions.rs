@@ -1,7 +1,9 @@
+use crate::primitives::{views, IndexerExecutionOutcomeWithReceipt, IndexerTransactionWithOutcome};
 use borsh::{BorshDeserialize, BorshSerialize};
-use near_lake_framework::near_indexer_primitives::{views, IndexerTransactionWithOutcome};
 use serde::{Deserialize, Serialize};
 
+/// A transaction with the receipts it caused, `execution_outcomes` starts with the outcome of the
+/// transaction itself, followed by the outcome of each receipt in order
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone)]
 pub struct TransactionDetails {
     pub transaction: views::SignedTransactionView,
@@ -17,4 +19,26 @@ impl TransactionDetails {
             execution_outcomes: vec![transaction.outcome.execution_outcome],
         }
     }
+
+    pub fn add_receipt(&mut self, receipt_execution_outcome: &IndexerExecutionOutcomeWithReceipt) {
+        self.receipts
+            .push(receipt_execution_outcome.receipt.clone());
+        self.execution_outcomes
+            .push(receipt_execution_outcome.execution_outcome.clone());
+    }
+
+    /// Receipts paired with their outcomes, as the matcher expects them
+    pub fn receipt_execution_outcomes(
+        &self,
+    ) -> impl Iterator<Item = IndexerExecutionOutcomeWithReceipt> + '_ {
+        self.receipts
+            .iter()
+            .zip(self.execution_outcomes.iter().skip(1))
+            .map(
+                |(receipt, execution_outcome)| IndexerExecutionOutcomeWithReceipt {
+                    execution_outcome: execution_outcome.clone(),
+                    receipt: receipt.clone(),
+                },
+            )
+    }
 }
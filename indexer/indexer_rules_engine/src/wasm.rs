@@ -0,0 +1,66 @@
+use wasm_bindgen::prelude::*;
+
+use crate::primitives::StreamerMessage;
+use crate::types::indexer_rule_match::{ChainId, ExtractionMode};
+use indexer_rule_type::indexer_rule::IndexerRule;
+
+fn parse_indexer_rule(indexer_rule_json: &str) -> Result<IndexerRule, JsError> {
+    let indexer_rule: IndexerRule = serde_json::from_str(indexer_rule_json)
+        .map_err(|e| JsError::new(&format!("Invalid rule JSON: {}", e)))?;
+    indexer_rule
+        .matching_rule
+        .validate()
+        .map_err(|e| JsError::new(&format!("Invalid rule: {}", e)))?;
+    Ok(indexer_rule)
+}
+
+fn parse_streamer_message(streamer_message_json: &str) -> Result<StreamerMessage, JsError> {
+    serde_json::from_str(streamer_message_json)
+        .map_err(|e| JsError::new(&format!("Invalid streamer message JSON: {}", e)))
+}
+
+fn parse_chain_id(chain_id: &str) -> ChainId {
+    match chain_id {
+        "mainnet" => ChainId::Mainnet,
+        "testnet" => ChainId::Testnet,
+        _ => ChainId::Custom(chain_id.to_string()),
+    }
+}
+
+/// Matches a rule, as stored in the registry contract, against a block in the snake_case JSON
+/// of near-lake, returning the `IndexerRuleMatch` list JSON the coordinator would send the runner
+#[wasm_bindgen(js_name = reduceIndexerRuleMatches)]
+pub fn reduce_indexer_rule_matches(
+    indexer_rule_json: &str,
+    streamer_message_json: &str,
+    chain_id: &str,
+) -> Result<String, JsError> {
+    let indexer_rule = parse_indexer_rule(indexer_rule_json)?;
+    let streamer_message = parse_streamer_message(streamer_message_json)?;
+
+    let indexer_rule_matches = crate::reduce_indexer_rule_matches_sync(
+        &indexer_rule,
+        &streamer_message,
+        parse_chain_id(chain_id),
+        ExtractionMode::All,
+    );
+    serde_json::to_string(&indexer_rule_matches).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// Same trace as [crate::explain] for the rule and block JSON of [reduce_indexer_rule_matches]
+#[wasm_bindgen(js_name = explain)]
+pub fn explain(
+    indexer_rule_json: &str,
+    streamer_message_json: &str,
+    chain_id: &str,
+) -> Result<String, JsError> {
+    let indexer_rule = parse_indexer_rule(indexer_rule_json)?;
+    let streamer_message = parse_streamer_message(streamer_message_json)?;
+
+    serde_json::to_string(&crate::explain(
+        &indexer_rule,
+        &streamer_message,
+        parse_chain_id(chain_id),
+    ))
+    .map_err(|e| JsError::new(&e.to_string()))
+}
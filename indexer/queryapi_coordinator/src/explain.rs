@@ -0,0 +1,67 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use indexer_rules_engine::types::chain_profile::ChainProfile;
+use near_lake_framework::near_indexer_primitives::types::{AccountId, BlockHeight};
+
+use crate::historical_block_processing::fetch_streamer_message;
+use crate::SharedIndexerRegistry;
+
+pub(crate) struct ExplainContext {
+    pub chain_profile: ChainProfile,
+    pub s3_client: aws_sdk_s3::Client,
+    pub indexer_registry: SharedIndexerRegistry,
+}
+
+/// Traces the registered rule of an indexer function against every receipt of a block,
+/// to answer why the function did or did not run for it
+#[get("/explain/{account_id}/{function_name}/{block_height}")]
+async fn explain_block(
+    path: web::Path<(String, String, BlockHeight)>,
+    context: web::Data<ExplainContext>,
+) -> impl Responder {
+    let (account_id, function_name, block_height) = path.into_inner();
+    let account_id: AccountId = match account_id.parse() {
+        Ok(account_id) => account_id,
+        Err(e) => return HttpResponse::BadRequest().body(format!("Invalid account id: {}", e)),
+    };
+
+    let indexer_rule = context
+        .indexer_registry
+        .lock()
+        .await
+        .get(&account_id)
+        .and_then(|functions| functions.get(&function_name))
+        .map(|indexer_function| indexer_function.indexer_rule.clone());
+    let indexer_rule = match indexer_rule {
+        Some(indexer_rule) => indexer_rule,
+        None => {
+            return HttpResponse::NotFound().body(format!(
+                "Indexer function {}/{} not found in registry",
+                account_id, function_name
+            ))
+        }
+    };
+
+    match fetch_streamer_message(
+        &context.s3_client,
+        &context.chain_profile.lake_bucket,
+        block_height,
+    )
+    .await
+    {
+        Ok(streamer_message) => HttpResponse::Ok().json(indexer_rules_engine::explain(
+            &indexer_rule,
+            &streamer_message,
+            context.chain_profile.chain_id.clone(),
+        )),
+        Err(e) => {
+            tracing::error!(
+                target: crate::INDEXER,
+                "Unable to fetch block {} to explain: {:?}",
+                block_height,
+                e
+            );
+            HttpResponse::InternalServerError()
+                .body(format!("Unable to fetch block {}: {}", block_height, e))
+        }
+    }
+}
@@ -1,21 +1,23 @@
 use crate::indexer_types::IndexerFunction;
 use crate::s3;
+use crate::SharedStorage;
 use anyhow::{bail, Context};
 use aws_sdk_s3::Client as S3Client;
 use chrono::{DateTime, LocalResult, TimeZone, Utc};
 use indexer_rule_type::indexer_rule::MatchingRule;
-use indexer_rules_engine::types::indexer_rule_match::ChainId;
+use indexer_rules_engine::types::chain_profile::{ChainProfile, IndexFiles};
+use indexer_rules_engine::types::indexer_rule_match::ExtractionMode;
 use near_jsonrpc_client::JsonRpcClient;
 use near_jsonrpc_primitives::types::blocks::RpcBlockRequest;
 use near_lake_framework::near_indexer_primitives::types::{BlockHeight, BlockId, BlockReference};
+use near_lake_framework::near_indexer_primitives::StreamerMessage;
 use serde_json::from_str;
 use tokio::task::JoinHandle;
 
-pub const INDEXED_DATA_FILES_BUCKET: &str = "near-delta-lake";
-pub const LAKE_BUCKET_PREFIX: &str = "near-lake-data-";
-pub const INDEXED_ACTIONS_FILES_FOLDER: &str = "silver/accounts/action_receipt_actions/metadata";
 pub const MAX_UNINDEXED_BLOCKS_TO_PROCESS: u64 = 7200; // two hours of blocks takes ~14 minutes.
 pub const MAX_RPC_BLOCKS_TO_PROCESS: u8 = 20;
+/// Stream entries added to the historical stream per round trip to the storage
+pub const HISTORICAL_STREAM_CHUNK_SIZE: usize = 1000;
 
 pub struct Task {
     handle: JoinHandle<()>,
@@ -36,9 +38,9 @@ impl Streamer {
         &mut self,
         current_block_height: BlockHeight,
         indexer: IndexerFunction,
-        redis_connection_manager: storage::ConnectionManager,
+        storage: SharedStorage,
         s3_client: S3Client,
-        chain_id: ChainId,
+        chain_profile: ChainProfile,
         json_rpc_client: JsonRpcClient,
     ) -> anyhow::Result<()> {
         if self.task.is_some() {
@@ -60,9 +62,9 @@ impl Streamer {
                 _ = process_historical_messages_or_handle_error(
                     current_block_height,
                     indexer.clone(),
-                    &redis_connection_manager,
+                    storage.as_ref(),
                     &s3_client,
-                    &chain_id,
+                    &chain_profile,
                     &json_rpc_client,
                 ) => { }
             }
@@ -93,17 +95,17 @@ impl Streamer {
 pub(crate) async fn process_historical_messages_or_handle_error(
     current_block_height: BlockHeight,
     indexer_function: IndexerFunction,
-    redis_connection_manager: &storage::ConnectionManager,
+    storage: &dyn storage::Storage,
     s3_client: &S3Client,
-    chain_id: &ChainId,
+    chain_profile: &ChainProfile,
     json_rpc_client: &JsonRpcClient,
 ) -> i64 {
     match process_historical_messages(
         current_block_height,
         indexer_function,
-        redis_connection_manager,
+        storage,
         s3_client,
-        chain_id,
+        chain_profile,
         json_rpc_client,
     )
     .await
@@ -123,9 +125,9 @@ pub(crate) async fn process_historical_messages_or_handle_error(
 pub(crate) async fn process_historical_messages(
     current_block_height: BlockHeight,
     indexer_function: IndexerFunction,
-    redis_connection_manager: &storage::ConnectionManager,
+    storage: &dyn storage::Storage,
     s3_client: &S3Client,
-    chain_id: &ChainId,
+    chain_profile: &ChainProfile,
     json_rpc_client: &JsonRpcClient,
 ) -> anyhow::Result<i64> {
     let start_block = indexer_function.start_block_height.unwrap();
@@ -149,78 +151,131 @@ pub(crate) async fn process_historical_messages(
                 indexer_function.function_name
             );
 
-            let start_date =
-                lookup_block_date_or_next_block_date(start_block, json_rpc_client).await?;
-
-            let last_indexed_block = last_indexed_block_from_metadata(s3_client).await?;
-
-            let mut blocks_from_index = filter_matching_blocks_from_index_files(
-                start_block,
-                &indexer_function,
-                s3_client,
-                start_date,
-            )
-            .await?;
+            match &indexer_function.indexer_rule.matching_rule {
+                // every block matches, there is nothing to look up in the index files or the lake
+                MatchingRule::AnyBlock => {
+                    stream_historical_blocks(
+                        storage,
+                        &indexer_function,
+                        start_block..current_block_height,
+                    )
+                    .await?
+                }
+                _ => {
+                    let blocks_from_index = filter_matching_blocks(
+                        start_block,
+                        current_block_height,
+                        &indexer_function,
+                        s3_client,
+                        chain_profile,
+                        json_rpc_client,
+                    )
+                    .await?;
+                    stream_historical_blocks(storage, &indexer_function, blocks_from_index).await?
+                }
+            }
+        }
+    }
+    Ok(block_difference)
+}
 
-            // Check for the case where an index file is written right after we get the last_indexed_block metadata
-            let last_block_in_data = blocks_from_index.last().unwrap_or(&start_block);
-            let last_indexed_block = if last_block_in_data > &last_indexed_block {
-                *last_block_in_data
-            } else {
-                last_indexed_block
-            };
+/// Replaces the historical stream of the indexer with `blocks`, committed in chunks of
+/// [HISTORICAL_STREAM_CHUNK_SIZE] entries
+async fn stream_historical_blocks(
+    storage: &dyn storage::Storage,
+    indexer_function: &IndexerFunction,
+    blocks: impl IntoIterator<Item = BlockHeight>,
+) -> anyhow::Result<()> {
+    let mut blocks = blocks.into_iter().peekable();
+    if blocks.peek().is_none() {
+        return Ok(());
+    }
 
-            let mut blocks_between_indexed_and_current_block: Vec<BlockHeight> =
-                filter_matching_unindexed_blocks_from_lake(
-                    last_indexed_block,
-                    current_block_height,
-                    &indexer_function,
-                    s3_client,
-                    chain_id,
-                )
-                .await?;
-
-            blocks_from_index.append(&mut blocks_between_indexed_and_current_block);
-
-            if !blocks_from_index.is_empty() {
-                storage::del(
-                    redis_connection_manager,
-                    storage::generate_historical_stream_key(&indexer_function.get_full_name()),
-                )
-                .await?;
-                storage::sadd(
-                    redis_connection_manager,
-                    storage::STREAMS_SET_KEY,
-                    storage::generate_historical_stream_key(&indexer_function.get_full_name()),
-                )
-                .await?;
-                storage::set(
-                    redis_connection_manager,
-                    storage::generate_historical_storage_key(&indexer_function.get_full_name()),
-                    serde_json::to_string(&indexer_function)?,
-                    None,
-                )
-                .await?;
-            }
+    let historical_stream_key =
+        storage::generate_historical_stream_key(&indexer_function.get_full_name());
+    let mut batch = storage::WriteBatch::new();
+    batch
+        .del(&historical_stream_key)
+        .create_consumer_group(&historical_stream_key)
+        .sadd(storage::STREAMS_SET_KEY, &historical_stream_key)
+        .set(
+            &storage::generate_historical_storage_key(&indexer_function.get_full_name()),
+            &serde_json::to_string(indexer_function)?,
+            None,
+        );
 
-            for current_block in blocks_from_index {
-                storage::xadd(
-                    redis_connection_manager,
-                    storage::generate_historical_stream_key(&indexer_function.get_full_name()),
-                    &[("block_height", current_block)],
-                )
-                .await?;
-            }
+    loop {
+        for block_height in blocks.by_ref().take(HISTORICAL_STREAM_CHUNK_SIZE) {
+            batch.xadd(
+                &historical_stream_key,
+                block_height,
+                &[("block_height", block_height.to_string())],
+            );
         }
+        if batch.is_empty() {
+            return Ok(());
+        }
+        storage.commit(std::mem::take(&mut batch)).await?;
     }
-    Ok(block_difference)
+}
+
+async fn filter_matching_blocks(
+    start_block: BlockHeight,
+    current_block_height: BlockHeight,
+    indexer_function: &IndexerFunction,
+    s3_client: &S3Client,
+    chain_profile: &ChainProfile,
+    json_rpc_client: &JsonRpcClient,
+) -> anyhow::Result<Vec<BlockHeight>> {
+    let index_files = chain_profile.index_files.as_ref().with_context(|| {
+        format!(
+            "Chain {} has no index files to back fill from, function {:?} {:?}",
+            chain_profile.chain_id, indexer_function.account_id, indexer_function.function_name
+        )
+    })?;
+
+    let start_date = lookup_block_date_or_next_block_date(start_block, json_rpc_client).await?;
+
+    let last_indexed_block = last_indexed_block_from_metadata(s3_client, index_files).await?;
+
+    let mut blocks_from_index = filter_matching_blocks_from_index_files(
+        start_block,
+        indexer_function,
+        s3_client,
+        index_files,
+        start_date,
+    )
+    .await?;
+
+    // Check for the case where an index file is written right after we get the last_indexed_block metadata
+    let last_block_in_data = blocks_from_index.last().unwrap_or(&start_block);
+    let last_indexed_block = if last_block_in_data > &last_indexed_block {
+        *last_block_in_data
+    } else {
+        last_indexed_block
+    };
+
+    let mut blocks_between_indexed_and_current_block: Vec<BlockHeight> =
+        filter_matching_unindexed_blocks_from_lake(
+            last_indexed_block,
+            current_block_height,
+            indexer_function,
+            s3_client,
+            chain_profile,
+        )
+        .await?;
+
+    blocks_from_index.append(&mut blocks_between_indexed_and_current_block);
+
+    Ok(blocks_from_index)
 }
 
 pub(crate) async fn last_indexed_block_from_metadata(
     s3_client: &S3Client,
+    index_files: &IndexFiles,
 ) -> anyhow::Result<BlockHeight> {
-    let key = format!("{}/{}", INDEXED_ACTIONS_FILES_FOLDER, "latest_block.json");
-    let metadata = s3::fetch_text_file_from_s3(INDEXED_DATA_FILES_BUCKET, key, s3_client).await?;
+    let key = format!("{}/{}", index_files.actions_folder, "latest_block.json");
+    let metadata = s3::fetch_text_file_from_s3(&index_files.bucket, key, s3_client).await?;
 
     let metadata: serde_json::Value = serde_json::from_str(&metadata).unwrap();
     let last_indexed_block = metadata["last_indexed_block"].clone();
@@ -241,9 +296,10 @@ pub(crate) async fn filter_matching_blocks_from_index_files(
     start_block_height: BlockHeight,
     indexer_function: &IndexerFunction,
     s3_client: &S3Client,
+    index_files: &IndexFiles,
     start_date: DateTime<Utc>,
 ) -> anyhow::Result<Vec<BlockHeight>> {
-    let s3_bucket = INDEXED_DATA_FILES_BUCKET;
+    let s3_bucket = &index_files.bucket;
 
     let mut needs_dedupe_and_sort = false;
     let indexer_rule = &indexer_function.indexer_rule;
@@ -259,18 +315,53 @@ pub(crate) async fn filter_matching_blocks_from_index_files(
             s3::fetch_contract_index_files(
                 s3_client,
                 s3_bucket,
-                INDEXED_ACTIONS_FILES_FOLDER,
+                &index_files.actions_folder,
                 start_date,
                 affected_account_id,
             )
             .await
         }
-        MatchingRule::ActionFunctionCall { .. } => {
+        MatchingRule::ActionFunctionCall { .. } | MatchingRule::ActionFunctionCallArgs { .. } => {
             bail!("ActionFunctionCall matching rule not yet supported for historical processing, function: {:?} {:?}", indexer_function.account_id, indexer_function.function_name);
         }
-        MatchingRule::Event { .. } => {
+        MatchingRule::ActionDeployContract { .. }
+        | MatchingRule::ActionAddKey { .. }
+        | MatchingRule::ActionDeleteKey { .. }
+        | MatchingRule::ActionStake { .. }
+        | MatchingRule::ActionCreateAccount { .. }
+        | MatchingRule::ActionDeleteAccount { .. }
+        | MatchingRule::ActionTransfer { .. } => {
+            bail!("Action kind matching rules not yet supported for historical processing, function: {:?} {:?}", indexer_function.account_id, indexer_function.function_name);
+        }
+        MatchingRule::Event { .. } | MatchingRule::EventData { .. } => {
             bail!("Event matching rule not yet supported for historical processing, function {:?} {:?}", indexer_function.account_id, indexer_function.function_name);
         }
+        MatchingRule::All { .. } | MatchingRule::Any { .. } | MatchingRule::Not { .. } => {
+            match index_files_account_pattern(&indexer_rule.matching_rule) {
+                Some(affected_account_id) => {
+                    needs_dedupe_and_sort = true;
+                    s3::fetch_contract_index_files(
+                        s3_client,
+                        s3_bucket,
+                        &index_files.actions_folder,
+                        start_date,
+                        &affected_account_id,
+                    )
+                    .await
+                }
+                None => {
+                    bail!("Composed matching rule can only be back filled when built from ActionAny rules, function {:?} {:?}", indexer_function.account_id, indexer_function.function_name);
+                }
+            }
+        }
+        MatchingRule::AnyBlock => {
+            bail!("AnyBlock matching rule matches every block and has no index files, function {:?} {:?}", indexer_function.account_id, indexer_function.function_name);
+        }
+        MatchingRule::StateChangeAccountBalance { .. }
+        | MatchingRule::StateChangeContractData { .. }
+        | MatchingRule::StateChangeAccessKey { .. } => {
+            bail!("State change matching rules not yet supported for historical processing, function {:?} {:?}", indexer_function.account_id, indexer_function.function_name);
+        }
     }?;
 
     tracing::info!(
@@ -297,6 +388,27 @@ pub(crate) async fn filter_matching_blocks_from_index_files(
     Ok(blocks_to_process)
 }
 
+/// Builds the account pattern to look up in the index files for a matching rule.
+/// `All` uses the first nested rule with an account pattern, so the blocks found are a superset
+/// of the matching ones, in the same way `Status` is not taken into account for `ActionAny`.
+fn index_files_account_pattern(matching_rule: &MatchingRule) -> Option<String> {
+    match matching_rule {
+        MatchingRule::ActionAny {
+            affected_account_id,
+            ..
+        } => Some(affected_account_id.clone()),
+        MatchingRule::All { matching_rules } => {
+            matching_rules.iter().find_map(index_files_account_pattern)
+        }
+        MatchingRule::Any { matching_rules } => matching_rules
+            .iter()
+            .map(index_files_account_pattern)
+            .collect::<Option<Vec<String>>>()
+            .map(|patterns| patterns.join(", ")),
+        _ => None,
+    }
+}
+
 fn parse_blocks_from_index_files(
     index_files_content: Vec<String>,
     start_block_height: u64,
@@ -338,10 +450,8 @@ async fn filter_matching_unindexed_blocks_from_lake(
     ending_block_height: BlockHeight,
     indexer_function: &IndexerFunction,
     s3_client: &S3Client,
-    chain_id: &ChainId,
+    chain_profile: &ChainProfile,
 ) -> anyhow::Result<Vec<u64>> {
-    let lake_bucket = lake_bucket_for_chain(chain_id);
-
     let indexer_rule = &indexer_function.indexer_rule;
     let count = ending_block_height - last_indexed_block;
     if count > MAX_UNINDEXED_BLOCKS_TO_PROCESS {
@@ -360,16 +470,19 @@ async fn filter_matching_unindexed_blocks_from_lake(
 
     let mut blocks_to_process: Vec<u64> = vec![];
     for current_block in (last_indexed_block + 1)..ending_block_height {
-        // fetch block file from S3
-        let key = format!("{}/block.json", normalize_block_height(current_block));
-        let s3_result = s3::fetch_text_file_from_s3(&lake_bucket, key, s3_client).await;
-
-        if s3_result.is_err() {
-            let error = s3_result.err().unwrap();
-            if error
-                .root_cause()
-                .downcast_ref::<aws_sdk_s3::error::NoSuchKey>()
-                .is_some()
+        let streamer_message = match fetch_streamer_message(
+            s3_client,
+            &chain_profile.lake_bucket,
+            current_block,
+        )
+        .await
+        {
+            Ok(streamer_message) => streamer_message,
+            Err(error)
+                if error
+                    .root_cause()
+                    .downcast_ref::<aws_sdk_s3::error::NoSuchKey>()
+                    .is_some() =>
             {
                 tracing::info!(
                     target: crate::INDEXER,
@@ -379,47 +492,16 @@ async fn filter_matching_unindexed_blocks_from_lake(
                     indexer_function.function_name,
                 );
                 continue;
-            } else {
-                bail!(error);
             }
-        }
-
-        let block = s3_result.unwrap();
-        let block_view = serde_json::from_slice::<
-            near_lake_framework::near_indexer_primitives::views::BlockView,
-        >(block.as_ref())
-        .with_context(|| format!("Error parsing block {} from S3", current_block))?;
-
-        let mut shards = vec![];
-        for shard_id in 0..block_view.chunks.len() as u64 {
-            let key = format!(
-                "{}/shard_{}.json",
-                normalize_block_height(current_block),
-                shard_id
-            );
-            let shard = s3::fetch_text_file_from_s3(&lake_bucket, key, s3_client).await?;
-            match serde_json::from_slice::<near_lake_framework::near_indexer_primitives::IndexerShard>(
-                shard.as_ref(),
-            ) {
-                Ok(parsed_shard) => {
-                    shards.push(parsed_shard);
-                }
-                Err(e) => {
-                    bail!("Error parsing shard: {}", e.to_string());
-                }
-            }
-        }
-
-        let streamer_message = near_lake_framework::near_indexer_primitives::StreamerMessage {
-            block: block_view,
-            shards,
+            Err(error) => bail!(error),
         };
 
         // filter block
         let matches = indexer_rules_engine::reduce_indexer_rule_matches_sync(
             indexer_rule,
             &streamer_message,
-            chain_id.clone(),
+            chain_profile.chain_id.clone(),
+            ExtractionMode::FirstPerShard,
         );
         if !matches.is_empty() {
             blocks_to_process.push(current_block);
@@ -436,8 +518,43 @@ async fn filter_matching_unindexed_blocks_from_lake(
     Ok(blocks_to_process)
 }
 
-fn lake_bucket_for_chain(chain_id: &ChainId) -> String {
-    format!("{}{}", LAKE_BUCKET_PREFIX, chain_id)
+/// Reads the block and its shards from the lake bucket
+pub(crate) async fn fetch_streamer_message(
+    s3_client: &S3Client,
+    lake_bucket: &str,
+    block_height: BlockHeight,
+) -> anyhow::Result<StreamerMessage> {
+    let key = format!("{}/block.json", normalize_block_height(block_height));
+    let block = s3::fetch_text_file_from_s3(lake_bucket, key, s3_client).await?;
+    let block_view = serde_json::from_slice::<
+        near_lake_framework::near_indexer_primitives::views::BlockView,
+    >(block.as_ref())
+    .with_context(|| format!("Error parsing block {} from S3", block_height))?;
+
+    let mut shards = vec![];
+    for shard_id in 0..block_view.chunks.len() as u64 {
+        let key = format!(
+            "{}/shard_{}.json",
+            normalize_block_height(block_height),
+            shard_id
+        );
+        let shard = s3::fetch_text_file_from_s3(lake_bucket, key, s3_client).await?;
+        match serde_json::from_slice::<near_lake_framework::near_indexer_primitives::IndexerShard>(
+            shard.as_ref(),
+        ) {
+            Ok(parsed_shard) => {
+                shards.push(parsed_shard);
+            }
+            Err(e) => {
+                bail!("Error parsing shard: {}", e.to_string());
+            }
+        }
+    }
+
+    Ok(StreamerMessage {
+        block: block_view,
+        shards,
+    })
 }
 
 fn normalize_block_height(block_height: BlockHeight) -> String {
@@ -477,3 +594,103 @@ pub async fn lookup_block_date_or_next_block_date(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::process_historical_messages;
+    use crate::indexer_types::IndexerFunction;
+    use indexer_rule_type::indexer_rule::{
+        IndexerRule, IndexerRuleKind, MatchingRule, PayloadEnrichment, Status,
+    };
+    use indexer_rules_engine::types::chain_profile::ChainProfile;
+    use storage::InMemoryStorage;
+
+    #[tokio::test]
+    async fn stream_every_block_for_any_block_rule() {
+        let indexer_function = IndexerFunction {
+            account_id: "buildnear.testnet".to_string().parse().unwrap(),
+            function_name: "every_block".to_string(),
+            code: "".to_string(),
+            start_block_height: Some(100),
+            schema: None,
+            provisioned: false,
+            indexer_rule: IndexerRule {
+                indexer_rule_kind: IndexerRuleKind::AnyBlock,
+                matching_rule: MatchingRule::AnyBlock,
+                id: None,
+                name: None,
+                payload_enrichment: PayloadEnrichment::None,
+            },
+        };
+        let storage = InMemoryStorage::new();
+        // neither client is called for rules matching every block
+        let s3_client = aws_sdk_s3::Client::from_conf(aws_sdk_s3::Config::builder().build());
+        let json_rpc_client = near_jsonrpc_client::JsonRpcClient::connect("http://localhost");
+
+        let block_difference = process_historical_messages(
+            103,
+            indexer_function.clone(),
+            &storage,
+            &s3_client,
+            &ChainProfile::testnet(),
+            &json_rpc_client,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(block_difference, 3);
+        let stream_key = storage::generate_historical_stream_key(&indexer_function.get_full_name());
+        assert_eq!(
+            storage.set_members(storage::STREAMS_SET_KEY),
+            vec![stream_key.clone()]
+        );
+        let streamed_blocks: Vec<Vec<(String, String)>> = storage
+            .stream_entries(&stream_key)
+            .into_iter()
+            .map(|(_, fields)| fields)
+            .collect();
+        assert_eq!(
+            streamed_blocks,
+            ["100", "101", "102"]
+                .map(|block_height| vec![("block_height".to_string(), block_height.to_string())])
+        );
+    }
+
+    #[tokio::test]
+    async fn refuse_index_back_fill_on_chains_without_index_files() {
+        let indexer_function = IndexerFunction {
+            account_id: "buildnear.testnet".to_string().parse().unwrap(),
+            function_name: "social".to_string(),
+            code: "".to_string(),
+            start_block_height: Some(100),
+            schema: None,
+            provisioned: false,
+            indexer_rule: IndexerRule {
+                indexer_rule_kind: IndexerRuleKind::Action,
+                matching_rule: MatchingRule::ActionAny {
+                    affected_account_id: "social.near".to_string(),
+                    status: Status::Any,
+                },
+                id: None,
+                name: None,
+                payload_enrichment: PayloadEnrichment::None,
+            },
+        };
+        let storage = InMemoryStorage::new();
+        let s3_client = aws_sdk_s3::Client::from_conf(aws_sdk_s3::Config::builder().build());
+        let json_rpc_client = near_jsonrpc_client::JsonRpcClient::connect("http://localhost");
+
+        let result = process_historical_messages(
+            103,
+            indexer_function,
+            &storage,
+            &s3_client,
+            &ChainProfile::testnet(),
+            &json_rpc_client,
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert!(storage.set_members(storage::STREAMS_SET_KEY).is_empty());
+    }
+}
@@ -2,14 +2,18 @@
 mod tests {
     use crate::historical_block_processing::filter_matching_blocks_from_index_files;
     use crate::indexer_types::IndexerFunction;
-    use crate::opts::{ChainId, Opts, StartOptions};
+    use crate::opts::{ChainId, Command, Opts, RedisMode, RetentionOpts, StartOptions};
     use crate::{historical_block_processing, opts};
     use aws_types::SdkConfig;
     use chrono::{DateTime, NaiveDate, Utc};
-    use indexer_rule_type::indexer_rule::{IndexerRule, IndexerRuleKind, MatchingRule, Status};
+    use indexer_rule_type::indexer_rule::{
+        IndexerRule, IndexerRuleKind, MatchingRule, PayloadEnrichment, Status,
+    };
+    use indexer_rules_engine::types::chain_profile::ChainProfile;
     use near_lake_framework::near_indexer_primitives::types::BlockHeight;
     use std::env;
     use std::ops::Range;
+    use storage::StreamerMessageEncoding;
 
     impl Opts {
         pub fn test_opts_with_aws() -> Self {
@@ -18,11 +22,16 @@ mod tests {
             let lake_aws_secret_access_key = env::var("LAKE_AWS_SECRET_ACCESS_KEY").unwrap();
             Opts {
                 redis_connection_string: env::var("REDIS_CONNECTION_STRING").unwrap(),
+                redis_mode: RedisMode::Single,
+                redis_sentinel_master_name: None,
+                streamer_message_encoding: StreamerMessageEncoding::Json,
+                redis_key_prefix: None,
                 lake_aws_access_key,
                 lake_aws_secret_access_key,
                 registry_contract_id: "".to_string(),
                 port: 0,
-                chain_id: ChainId::Mainnet(StartOptions::FromLatest),
+                retention: RetentionOpts::default(),
+                command: Command::Index(ChainId::Mainnet(StartOptions::FromLatest)),
             }
         }
     }
@@ -36,10 +45,12 @@ mod tests {
         let s3_config = aws_sdk_s3::config::Builder::from(aws_config).build();
         let s3_client = aws_sdk_s3::Client::from_conf(s3_config);
 
-        let last_indexed_block =
-            historical_block_processing::last_indexed_block_from_metadata(&s3_client)
-                .await
-                .unwrap();
+        let last_indexed_block = historical_block_processing::last_indexed_block_from_metadata(
+            &s3_client,
+            ChainProfile::mainnet().index_files.as_ref().unwrap(),
+        )
+        .await
+        .unwrap();
         let a: Range<u64> = 90000000..9000000000; // valid for the next 300 years
         assert!(a.contains(&last_indexed_block));
     }
@@ -60,6 +71,7 @@ mod tests {
             matching_rule,
             id: None,
             name: None,
+            payload_enrichment: PayloadEnrichment::None,
         };
         let indexer_function = IndexerFunction {
             account_id: "buildnear.testnet".to_string().parse().unwrap(),
@@ -77,22 +89,22 @@ mod tests {
         let s3_config = aws_sdk_s3::config::Builder::from(aws_config).build();
         let s3_client = aws_sdk_s3::Client::from_conf(s3_config);
 
-        let redis_connection_manager = storage::connect(&opts.redis_connection_string)
-            .await
-            .unwrap();
+        let storage = opts.connect_storage().await.unwrap();
 
-        let json_rpc_client = near_jsonrpc_client::JsonRpcClient::connect(opts.rpc_url());
+        let json_rpc_client = near_jsonrpc_client::JsonRpcClient::connect(opts.archival_rpc_url());
 
-        let fake_block_height =
-            historical_block_processing::last_indexed_block_from_metadata(&s3_client)
-                .await
-                .unwrap();
+        let fake_block_height = historical_block_processing::last_indexed_block_from_metadata(
+            &s3_client,
+            ChainProfile::mainnet().index_files.as_ref().unwrap(),
+        )
+        .await
+        .unwrap();
         let result = historical_block_processing::process_historical_messages(
             fake_block_height + 1,
             indexer_function,
-            &redis_connection_manager,
+            &storage,
             &s3_client,
-            &opts.chain_id(),
+            &opts.chain_profile(),
             &json_rpc_client,
         )
         .await;
@@ -113,6 +125,7 @@ mod tests {
             matching_rule,
             id: None,
             name: None,
+            payload_enrichment: PayloadEnrichment::None,
         };
         let indexer_function = IndexerFunction {
             account_id: "buildnear.testnet".to_string().parse().unwrap(),
@@ -139,6 +152,7 @@ mod tests {
             start_block_height,
             &indexer_function,
             &s3_client,
+            ChainProfile::mainnet().index_files.as_ref().unwrap(),
             datetime_utc,
         )
         .await;
@@ -172,6 +186,7 @@ mod tests {
             matching_rule,
             id: None,
             name: None,
+            payload_enrichment: PayloadEnrichment::None,
         };
         let indexer_function = IndexerFunction {
             account_id: "buildnear.testnet".to_string().parse().unwrap(),
@@ -198,6 +213,7 @@ mod tests {
             start_block_height,
             &indexer_function,
             &s3_client,
+            ChainProfile::mainnet().index_files.as_ref().unwrap(),
             datetime_utc,
         )
         .await;
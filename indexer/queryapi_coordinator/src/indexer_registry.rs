@@ -1,4 +1,6 @@
 use crate::QueryApiContext;
+use indexer_rules_engine::rule_set::RuleSet;
+use indexer_rules_engine::transaction_tracker::TransactionFilter;
 use near_jsonrpc_client::JsonRpcClient;
 use near_jsonrpc_primitives::types::query::{QueryResponseKind, RpcQueryRequest};
 use near_lake_framework::near_indexer_primitives::types::BlockReference::Finality;
@@ -8,12 +10,65 @@ use near_lake_framework::near_indexer_primitives::views::QueryRequest;
 use serde_json::{json, Value};
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::sync::Arc;
 use unescape::unescape;
 
 use crate::indexer_reducer;
 use crate::indexer_reducer::FunctionCallInfo;
 use crate::indexer_types::{IndexerFunction, IndexerRegistry};
-use indexer_rule_type::indexer_rule::{IndexerRule, IndexerRuleKind, MatchingRule, Status};
+use indexer_rule_type::indexer_rule::{
+    IndexerRule, IndexerRuleKind, MatchingRule, PayloadEnrichment, Status,
+};
+
+/// Rules of the registered indexer functions, compiled once and rebuilt after the registry changes
+pub(crate) struct RegistryRules {
+    pub receipt_rules: RuleSet<String>,
+    pub transaction_rules: Vec<(String, IndexerRule)>,
+    pub transaction_filter: TransactionFilter,
+}
+
+impl RegistryRules {
+    pub(crate) fn from_registry(indexer_registry: &IndexerRegistry) -> Self {
+        let (transaction_rules, receipt_rules): (Vec<_>, Vec<_>) = indexer_registry
+            .values()
+            .flat_map(|fns| fns.values())
+            .map(|indexer_function| {
+                (
+                    indexer_function.get_full_name(),
+                    indexer_function.indexer_rule.clone(),
+                )
+            })
+            .partition(|(_, indexer_rule)| {
+                indexer_rule.indexer_rule_kind == IndexerRuleKind::Transaction
+            });
+
+        Self {
+            // all receipt rules are evaluated together in a single pass over the block
+            receipt_rules: RuleSet::new(receipt_rules),
+            transaction_filter: TransactionFilter::new(
+                transaction_rules
+                    .iter()
+                    .map(|(_, indexer_rule)| indexer_rule),
+            ),
+            transaction_rules,
+        }
+    }
+}
+
+/// Rules of the current registry, compiled if the registry changed since they last were
+pub(crate) async fn registry_rules(context: &QueryApiContext<'_>) -> Arc<RegistryRules> {
+    let mut registry_rules = context.registry_rules.lock().await;
+    match registry_rules.as_ref() {
+        Some(registry_rules) => registry_rules.clone(),
+        None => {
+            let rules = Arc::new(RegistryRules::from_registry(
+                &*context.indexer_registry.lock().await,
+            ));
+            *registry_rules = Some(rules.clone());
+            rules
+        }
+    }
+}
 
 struct RegistryFunctionInvocation {
     pub account_id: AccountId,
@@ -90,22 +145,30 @@ pub(crate) async fn index_registry_changes(
     current_block_height: BlockHeight,
     context: &QueryApiContext<'_>,
 ) -> anyhow::Result<()> {
-    index_and_process_remove_calls(context).await;
+    let removed_functions = index_and_process_remove_calls(context).await;
+    let registered_functions =
+        index_and_process_register_calls(current_block_height, context).await;
 
-    index_and_process_register_calls(current_block_height, context).await
+    // a failed registration may have changed the registry before failing
+    if removed_functions || !matches!(registered_functions, Ok(false)) {
+        *context.registry_rules.lock().await = None;
+    }
+    registered_functions.map(|_| ())
 }
 
+/// Returns whether any function was registered or updated
 async fn index_and_process_register_calls(
     current_block_height: BlockHeight,
     context: &QueryApiContext<'_>,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<bool> {
     let registry_method_name = "register_indexer_function";
+    let mut registered_functions = false;
     let registry_calls_rule =
         build_registry_indexer_rule(registry_method_name, context.registry_contract_id);
     let registry_updates = indexer_reducer::reduce_function_registry_from_outcomes(
         &registry_calls_rule,
         &context.streamer_message,
-        context.chain_id,
+        &context.chain_profile.chain_id,
         context.streamer_message.block.header.height,
     );
 
@@ -167,9 +230,9 @@ async fn index_and_process_register_calls(
                         streamer.start(
                             current_block_height,
                             new_indexer_function.clone(),
-                            context.redis_connection_manager.clone(),
+                            context.storage.clone(),
                             context.s3_client.clone(),
-                            context.chain_id.clone(),
+                            context.chain_profile.clone(),
                             context.json_rpc_client.clone(),
                         )?;
 
@@ -177,22 +240,25 @@ async fn index_and_process_register_calls(
                     }
 
                     fns.insert(update.method_name.clone(), new_indexer_function);
+                    registered_functions = true;
                 }
             };
         }
     }
 
-    Ok(())
+    Ok(registered_functions)
 }
 
-async fn index_and_process_remove_calls(context: &QueryApiContext<'_>) {
+/// Returns whether any function was removed
+async fn index_and_process_remove_calls(context: &QueryApiContext<'_>) -> bool {
     let registry_method_name = "remove_indexer_function";
+    let mut removed_functions = false;
     let registry_calls_rule =
         build_registry_indexer_rule(registry_method_name, context.registry_contract_id);
     let registry_updates = indexer_reducer::reduce_function_registry_from_outcomes(
         &registry_calls_rule,
         &context.streamer_message,
-        context.chain_id,
+        &context.chain_profile.chain_id,
         context.streamer_message.block.header.height,
     );
 
@@ -220,8 +286,10 @@ async fn index_and_process_remove_calls(context: &QueryApiContext<'_>) {
                     {
                         Entry::Vacant(_) => {}
                         Entry::Occupied(mut fns) => {
-                            fns.get_mut()
-                                .remove(function_invocation.function_name.as_str());
+                            removed_functions |= fns
+                                .get_mut()
+                                .remove(function_invocation.function_name.as_str())
+                                .is_some();
                         }
                     }
                     // todo request removal of DB schema
@@ -229,6 +297,7 @@ async fn index_and_process_remove_calls(context: &QueryApiContext<'_>) {
             }
         }
     }
+    removed_functions
 }
 
 fn build_function_invocation_from_args(
@@ -331,6 +400,7 @@ fn build_registry_indexer_rule(
         name: Some(format!("{}{}", registry_method_name, "_changes")),
         indexer_rule_kind: IndexerRuleKind::Action,
         matching_rule,
+        payload_enrichment: PayloadEnrichment::None,
     }
 }
 
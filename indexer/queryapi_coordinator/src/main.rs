@@ -1,42 +1,63 @@
 use std::collections::HashMap;
 
-use futures::stream::{self, StreamExt};
+use futures::stream::StreamExt;
 use near_jsonrpc_client::JsonRpcClient;
 use tokio::sync::Mutex;
 
-use indexer_rules_engine::types::indexer_rule_match::{ChainId, IndexerRuleMatch};
+use indexer_rules_engine::transaction_resolver::{self, ReceiptOrigin, ReceiptTransactionHashes};
+use indexer_rules_engine::transaction_tracker::{self, PendingTransaction};
+use indexer_rules_engine::types::chain_profile::ChainProfile;
+use indexer_rules_engine::types::indexer_rule_match::ExtractionMode;
+use indexer_rules_engine::types::indexer_rule_match::IndexerRuleMatch;
 use near_lake_framework::near_indexer_primitives::types::BlockHeight;
 use near_lake_framework::near_indexer_primitives::StreamerMessage;
-use utils::serialize_to_camel_case_json_string;
 
 use crate::indexer_types::IndexerFunction;
 use indexer_types::IndexerRegistry;
-use opts::{Opts, Parser};
-use storage::{self, generate_real_time_streamer_message_key, ConnectionManager};
+use opts::{Command, Opts, Parser};
+use storage::{
+    self, generate_real_time_streamer_message_key, RetentionPolicy, Storage,
+    StreamerMessageEncoding, WriteBatch,
+};
 
+mod explain;
 mod historical_block_processing;
 mod indexer_reducer;
 mod indexer_registry;
 mod indexer_types;
 mod metrics;
 mod opts;
+mod retention;
+mod runner_progress;
 mod s3;
 mod utils;
 
 pub(crate) const INDEXER: &str = "queryapi_coordinator";
 
+/// Receipts followed back through the watching list to find the transaction of a receipt
+const MAX_RECEIPT_ORIGIN_DEPTH: usize = 32;
+
 type SharedIndexerRegistry = std::sync::Arc<Mutex<IndexerRegistry>>;
 
+/// Compiled rules of the registry, `None` until they are compiled after a registry change
+type SharedRegistryRules =
+    std::sync::Arc<Mutex<Option<std::sync::Arc<indexer_registry::RegistryRules>>>>;
+
+type SharedStorage = std::sync::Arc<dyn Storage>;
+
 type Streamers = std::sync::Arc<Mutex<HashMap<String, historical_block_processing::Streamer>>>;
 
 pub(crate) struct QueryApiContext<'a> {
     pub streamer_message: near_lake_framework::near_indexer_primitives::StreamerMessage,
-    pub chain_id: &'a ChainId,
+    pub chain_profile: &'a ChainProfile,
     pub s3_client: &'a aws_sdk_s3::Client,
     pub json_rpc_client: &'a JsonRpcClient,
     pub registry_contract_id: &'a str,
-    pub redis_connection_manager: &'a ConnectionManager,
+    pub storage: &'a SharedStorage,
+    pub retention_policy: &'a RetentionPolicy,
+    pub streamer_message_encoding: StreamerMessageEncoding,
     pub indexer_registry: &'a SharedIndexerRegistry,
+    pub registry_rules: &'a SharedRegistryRules,
     pub streamers: &'a Streamers,
 }
 
@@ -48,17 +69,33 @@ async fn main() -> anyhow::Result<()> {
 
     let opts = Opts::parse();
 
-    let chain_id = &opts.chain_id();
+    let chain_profile = &opts.chain_profile();
     let registry_contract_id = opts.registry_contract_id.clone();
+    let retention_policy = &opts.retention_policy();
 
     let aws_config = &opts.lake_aws_sdk_config();
     let s3_config = aws_sdk_s3::config::Builder::from(aws_config).build();
     let s3_client = aws_sdk_s3::Client::from_conf(s3_config);
 
     tracing::info!(target: INDEXER, "Connecting to redis...");
-    let redis_connection_manager = storage::connect(&opts.redis_connection_string).await?;
+    let storage = opts.connect_storage().await?;
+
+    let start_options = match &opts.command {
+        Command::Index(chain_id) => chain_id.start_options(),
+        Command::MigrateKeys { .. } => {
+            let migrated_keys = storage::migrate_keys(storage.inner(), storage.namespace()).await?;
+            tracing::info!(
+                target: INDEXER,
+                "Migrated {} keys into {}",
+                migrated_keys,
+                storage.namespace().name()
+            );
+            return Ok(());
+        }
+    };
+    let storage: SharedStorage = std::sync::Arc::new(storage);
 
-    let json_rpc_client = JsonRpcClient::connect(opts.rpc_url());
+    let json_rpc_client = JsonRpcClient::connect(opts.archival_rpc_url());
 
     // fetch raw indexer functions for use in indexer
     // Could this give us results from a newer block than the next block we receive from the Lake?
@@ -75,28 +112,48 @@ async fn main() -> anyhow::Result<()> {
     let indexer_registry: SharedIndexerRegistry =
         std::sync::Arc::new(Mutex::new(indexer_functions));
 
+    let registry_rules: SharedRegistryRules = std::sync::Arc::new(Mutex::new(None));
     let streamers = std::sync::Arc::new(tokio::sync::Mutex::new(HashMap::new()));
 
     tracing::info!(target: INDEXER, "Generating LakeConfig...");
-    let config: near_lake_framework::LakeConfig = opts.to_lake_config().await;
+    let config: near_lake_framework::LakeConfig = opts.to_lake_config(start_options).await;
 
     tracing::info!(target: INDEXER, "Instantiating the stream...",);
     let (sender, stream) = near_lake_framework::streamer(config);
 
-    tokio::spawn(utils::stats(redis_connection_manager.clone()));
-    tokio::spawn(metrics::init_server(opts.port).expect("Failed to start metrics server"));
+    tokio::spawn(utils::stats(storage.clone()));
+    tokio::spawn(runner_progress::publish(storage.clone()));
+    tokio::spawn(retention::trim_streams(
+        storage.clone(),
+        retention_policy.clone(),
+        std::time::Duration::from_secs(opts.retention.stream_trim_interval_seconds),
+    ));
+    tokio::spawn(
+        metrics::init_server(
+            opts.port,
+            explain::ExplainContext {
+                chain_profile: chain_profile.clone(),
+                s3_client: s3_client.clone(),
+                indexer_registry: indexer_registry.clone(),
+            },
+        )
+        .expect("Failed to start metrics server"),
+    );
 
     tracing::info!(target: INDEXER, "Starting queryapi_coordinator...",);
     let mut handlers = tokio_stream::wrappers::ReceiverStream::new(stream)
         .map(|streamer_message| {
             let context = QueryApiContext {
-                redis_connection_manager: &redis_connection_manager,
+                storage: &storage,
+                retention_policy,
+                streamer_message_encoding: opts.streamer_message_encoding,
                 registry_contract_id: &registry_contract_id,
                 streamer_message,
-                chain_id,
+                chain_profile,
                 json_rpc_client: &json_rpc_client,
                 s3_client: &s3_client,
                 indexer_registry: &indexer_registry,
+                registry_rules: &registry_rules,
                 streamers: &streamers,
             };
 
@@ -120,88 +177,122 @@ async fn main() -> anyhow::Result<()> {
 }
 
 async fn handle_streamer_message(context: QueryApiContext<'_>) -> anyhow::Result<u64> {
-    let indexer_functions = {
-        let lock = context.indexer_registry.lock().await;
+    let registry_rules = indexer_registry::registry_rules(&context).await;
+
+    // every write for the block is committed at once, together with the checkpoint
+    let mut writes = WriteBatch::new();
+
+    let mut matches_by_indexer_function = registry_rules.receipt_rules.reduce_indexer_rule_matches(
+        &context.streamer_message,
+        context.chain_profile.chain_id.clone(),
+        ExtractionMode::All,
+    );
+
+    // receipts of earlier blocks are only resolved when a match or a tracked transaction needs them
+    let receipt_transaction_hashes = resolve_receipt_transaction_hashes(
+        &context.streamer_message,
+        context.storage.as_ref(),
+        context.retention_policy,
+        !matches_by_indexer_function.is_empty() || !registry_rules.transaction_rules.is_empty(),
+        &mut writes,
+    )
+    .await?;
+
+    // transactions are only tracked across blocks while some indexer function asks for them
+    if !registry_rules.transaction_rules.is_empty() {
+        matches_by_indexer_function.extend(
+            reduce_transaction_matches(
+                &registry_rules,
+                &context,
+                &receipt_transaction_hashes,
+                &mut writes,
+            )
+            .await?,
+        );
+    }
 
-        lock.values()
+    // the functions are read before the registry changes of this block, like their rules were
+    let matched_functions: Vec<IndexerFunction> = if matches_by_indexer_function.is_empty() {
+        vec![]
+    } else {
+        context
+            .indexer_registry
+            .lock()
+            .await
+            .values()
             .flat_map(|fns| fns.values())
+            .filter(|indexer_function| {
+                matches_by_indexer_function.contains_key(&indexer_function.get_full_name())
+            })
             .cloned()
-            .collect::<Vec<_>>()
+            .collect()
     };
 
-    let mut indexer_function_filter_matches_futures = stream::iter(indexer_functions.iter())
-        .map(|indexer_function| {
-            reduce_rule_matches_for_indexer_function(
-                indexer_function,
-                &context.streamer_message,
-                context.chain_id.clone(),
-            )
-        })
-        // TODO: fix the buffer size used to accumulate results, it takes 10 vecs of vecs while we want to take 10 IndexerRuleMatches
-        .buffer_unordered(10usize);
-
     let block_height: BlockHeight = context.streamer_message.block.header.height;
 
     // Cache streamer message block and shards for use in real time processing
-    storage::set(
-        context.redis_connection_manager,
-        generate_real_time_streamer_message_key(block_height),
-        &serialize_to_camel_case_json_string(&context.streamer_message)?,
-        Some(60),
-    )
-    .await?;
-
-    indexer_registry::index_registry_changes(block_height, &context).await?;
+    writes.set(
+        &generate_real_time_streamer_message_key(block_height),
+        storage::encode_streamer_message(
+            &context.streamer_message,
+            context.streamer_message_encoding,
+        )?,
+        Some(context.retention_policy.streamer_message_expiration_seconds),
+    );
 
-    while let Some(indexer_function_with_matches) =
-        indexer_function_filter_matches_futures.next().await
-    {
-        if let Ok(indexer_function_with_matches) = indexer_function_with_matches {
-            let indexer_function = indexer_function_with_matches.indexer_function;
-            let indexer_rule_matches = indexer_function_with_matches.matches;
-
-            for _ in indexer_rule_matches.iter() {
-                tracing::debug!(
-                    target: INDEXER,
-                    "Matched filter {:?} for function {} {}",
-                    indexer_function.indexer_rule.matching_rule,
-                    indexer_function.account_id,
-                    indexer_function.function_name,
-                );
+    for indexer_function in matched_functions.iter() {
+        if let Some(mut indexer_rule_matches) =
+            matches_by_indexer_function.remove(&indexer_function.get_full_name())
+        {
+            receipt_transaction_hashes.populate(&mut indexer_rule_matches);
 
-                if !indexer_function.provisioned {
-                    set_provisioned_flag(context.indexer_registry, indexer_function).await;
-                }
+            tracing::debug!(
+                target: INDEXER,
+                "Matched filter {:?} for function {} {}, {} matches",
+                indexer_function.indexer_rule.matching_rule,
+                indexer_function.account_id,
+                indexer_function.function_name,
+                indexer_rule_matches.len(),
+            );
 
-                storage::sadd(
-                    context.redis_connection_manager,
+            // the consumer group goes ahead of the first entry so the runner reads the stream from its start
+            writes
+                .create_consumer_group(&storage::generate_real_time_stream_key(
+                    &indexer_function.get_full_name(),
+                ))
+                .sadd(
                     storage::STREAMS_SET_KEY,
-                    storage::generate_real_time_stream_key(&indexer_function.get_full_name()),
+                    &storage::generate_real_time_stream_key(&indexer_function.get_full_name()),
                 )
-                .await?;
-                storage::set(
-                    context.redis_connection_manager,
-                    storage::generate_real_time_storage_key(&indexer_function.get_full_name()),
-                    serde_json::to_string(indexer_function)?,
+                .set(
+                    &storage::generate_real_time_storage_key(&indexer_function.get_full_name()),
+                    &serde_json::to_string(indexer_function)?,
                     None,
                 )
-                .await?;
-                storage::xadd(
-                    context.redis_connection_manager,
-                    storage::generate_real_time_stream_key(&indexer_function.get_full_name()),
-                    &[("block_height", block_height)],
-                )
-                .await?;
-            }
+                // one stream entry per block, carrying every match extracted from it, a replayed block is skipped
+                .xadd(
+                    &storage::generate_real_time_stream_key(&indexer_function.get_full_name()),
+                    block_height,
+                    &[
+                        ("block_height", block_height.to_string()),
+                        ("matches", serde_json::to_string(&indexer_rule_matches)?),
+                    ],
+                );
         }
     }
 
-    // cache last indexed block height
-    storage::update_last_indexed_block(
-        context.redis_connection_manager,
-        context.streamer_message.block.header.height,
-    )
-    .await?;
+    // cache last indexed block height, it only advances if every stream write above succeeds
+    writes.update_last_indexed_block(block_height);
+    context.storage.commit(writes).await?;
+
+    // the registry only changes once the block is committed, so a failed block replays against
+    // the same functions
+    for indexer_function in matched_functions.iter() {
+        if !indexer_function.provisioned {
+            set_provisioned_flag(context.indexer_registry, indexer_function).await;
+        }
+    }
+    indexer_registry::index_registry_changes(block_height, &context).await?;
 
     metrics::BLOCK_COUNT.inc();
     metrics::LATEST_BLOCK_HEIGHT.set(
@@ -257,26 +348,144 @@ async fn set_provisioned_flag(
     }
 }
 
-struct IndexerFunctionWithMatches<'b> {
-    pub indexer_function: &'b IndexerFunction,
-    pub matches: Vec<IndexerRuleMatch>,
+/// Receipts created in earlier blocks are resolved through the watching list if
+/// `resolve_previous_blocks`, while the receipts this block creates for later ones are pushed to it
+async fn resolve_receipt_transaction_hashes(
+    streamer_message: &StreamerMessage,
+    storage: &dyn Storage,
+    retention_policy: &RetentionPolicy,
+    resolve_previous_blocks: bool,
+    writes: &mut WriteBatch,
+) -> anyhow::Result<ReceiptTransactionHashes> {
+    let previous_blocks_transaction_hashes = if resolve_previous_blocks {
+        read_receipts_watching_list(
+            storage,
+            transaction_resolver::receipts_from_previous_blocks(streamer_message),
+        )
+        .await?
+    } else {
+        HashMap::new()
+    };
+
+    let receipt_transaction_hashes =
+        ReceiptTransactionHashes::from_block(streamer_message, previous_blocks_transaction_hashes);
+
+    for (receipt_id, origin) in receipt_transaction_hashes.outgoing_receipts() {
+        storage::push_receipt_to_watching_list(
+            writes,
+            receipt_id,
+            &origin.to_string(),
+            retention_policy.receipt_watching_list_expiration_seconds,
+        );
+    }
+
+    Ok(receipt_transaction_hashes)
 }
 
-async fn reduce_rule_matches_for_indexer_function<'x>(
-    indexer_function: &'x IndexerFunction,
-    streamer_message: &StreamerMessage,
-    chain_id: ChainId,
-) -> anyhow::Result<IndexerFunctionWithMatches<'x>> {
-    let matches = indexer_rules_engine::reduce_indexer_rule_matches(
-        &indexer_function.indexer_rule,
-        streamer_message,
-        chain_id.clone(),
-    )
-    .await?;
-    Ok(IndexerFunctionWithMatches {
-        indexer_function,
-        matches,
-    })
+/// Transaction hashes of `receipt_ids` found in the watching list, following the receipts
+/// which created them, one round trip per level, for receipts pushed without a transaction
+async fn read_receipts_watching_list(
+    storage: &dyn Storage,
+    receipt_ids: Vec<String>,
+) -> anyhow::Result<HashMap<String, String>> {
+    let mut transaction_hashes = HashMap::new();
+    // each receipt of the block along with the receipt whose origin is read next
+    let mut unresolved_receipts: Vec<(String, String)> = receipt_ids
+        .into_iter()
+        .map(|receipt_id| (receipt_id.clone(), receipt_id))
+        .collect();
+
+    for _ in 0..MAX_RECEIPT_ORIGIN_DEPTH {
+        if unresolved_receipts.is_empty() {
+            break;
+        }
+        let ancestor_receipt_ids: Vec<String> = unresolved_receipts
+            .iter()
+            .map(|(_, ancestor_receipt_id)| ancestor_receipt_id.clone())
+            .collect();
+        let origins =
+            storage::get_receipts_from_watching_list(storage, &ancestor_receipt_ids).await?;
+
+        let mut next_unresolved_receipts = vec![];
+        for ((receipt_id, _), origin) in unresolved_receipts.into_iter().zip(origins) {
+            match origin.map(|origin| origin.parse::<ReceiptOrigin>()) {
+                Some(Ok(ReceiptOrigin::Transaction(transaction_hash))) => {
+                    transaction_hashes.insert(receipt_id, transaction_hash);
+                }
+                Some(Ok(ReceiptOrigin::Receipt(parent_receipt_id))) => {
+                    next_unresolved_receipts.push((receipt_id, parent_receipt_id));
+                }
+                Some(Err(err)) => {
+                    tracing::warn!(target: INDEXER, "Skipping receipt {}: {:#}", receipt_id, err);
+                }
+                None => {}
+            }
+        }
+        unresolved_receipts = next_unresolved_receipts;
+    }
+
+    Ok(transaction_hashes)
+}
+
+/// Advances the transactions pending in storage by the receipts of this block and matches
+/// the transaction indexer functions against the ones which completed
+async fn reduce_transaction_matches(
+    registry_rules: &indexer_registry::RegistryRules,
+    context: &QueryApiContext<'_>,
+    receipt_transaction_hashes: &ReceiptTransactionHashes,
+    writes: &mut WriteBatch,
+) -> anyhow::Result<HashMap<String, Vec<IndexerRuleMatch>>> {
+    let mut pending_transactions = HashMap::new();
+    for transaction_hash in transaction_tracker::pending_transaction_hashes(
+        &context.streamer_message,
+        receipt_transaction_hashes,
+    ) {
+        if let Some(pending_transaction) =
+            storage::get_pending_transaction(context.storage.as_ref(), &transaction_hash).await?
+        {
+            let pending_transaction: PendingTransaction =
+                serde_json::from_str(&pending_transaction)?;
+            pending_transactions.insert(transaction_hash, pending_transaction);
+        }
+    }
+
+    let completed_transactions = transaction_tracker::track_transactions(
+        &context.streamer_message,
+        receipt_transaction_hashes,
+        &registry_rules.transaction_filter,
+        &mut pending_transactions,
+    );
+
+    for (transaction_hash, pending_transaction) in pending_transactions.iter() {
+        storage::set_pending_transaction(
+            writes,
+            transaction_hash,
+            &serde_json::to_string(pending_transaction)?,
+            context
+                .retention_policy
+                .pending_transaction_expiration_seconds,
+        );
+    }
+    for transaction in completed_transactions.iter() {
+        storage::remove_pending_transaction(writes, &transaction.transaction.hash.to_string());
+    }
+
+    Ok(registry_rules
+        .transaction_rules
+        .iter()
+        .map(|(indexer_function_name, indexer_rule)| {
+            (
+                indexer_function_name.clone(),
+                transaction_tracker::reduce_transaction_matches(
+                    indexer_rule,
+                    &completed_transactions,
+                    &context.streamer_message,
+                    context.chain_profile.chain_id.clone(),
+                ),
+            )
+        })
+        .filter(|(_, indexer_rule_matches)| !indexer_rule_matches.is_empty())
+        .collect())
 }
 
 #[cfg(test)]
@@ -285,7 +494,9 @@ mod historical_block_processing_integration_tests;
 #[cfg(test)]
 mod tests {
     use super::*;
-    use indexer_rule_type::indexer_rule::{IndexerRule, IndexerRuleKind, MatchingRule, Status};
+    use indexer_rule_type::indexer_rule::{
+        IndexerRule, IndexerRuleKind, MatchingRule, PayloadEnrichment, Status,
+    };
     use std::collections::HashMap;
 
     #[tokio::test]
@@ -306,6 +517,7 @@ mod tests {
                     affected_account_id: "social.near".to_string(),
                     status: Status::Success,
                 },
+                payload_enrichment: PayloadEnrichment::None,
             },
         };
 
@@ -331,4 +543,23 @@ mod tests {
 
         assert!(indexer_function.provisioned);
     }
+
+    #[tokio::test]
+    async fn resolve_receipts_through_the_receipts_which_created_them() {
+        let storage = storage::InMemoryStorage::new();
+        let mut batch = WriteBatch::new();
+        storage::push_receipt_to_watching_list(&mut batch, "parent", "transaction:hash", 60);
+        storage::push_receipt_to_watching_list(&mut batch, "child", "receipt:parent", 60);
+        storage::push_receipt_to_watching_list(&mut batch, "orphan", "receipt:unknown", 60);
+        storage.commit(batch).await.unwrap();
+
+        let transaction_hashes =
+            read_receipts_watching_list(&storage, vec!["child".to_string(), "orphan".to_string()])
+                .await
+                .unwrap();
+        assert_eq!(
+            transaction_hashes,
+            HashMap::from([("child".to_string(), "hash".to_string())])
+        );
+    }
 }
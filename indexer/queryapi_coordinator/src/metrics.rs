@@ -1,8 +1,10 @@
-use actix_web::{get, App, HttpServer, Responder};
+use actix_web::{get, web, App, HttpServer, Responder};
 use lazy_static::lazy_static;
-use prometheus::{Encoder, IntCounter, IntGauge, Opts};
+use prometheus::{Encoder, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts};
 use tracing::info;
 
+use crate::explain::{self, ExplainContext};
+
 lazy_static! {
     pub(crate) static ref LATEST_BLOCK_HEIGHT: IntGauge = try_create_int_gauge(
         "queryapi_coordinator_latest_block_height",
@@ -14,6 +16,47 @@ lazy_static! {
         "Number of indexed blocks"
     )
     .unwrap();
+    pub(crate) static ref REDIS_USED_MEMORY: IntGauge = try_create_int_gauge(
+        "queryapi_coordinator_redis_used_memory_bytes",
+        "Bytes used by all keys of Redis"
+    )
+    .unwrap();
+    pub(crate) static ref STREAM_LENGTH: IntGaugeVec = try_create_int_gauge_vec(
+        "queryapi_coordinator_stream_length",
+        "Number of entries of an indexer stream after trimming",
+        &["stream"]
+    )
+    .unwrap();
+    pub(crate) static ref STREAM_MEMORY_USAGE: IntGaugeVec = try_create_int_gauge_vec(
+        "queryapi_coordinator_stream_memory_usage_bytes",
+        "Bytes used by an indexer stream after trimming",
+        &["stream"]
+    )
+    .unwrap();
+    pub(crate) static ref STREAM_TRIMMED_ENTRIES: IntCounterVec = try_create_int_counter_vec(
+        "queryapi_coordinator_stream_trimmed_entries",
+        "Number of entries trimmed from an indexer stream before the runner processed them",
+        &["stream"]
+    )
+    .unwrap();
+    pub(crate) static ref PROCESSED_BLOCK_HEIGHT: IntGaugeVec = try_create_int_gauge_vec(
+        "queryapi_coordinator_processed_block_height",
+        "Height up to which the runner processed every block of an indexer stream",
+        &["stream"]
+    )
+    .unwrap();
+    pub(crate) static ref RUNNER_LAG: IntGaugeVec = try_create_int_gauge_vec(
+        "queryapi_coordinator_runner_lag_blocks",
+        "Number of blocks the runner is behind the coordinator on an indexer stream",
+        &["stream"]
+    )
+    .unwrap();
+    pub(crate) static ref PENDING_ENTRIES: IntGaugeVec = try_create_int_gauge_vec(
+        "queryapi_coordinator_pending_entries",
+        "Number of entries of an indexer stream read by the runner but not acknowledged",
+        &["stream"]
+    )
+    .unwrap();
 }
 
 fn try_create_int_gauge(name: &str, help: &str) -> prometheus::Result<IntGauge> {
@@ -30,6 +73,28 @@ fn try_create_int_counter(name: &str, help: &str) -> prometheus::Result<IntCount
     Ok(counter)
 }
 
+fn try_create_int_gauge_vec(
+    name: &str,
+    help: &str,
+    label_names: &[&str],
+) -> prometheus::Result<IntGaugeVec> {
+    let opts = Opts::new(name, help);
+    let gauge = IntGaugeVec::new(opts, label_names)?;
+    prometheus::register(Box::new(gauge.clone()))?;
+    Ok(gauge)
+}
+
+fn try_create_int_counter_vec(
+    name: &str,
+    help: &str,
+    label_names: &[&str],
+) -> prometheus::Result<IntCounterVec> {
+    let opts = Opts::new(name, help);
+    let counter = IntCounterVec::new(opts, label_names)?;
+    prometheus::register(Box::new(counter.clone()))?;
+    Ok(counter)
+}
+
 #[get("/metrics")]
 async fn get_metrics() -> impl Responder {
     let mut buffer = Vec::<u8>::new();
@@ -45,14 +110,23 @@ async fn get_metrics() -> impl Responder {
     String::from_utf8(buffer.clone()).unwrap()
 }
 
-pub(crate) fn init_server(port: u16) -> anyhow::Result<actix_web::dev::Server> {
+pub(crate) fn init_server(
+    port: u16,
+    explain_context: ExplainContext,
+) -> anyhow::Result<actix_web::dev::Server> {
     info!(
         target: crate::INDEXER,
         "Starting metrics server on http://0.0.0.0:{port}"
     );
 
-    Ok(HttpServer::new(|| App::new().service(get_metrics))
-        .bind(("0.0.0.0", port))?
-        .disable_signals()
-        .run())
+    let explain_context = web::Data::new(explain_context);
+    Ok(HttpServer::new(move || {
+        App::new()
+            .app_data(explain_context.clone())
+            .service(get_metrics)
+            .service(explain::explain_block)
+    })
+    .bind(("0.0.0.0", port))?
+    .disable_signals()
+    .run())
 }
@@ -0,0 +1,57 @@
+use storage::RetentionPolicy;
+
+use crate::metrics;
+use crate::SharedStorage;
+use crate::INDEXER;
+
+/// Trims the indexer streams every `interval` and publishes how much memory they and
+/// Redis take, so a lagging indexer shows up before it fills Redis
+pub(crate) async fn trim_streams(
+    storage: SharedStorage,
+    retention_policy: RetentionPolicy,
+    interval: std::time::Duration,
+) {
+    loop {
+        tokio::time::sleep(interval).await;
+
+        // nothing is streamed before the first block is indexed
+        let last_indexed_block = match storage.get_last_indexed_block().await {
+            Ok(block_height) => block_height,
+            Err(_) => continue,
+        };
+
+        match storage::trim_streams(storage.as_ref(), &retention_policy, last_indexed_block).await {
+            Ok(stream_usages) => {
+                for stream_usage in stream_usages {
+                    if stream_usage.trimmed_entries > 0 {
+                        tracing::warn!(
+                            target: INDEXER,
+                            "Trimmed {} unprocessed entries of {}",
+                            stream_usage.trimmed_entries,
+                            stream_usage.stream_key,
+                        );
+                    }
+                    metrics::STREAM_LENGTH
+                        .with_label_values(&[&stream_usage.stream_key])
+                        .set(stream_usage.length as i64);
+                    metrics::STREAM_MEMORY_USAGE
+                        .with_label_values(&[&stream_usage.stream_key])
+                        .set(stream_usage.memory_usage_bytes as i64);
+                    metrics::STREAM_TRIMMED_ENTRIES
+                        .with_label_values(&[&stream_usage.stream_key])
+                        .inc_by(stream_usage.trimmed_entries);
+                }
+            }
+            Err(err) => {
+                tracing::error!(target: INDEXER, "Failed to trim streams\n{:#?}", err);
+            }
+        }
+
+        match storage.used_memory().await {
+            Ok(used_memory) => metrics::REDIS_USED_MEMORY.set(used_memory as i64),
+            Err(err) => {
+                tracing::warn!(target: INDEXER, "Failed to get used memory\n{:#?}", err);
+            }
+        }
+    }
+}
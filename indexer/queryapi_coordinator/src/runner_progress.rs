@@ -0,0 +1,107 @@
+use storage::Storage;
+
+use crate::metrics;
+use crate::SharedStorage;
+use crate::INDEXER;
+
+/// Publishes, for every indexer stream, the height up to which the runner processed its
+/// blocks and how far it lags behind, as read from the runner consumer group
+pub(crate) async fn publish(storage: SharedStorage) {
+    let interval_secs = 10;
+
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(interval_secs)).await;
+
+        if let Err(err) = publish_processed_block_heights(storage.as_ref()).await {
+            tracing::error!(
+                target: INDEXER,
+                "Failed to publish runner progress\n{:#?}",
+                err,
+            );
+        }
+    }
+}
+
+async fn publish_processed_block_heights(storage: &dyn Storage) -> anyhow::Result<()> {
+    // nothing is streamed before the first block is indexed
+    let last_indexed_block = match storage.get_last_indexed_block().await {
+        Ok(block_height) => block_height,
+        Err(_) => return Ok(()),
+    };
+
+    for stream_key in storage.smembers(storage::STREAMS_SET_KEY).await? {
+        let progress = match storage.consumer_group_progress(&stream_key).await? {
+            Some(progress) => progress,
+            None => continue,
+        };
+
+        let processed_block_height =
+            progress.stream_processed_block_height(&stream_key, last_indexed_block);
+
+        storage
+            .set(
+                &storage::generate_processed_block_height_key(&stream_key),
+                &processed_block_height.to_string(),
+                None,
+            )
+            .await?;
+
+        metrics::PROCESSED_BLOCK_HEIGHT
+            .with_label_values(&[&stream_key])
+            .set(processed_block_height as i64);
+        metrics::RUNNER_LAG
+            .with_label_values(&[&stream_key])
+            .set(last_indexed_block.saturating_sub(processed_block_height) as i64);
+        metrics::PENDING_ENTRIES
+            .with_label_values(&[&stream_key])
+            .set(progress.pending_entries as i64);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::publish_processed_block_heights;
+    use storage::{InMemoryStorage, Storage, WriteBatch};
+
+    #[tokio::test]
+    async fn publish_heights_processed_by_runner() {
+        let storage = InMemoryStorage::new();
+        let stream_key = storage::generate_real_time_stream_key("test.near/indexer");
+
+        let mut batch = WriteBatch::new();
+        batch
+            .create_consumer_group(&stream_key)
+            .sadd(storage::STREAMS_SET_KEY, &stream_key);
+        for block_height in [100, 105] {
+            batch.xadd(
+                &stream_key,
+                block_height,
+                &[("block_height", block_height.to_string())],
+            );
+        }
+        batch.update_last_indexed_block(110);
+        storage.commit(batch).await.unwrap();
+
+        storage.read_group(&stream_key, 2);
+        storage.acknowledge(&stream_key, 100);
+        publish_processed_block_heights(&storage).await.unwrap();
+        assert_eq!(
+            storage
+                .get(&storage::generate_processed_block_height_key(&stream_key))
+                .await
+                .unwrap(),
+            Some("104".to_string())
+        );
+
+        storage.acknowledge(&stream_key, 105);
+        publish_processed_block_heights(&storage).await.unwrap();
+        assert_eq!(
+            storage
+                .get(&storage::generate_processed_block_height_key(&stream_key))
+                .await
+                .unwrap(),
+            Some("110".to_string())
+        );
+    }
+}
@@ -194,13 +194,12 @@ fn file_name_date_after(start_date: DateTime<Utc>, file_name: &str) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use crate::historical_block_processing::INDEXED_DATA_FILES_BUCKET;
-    use crate::historical_block_processing::{INDEXED_ACTIONS_FILES_FOLDER, LAKE_BUCKET_PREFIX};
     use crate::opts::Opts;
     use crate::s3::{
         fetch_text_file_from_s3, find_index_files_by_pattern, list_s3_bucket_by_prefix,
     };
     use aws_sdk_s3::{Client as S3Client, Config};
+    use indexer_rules_engine::types::chain_profile::ChainProfile;
 
     /// Parses env vars from .env, Run with
     /// cargo test s3::tests::list_delta_bucket -- mainnet from-latest;
@@ -210,11 +209,12 @@ mod tests {
         let aws_config = &opts.lake_aws_sdk_config();
         let s3_config = aws_sdk_s3::config::Builder::from(aws_config).build();
         let s3_client = aws_sdk_s3::Client::from_conf(s3_config);
+        let index_files = ChainProfile::mainnet().index_files.unwrap();
 
         let list = list_s3_bucket_by_prefix(
             &s3_client,
-            INDEXED_DATA_FILES_BUCKET,
-            &format!("{}/", INDEXED_ACTIONS_FILES_FOLDER),
+            &index_files.bucket,
+            &format!("{}/", index_files.actions_folder),
         )
         .await
         .unwrap();
@@ -228,11 +228,12 @@ mod tests {
         let aws_config = &opts.lake_aws_sdk_config();
         let s3_config = aws_sdk_s3::config::Builder::from(aws_config).build();
         let s3_client = aws_sdk_s3::Client::from_conf(s3_config);
+        let index_files = ChainProfile::mainnet().index_files.unwrap();
 
         let list = find_index_files_by_pattern(
             &s3_client,
-            INDEXED_DATA_FILES_BUCKET,
-            INDEXED_ACTIONS_FILES_FOLDER,
+            &index_files.bucket,
+            &index_files.actions_folder,
             "hackathon.agency.near",
         )
         .await
@@ -247,11 +248,12 @@ mod tests {
         let aws_config = &opts.lake_aws_sdk_config();
         let s3_config = aws_sdk_s3::config::Builder::from(aws_config).build();
         let s3_client = aws_sdk_s3::Client::from_conf(s3_config);
+        let index_files = ChainProfile::mainnet().index_files.unwrap();
 
         let list = find_index_files_by_pattern(
             &s3_client,
-            INDEXED_DATA_FILES_BUCKET,
-            INDEXED_ACTIONS_FILES_FOLDER,
+            &index_files.bucket,
+            &index_files.actions_folder,
             "hackathon.agency.near, hackathon.aurora-silo-dev.near, hackathon.sputnik-dao.near",
         )
         .await
@@ -266,11 +268,12 @@ mod tests {
         let aws_config = &opts.lake_aws_sdk_config();
         let s3_config = aws_sdk_s3::config::Builder::from(aws_config).build();
         let s3_client = aws_sdk_s3::Client::from_conf(s3_config);
+        let index_files = ChainProfile::mainnet().index_files.unwrap();
 
         let list = find_index_files_by_pattern(
             &s3_client,
-            INDEXED_DATA_FILES_BUCKET,
-            INDEXED_ACTIONS_FILES_FOLDER,
+            &index_files.bucket,
+            &index_files.actions_folder,
             "*.keypom.near",
         )
         .await
@@ -285,11 +288,12 @@ mod tests {
         let aws_config = &opts.lake_aws_sdk_config();
         let s3_config = aws_sdk_s3::config::Builder::from(aws_config).build();
         let s3_client = aws_sdk_s3::Client::from_conf(s3_config);
+        let index_files = ChainProfile::mainnet().index_files.unwrap();
 
         let list = find_index_files_by_pattern(
             &s3_client,
-            INDEXED_DATA_FILES_BUCKET,
-            INDEXED_ACTIONS_FILES_FOLDER,
+            &index_files.bucket,
+            &index_files.actions_folder,
             "*.keypom.near, hackathon.agency.near, *.nearcrowd.near",
         )
         .await
@@ -326,7 +330,7 @@ mod tests {
         let s3_client: S3Client = S3Client::from_conf(s3_config);
 
         let s3_result = fetch_text_file_from_s3(
-            format!("{}{}", LAKE_BUCKET_PREFIX, "mainnet").as_str(),
+            &ChainProfile::mainnet().lake_bucket,
             "does_not_exist/block.json".to_string(),
             &s3_client,
         )
@@ -0,0 +1,251 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::{Parser, ValueEnum};
+use indexer_rule_type::indexer_rule::IndexerRule;
+use indexer_rules_engine::types::chain_profile::ChainProfile;
+use indexer_rules_engine::types::indexer_rule_match::{
+    ExtractionMode, IndexerRuleMatch, IndexerRuleMatchPayload,
+};
+use near_lake_framework::near_indexer_primitives::types::BlockHeight;
+use near_lake_framework::near_indexer_primitives::StreamerMessage;
+
+/// Runs an indexer rule over local blocks, e.g. the ones fetched by `download_test_blocks.sh`,
+/// to debug it before registering it in the registry contract
+#[derive(Parser, Debug)]
+#[clap(version, about)]
+struct Opts {
+    /// JSON file holding the IndexerRule
+    #[clap(long)]
+    rule: PathBuf,
+    /// Directory of `{block_height}.json` StreamerMessage files
+    #[clap(long, default_value = "./blocks")]
+    blocks_dir: PathBuf,
+    /// First block to test, inclusive
+    #[clap(long)]
+    from_block: Option<BlockHeight>,
+    /// Last block to test, inclusive
+    #[clap(long)]
+    to_block: Option<BlockHeight>,
+    #[clap(long, value_enum, default_value = "mainnet")]
+    chain: Chain,
+    #[clap(long, value_enum, default_value = "table")]
+    format: OutputFormat,
+    /// Only report the first match of each shard, as the historical backfill does
+    #[clap(long)]
+    first_per_shard: bool,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum Chain {
+    Mainnet,
+    Testnet,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum OutputFormat {
+    Json,
+    Table,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opts = Opts::parse();
+
+    let chain_profile = match opts.chain {
+        Chain::Mainnet => ChainProfile::mainnet(),
+        Chain::Testnet => ChainProfile::testnet(),
+    };
+    let extraction_mode = if opts.first_per_shard {
+        ExtractionMode::FirstPerShard
+    } else {
+        ExtractionMode::All
+    };
+
+    let indexer_rule = read_indexer_rule(&opts.rule)?;
+
+    let mut indexer_rule_matches = vec![];
+    for (block_height, path) in block_files(&opts.blocks_dir, opts.from_block, opts.to_block)? {
+        let streamer_message = read_streamer_message(&path)
+            .with_context(|| format!("Unable to read block {}", block_height))?;
+        indexer_rule_matches.extend(indexer_rules_engine::reduce_indexer_rule_matches_sync(
+            &indexer_rule,
+            &streamer_message,
+            chain_profile.chain_id.clone(),
+            extraction_mode,
+        ));
+    }
+
+    match opts.format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&indexer_rule_matches)?),
+        OutputFormat::Table => print!("{}", format_table(&indexer_rule_matches, &chain_profile)),
+    }
+
+    Ok(())
+}
+
+fn read_indexer_rule(path: &Path) -> anyhow::Result<IndexerRule> {
+    let indexer_rule: IndexerRule = serde_json::from_str(
+        &std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read rule {}", path.display()))?,
+    )
+    .with_context(|| format!("Invalid rule JSON {}", path.display()))?;
+    indexer_rule
+        .matching_rule
+        .validate()
+        .map_err(|err| anyhow::anyhow!("Invalid rule {}: {}", path.display(), err))?;
+
+    Ok(indexer_rule)
+}
+
+fn read_streamer_message(path: &Path) -> anyhow::Result<StreamerMessage> {
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+/// Block files of the directory within the range, ordered by height.
+/// Files which are not named after a block height are ignored.
+fn block_files(
+    blocks_dir: &Path,
+    from_block: Option<BlockHeight>,
+    to_block: Option<BlockHeight>,
+) -> anyhow::Result<Vec<(BlockHeight, PathBuf)>> {
+    let mut block_files = vec![];
+    for entry in std::fs::read_dir(blocks_dir)
+        .with_context(|| format!("Unable to read blocks directory {}", blocks_dir.display()))?
+    {
+        let path = entry?.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+            continue;
+        }
+        let block_height = match path
+            .file_stem()
+            .and_then(|file_stem| file_stem.to_str())
+            .and_then(|file_stem| file_stem.parse::<BlockHeight>().ok())
+        {
+            Some(block_height) => block_height,
+            None => continue,
+        };
+        if from_block.is_none_or(|from_block| block_height >= from_block)
+            && to_block.is_none_or(|to_block| block_height <= to_block)
+        {
+            block_files.push((block_height, path));
+        }
+    }
+    block_files.sort();
+
+    Ok(block_files)
+}
+
+fn format_table(indexer_rule_matches: &[IndexerRuleMatch], chain_profile: &ChainProfile) -> String {
+    let header = ["BLOCK", "PAYLOAD", "RECEIPT", "EXPLORER"].map(String::from);
+    let rows: Vec<[String; 4]> = indexer_rule_matches
+        .iter()
+        .map(|indexer_rule_match| {
+            let payload = match &indexer_rule_match.payload {
+                IndexerRuleMatchPayload::Actions { .. } => "Actions".to_string(),
+                IndexerRuleMatchPayload::Events {
+                    standard, event, ..
+                } => format!("Events {}/{}", standard, event),
+                IndexerRuleMatchPayload::StateChanges { .. } => "StateChanges".to_string(),
+                IndexerRuleMatchPayload::Block { .. } => "Block".to_string(),
+                IndexerRuleMatchPayload::Transaction { .. } => "Transaction".to_string(),
+            };
+            [
+                indexer_rule_match.block_height.to_string(),
+                payload,
+                indexer_rule_match
+                    .payload
+                    .receipt_id()
+                    .unwrap_or_else(|| "-".to_string()),
+                indexer_rule_match.explorer_link(chain_profile),
+            ]
+        })
+        .collect();
+
+    let mut widths = header.clone().map(|column| column.len());
+    for row in &rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.len());
+        }
+    }
+
+    std::iter::once(&header)
+        .chain(rows.iter())
+        .map(|row| {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(column, width)| format!("{:width$}", column, width = width))
+                .collect::<Vec<_>>()
+                .join("  ");
+            format!("{}\n", line.trim_end())
+        })
+        .collect::<String>()
+        + &format!("{} matches\n", rows.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{block_files, format_table};
+    use indexer_rules_engine::types::chain_profile::ChainProfile;
+    use indexer_rules_engine::types::indexer_rule_match::{
+        ChainId, IndexerRuleMatch, IndexerRuleMatchPayload,
+    };
+
+    #[test]
+    fn select_block_files_in_range() {
+        let blocks_dir = std::env::temp_dir().join(format!("rule_tester_{}", std::process::id()));
+        std::fs::create_dir_all(&blocks_dir).unwrap();
+        for file_name in [
+            "93085141.json",
+            "93085142.json",
+            "93085150.json",
+            "notes.json",
+        ] {
+            std::fs::write(blocks_dir.join(file_name), "{}").unwrap();
+        }
+
+        let heights = |from_block, to_block| -> Vec<u64> {
+            block_files(&blocks_dir, from_block, to_block)
+                .unwrap()
+                .into_iter()
+                .map(|(block_height, _)| block_height)
+                .collect()
+        };
+        assert_eq!(heights(None, None), vec![93085141, 93085142, 93085150]);
+        assert_eq!(heights(Some(93085142), None), vec![93085142, 93085150]);
+        assert_eq!(
+            heights(Some(93085141), Some(93085142)),
+            vec![93085141, 93085142]
+        );
+
+        std::fs::remove_dir_all(&blocks_dir).unwrap();
+    }
+
+    #[test]
+    fn format_matches_as_table() {
+        let indexer_rule_matches = vec![IndexerRuleMatch {
+            chain_id: ChainId::Mainnet,
+            indexer_rule_id: None,
+            indexer_rule_name: None,
+            payload: IndexerRuleMatchPayload::Events {
+                block_hash: "block".to_string(),
+                receipt_id: "receipt".to_string(),
+                transaction_hash: Some("tx".to_string()),
+                event: "nft_mint".to_string(),
+                standard: "nep171".to_string(),
+                version: "1.0.0".to_string(),
+                data: None,
+                transfers: vec![],
+                validation_error: None,
+            },
+            block_height: 93085141,
+        }];
+
+        assert_eq!(
+            format_table(&indexer_rule_matches, &ChainProfile::mainnet()),
+            "BLOCK     PAYLOAD                 RECEIPT  EXPLORER\n\
+             93085141  Events nep171/nft_mint  receipt  https://explorer.near.org/transactions/tx#receipt\n\
+             1 matches\n"
+        );
+    }
+}
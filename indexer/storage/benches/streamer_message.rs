@@ -0,0 +1,95 @@
+//! Compares caching streamer messages as camelCase JSON, in plain and zstd encodings, with
+//! the former round trip through a `serde_json::Value` renaming its keys, and with serializing
+//! them by serde_json alone.
+//! Download the fixture blocks first: `./download_test_blocks.sh 80854399 92476362 93085141 93659695`
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use near_indexer_primitives::StreamerMessage;
+use serde_json::Value;
+use storage::{encode_streamer_message, to_camel_case_json, StreamerMessageEncoding};
+
+const FIXTURE_BLOCKS: [u64; 4] = [80854399, 92476362, 93085141, 93659695];
+
+fn read_local_streamer_message(block_height: u64) -> StreamerMessage {
+    let path = format!("../blocks/{}.json", block_height);
+    let json =
+        std::fs::read_to_string(&path).unwrap_or_else(|_| panic!("Missing fixture block {}", path));
+    serde_json::from_str(&json).unwrap()
+}
+
+/// camelCase JSON as the coordinator serialized it before [to_camel_case_json]
+fn value_round_trip(streamer_message: &StreamerMessage) -> serde_json::Result<String> {
+    let json_str = serde_json::to_string(&streamer_message)?;
+    let mut message_value: Value = serde_json::from_str(&json_str)?;
+    to_camel_case_keys(&mut message_value);
+    serde_json::to_string(&message_value)
+}
+
+fn to_camel_case_keys(message_value: &mut Value) {
+    match message_value {
+        Value::Object(map) => {
+            for key in map.keys().cloned().collect::<Vec<String>>() {
+                let new_key = key
+                    .split('_')
+                    .enumerate()
+                    .map(|(i, str)| {
+                        if i > 0 {
+                            return str[..1].to_uppercase() + &str[1..];
+                        }
+                        str.to_owned()
+                    })
+                    .collect::<Vec<String>>()
+                    .join("");
+
+                if let Some(mut val) = map.remove(&key) {
+                    to_camel_case_keys(&mut val);
+                    map.insert(new_key, val);
+                }
+            }
+        }
+        Value::Array(vec) => {
+            for val in vec {
+                to_camel_case_keys(val);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn encode_streamer_messages(criterion: &mut Criterion) {
+    let streamer_messages: Vec<StreamerMessage> = FIXTURE_BLOCKS
+        .iter()
+        .map(|block_height| read_local_streamer_message(*block_height))
+        .collect();
+
+    let mut group = criterion.benchmark_group("encode_streamer_message");
+    for (block_height, streamer_message) in FIXTURE_BLOCKS.iter().zip(&streamer_messages) {
+        group.bench_with_input(
+            BenchmarkId::new("value_round_trip", block_height),
+            streamer_message,
+            |bencher, streamer_message| bencher.iter(|| value_round_trip(streamer_message)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("serde_json", block_height),
+            streamer_message,
+            |bencher, streamer_message| bencher.iter(|| serde_json::to_vec(streamer_message)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("camel_case_json", block_height),
+            streamer_message,
+            |bencher, streamer_message| bencher.iter(|| to_camel_case_json(streamer_message)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("zstd", block_height),
+            streamer_message,
+            |bencher, streamer_message| {
+                bencher.iter(|| {
+                    encode_streamer_message(streamer_message, StreamerMessageEncoding::Zstd)
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encode_streamer_messages);
+criterion_main!(benches);
@@ -0,0 +1,351 @@
+/// Single key write of a [WriteBatch]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WriteCommand {
+    Set {
+        key: String,
+        value: Vec<u8>,
+        expiration_seconds: Option<usize>,
+    },
+    Del {
+        key: String,
+    },
+    IncrBy {
+        key: String,
+        delta: i64,
+    },
+    SAdd {
+        key: String,
+        member: String,
+    },
+    XAdd {
+        stream_key: String,
+        block_height: u64,
+        fields: Vec<(String, String)>,
+    },
+    CreateConsumerGroup {
+        stream_key: String,
+    },
+}
+
+impl WriteCommand {
+    pub fn key(&self) -> &str {
+        match self {
+            WriteCommand::Set { key, .. }
+            | WriteCommand::Del { key }
+            | WriteCommand::IncrBy { key, .. }
+            | WriteCommand::SAdd { key, .. }
+            | WriteCommand::XAdd {
+                stream_key: key, ..
+            }
+            | WriteCommand::CreateConsumerGroup { stream_key: key } => key,
+        }
+    }
+
+    fn key_mut(&mut self) -> &mut String {
+        match self {
+            WriteCommand::Set { key, .. }
+            | WriteCommand::Del { key }
+            | WriteCommand::IncrBy { key, .. }
+            | WriteCommand::SAdd { key, .. }
+            | WriteCommand::XAdd {
+                stream_key: key, ..
+            }
+            | WriteCommand::CreateConsumerGroup { stream_key: key } => key,
+        }
+    }
+
+    /// Redis command name and its arguments, the key among them. Values are passed as bytes
+    /// since they need not be UTF-8, e.g. compressed streamer messages.
+    pub(crate) fn redis_args(&self) -> (&'static str, Vec<Vec<u8>>) {
+        let key = self.key().as_bytes().to_vec();
+        match self {
+            WriteCommand::Set {
+                value,
+                expiration_seconds,
+                ..
+            } => {
+                let mut args = vec![key, value.clone()];
+                if let Some(expiration_seconds) = expiration_seconds {
+                    args.push(b"EX".to_vec());
+                    args.push(expiration_seconds.to_string().into_bytes());
+                }
+                ("SET", args)
+            }
+            WriteCommand::Del { .. } => ("DEL", vec![key]),
+            WriteCommand::IncrBy { delta, .. } => {
+                ("INCRBY", vec![key, delta.to_string().into_bytes()])
+            }
+            WriteCommand::SAdd { member, .. } => ("SADD", vec![key, member.as_bytes().to_vec()]),
+            WriteCommand::XAdd {
+                block_height,
+                fields,
+                ..
+            } => {
+                let mut args = vec![
+                    key,
+                    crate::generate_stream_entry_id(*block_height).into_bytes(),
+                ];
+                for (field, value) in fields {
+                    args.push(field.as_bytes().to_vec());
+                    args.push(value.as_bytes().to_vec());
+                }
+                ("XADD", args)
+            }
+            WriteCommand::CreateConsumerGroup { .. } => (
+                "XGROUP",
+                vec![
+                    b"CREATE".to_vec(),
+                    key,
+                    crate::RUNNER_CONSUMER_GROUP.as_bytes().to_vec(),
+                    b"0".to_vec(),
+                    b"MKSTREAM".to_vec(),
+                ],
+            ),
+        }
+    }
+
+    /// Part of the error message of a failure which leaves the storage as the command
+    /// would have, so the rest of the batch still applies
+    pub(crate) fn ignored_error(&self) -> Option<&'static str> {
+        match self {
+            WriteCommand::XAdd { .. } => Some(crate::STALE_STREAM_ENTRY_ERROR),
+            WriteCommand::CreateConsumerGroup { .. } => Some(crate::EXISTING_CONSUMER_GROUP_ERROR),
+            _ => None,
+        }
+    }
+}
+
+/// Writes committed together by [crate::Storage::commit], in the order they were added.
+/// If one of them fails the ones after it are not applied while the ones before it stay
+/// applied, so the checkpoint goes last and the batch of a failed block is committed again,
+/// the replayed stream entries being skipped by their ids.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WriteBatch {
+    commands: Vec<WriteCommand>,
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn commands(&self) -> &[WriteCommand] {
+        &self.commands
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Same writes with every key in `namespace`
+    pub(crate) fn with_namespace(mut self, namespace: &crate::KeyNamespace) -> Self {
+        for command in self.commands.iter_mut() {
+            *command.key_mut() = namespace.key(command.key());
+        }
+        self
+    }
+
+    /// Writes of each Redis Cluster hash slot, ordered by the last write of each slot so
+    /// the slot of the checkpoint comes after the slots it checkpoints
+    pub(crate) fn split_by_slot(&self) -> Vec<WriteBatch> {
+        let mut slot_batches: Vec<(u16, usize, WriteBatch)> = vec![];
+        for (index, command) in self.commands.iter().enumerate() {
+            let slot = redis::cluster_routing::get_slot(command.key().as_bytes());
+            match slot_batches
+                .iter_mut()
+                .find(|(batch_slot, _, _)| *batch_slot == slot)
+            {
+                Some((_, last_index, batch)) => {
+                    *last_index = index;
+                    batch.commands.push(command.clone());
+                }
+                None => slot_batches.push((
+                    slot,
+                    index,
+                    WriteBatch {
+                        commands: vec![command.clone()],
+                    },
+                )),
+            }
+        }
+        slot_batches.sort_by_key(|(_, last_index, _)| *last_index);
+        slot_batches
+            .into_iter()
+            .map(|(_, _, batch)| batch)
+            .collect()
+    }
+
+    pub fn set(
+        &mut self,
+        key: &str,
+        value: impl AsRef<[u8]>,
+        expiration_seconds: Option<usize>,
+    ) -> &mut Self {
+        self.commands.push(WriteCommand::Set {
+            key: key.to_string(),
+            value: value.as_ref().to_vec(),
+            expiration_seconds,
+        });
+        self
+    }
+
+    pub fn del(&mut self, key: &str) -> &mut Self {
+        self.commands.push(WriteCommand::Del {
+            key: key.to_string(),
+        });
+        self
+    }
+
+    pub fn incr(&mut self, key: &str, delta: i64) -> &mut Self {
+        self.commands.push(WriteCommand::IncrBy {
+            key: key.to_string(),
+            delta,
+        });
+        self
+    }
+
+    pub fn sadd(&mut self, key: &str, member: &str) -> &mut Self {
+        self.commands.push(WriteCommand::SAdd {
+            key: key.to_string(),
+            member: member.to_string(),
+        });
+        self
+    }
+
+    /// Queues the stream entry of `block_height`, skipped on commit if the stream already
+    /// has an entry for this block or a later one
+    pub fn xadd(
+        &mut self,
+        stream_key: &str,
+        block_height: u64,
+        fields: &[(&str, String)],
+    ) -> &mut Self {
+        self.commands.push(WriteCommand::XAdd {
+            stream_key: stream_key.to_string(),
+            block_height,
+            fields: fields
+                .iter()
+                .map(|(field, value)| (field.to_string(), value.clone()))
+                .collect(),
+        });
+        self
+    }
+
+    /// Queues the creation of the runner consumer group of the stream, including the stream
+    /// itself, unless the group exists
+    pub fn create_consumer_group(&mut self, stream_key: &str) -> &mut Self {
+        self.commands.push(WriteCommand::CreateConsumerGroup {
+            stream_key: stream_key.to_string(),
+        });
+        self
+    }
+
+    pub fn update_last_indexed_block(&mut self, block_height: u64) -> &mut Self {
+        self.set("last_indexed_block", block_height.to_string(), None)
+            .incr("blocks_processed", 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WriteBatch;
+
+    #[test]
+    fn encode_commands_for_commit_script() {
+        let mut batch = WriteBatch::new();
+        batch
+            .set("streamer:message:1", "{}", Some(60))
+            .xadd(
+                "a:real_time:stream",
+                1,
+                &[("block_height", "1".to_string())],
+            )
+            .del("receipt");
+
+        let encoded: Vec<(&str, &str, Vec<String>)> = batch
+            .commands()
+            .iter()
+            .map(|command| {
+                let (name, args) = command.redis_args();
+                let args = args
+                    .into_iter()
+                    .map(|arg| String::from_utf8(arg).unwrap())
+                    .collect();
+                (command.key(), name, args)
+            })
+            .collect();
+        assert_eq!(
+            encoded,
+            vec![
+                (
+                    "streamer:message:1",
+                    "SET",
+                    vec![
+                        "streamer:message:1".to_string(),
+                        "{}".to_string(),
+                        "EX".to_string(),
+                        "60".to_string()
+                    ]
+                ),
+                (
+                    "a:real_time:stream",
+                    "XADD",
+                    vec![
+                        "a:real_time:stream".to_string(),
+                        "1-0".to_string(),
+                        "block_height".to_string(),
+                        "1".to_string()
+                    ]
+                ),
+                ("receipt", "DEL", vec!["receipt".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_by_hash_slot_of_indexer_function() {
+        let namespace = crate::KeyNamespace::hash_tagged("mainnet", None);
+        let stream_key = crate::generate_real_time_stream_key("test.near/indexer");
+        let mut batch = WriteBatch::new();
+        batch
+            .set("streamer:message:1", "{}", Some(60))
+            .create_consumer_group(&stream_key)
+            .sadd(crate::STREAMS_SET_KEY, &stream_key)
+            .set(
+                &crate::generate_real_time_storage_key("test.near/indexer"),
+                "{}",
+                None,
+            )
+            .xadd(&stream_key, 1, &[("block_height", "1".to_string())])
+            .update_last_indexed_block(1);
+
+        let keys: Vec<Vec<String>> = batch
+            .with_namespace(&namespace)
+            .split_by_slot()
+            .iter()
+            .map(|slot_batch| {
+                slot_batch
+                    .commands()
+                    .iter()
+                    .map(|command| command.key().to_string())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                vec![
+                    "{mainnet:test.near/indexer}:real_time:stream",
+                    "{mainnet:test.near/indexer}:real_time:stream:storage",
+                    "{mainnet:test.near/indexer}:real_time:stream",
+                ],
+                vec![
+                    "{mainnet}:streamer:message:1",
+                    "{mainnet}:streams",
+                    "{mainnet}:last_indexed_block",
+                    "{mainnet}:blocks_processed",
+                ],
+            ]
+        );
+    }
+}
@@ -0,0 +1,96 @@
+/// Delivery state of the runner consumer group of a stream, see [crate::RUNNER_CONSUMER_GROUP]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConsumerGroupProgress {
+    /// Block of the last entry read by the runner, `None` before its first read
+    pub last_delivered_block_height: Option<u64>,
+    /// Entries read by the runner which it has not acknowledged yet
+    pub pending_entries: u64,
+    pub oldest_pending_block_height: Option<u64>,
+    /// Block of the first entry the runner has not read yet
+    pub next_undelivered_block_height: Option<u64>,
+}
+
+impl ConsumerGroupProgress {
+    /// Height up to which the runner processed every block of the stream. When nothing is
+    /// pending or left to read it is `caught_up_block_height`, as matching blocks with no
+    /// entry need no processing.
+    pub fn processed_block_height(&self, caught_up_block_height: u64) -> u64 {
+        match self
+            .oldest_pending_block_height
+            .or(self.next_undelivered_block_height)
+        {
+            Some(block_height) => block_height.saturating_sub(1),
+            None => caught_up_block_height,
+        }
+    }
+
+    /// [Self::processed_block_height] of the stream, a real-time stream is caught up with the
+    /// last indexed block while a historical one is with its last delivered block
+    pub fn stream_processed_block_height(&self, stream_key: &str, last_indexed_block: u64) -> u64 {
+        let caught_up_block_height = if crate::is_real_time_stream_key(stream_key) {
+            last_indexed_block
+        } else {
+            self.last_delivered_block_height.unwrap_or_default()
+        };
+        self.processed_block_height(caught_up_block_height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{InMemoryStorage, Storage, WriteBatch};
+
+    async fn progress(storage: &InMemoryStorage) -> u64 {
+        storage
+            .consumer_group_progress("stream")
+            .await
+            .unwrap()
+            .unwrap()
+            .processed_block_height(20)
+    }
+
+    #[tokio::test]
+    async fn follow_runner_acknowledgements() {
+        let storage = InMemoryStorage::new();
+        assert_eq!(
+            storage.consumer_group_progress("stream").await.unwrap(),
+            None
+        );
+
+        let mut batch = WriteBatch::new();
+        batch.create_consumer_group("stream");
+        for block_height in [10, 12, 15] {
+            batch.xadd(
+                "stream",
+                block_height,
+                &[("block_height", block_height.to_string())],
+            );
+        }
+        storage.commit(batch).await.unwrap();
+        // creating the group again keeps what was delivered
+        storage.create_consumer_group("stream").await.unwrap();
+        assert_eq!(progress(&storage).await, 9);
+
+        assert_eq!(storage.read_group("stream", 2).len(), 2);
+        assert_eq!(progress(&storage).await, 9);
+
+        storage.acknowledge("stream", 10);
+        assert_eq!(progress(&storage).await, 11);
+
+        storage.acknowledge("stream", 12);
+        assert_eq!(progress(&storage).await, 14);
+
+        storage.read_group("stream", 1);
+        storage.acknowledge("stream", 15);
+        assert_eq!(
+            storage.consumer_group_progress("stream").await.unwrap(),
+            Some(super::ConsumerGroupProgress {
+                last_delivered_block_height: Some(15),
+                pending_entries: 0,
+                oldest_pending_block_height: None,
+                next_undelivered_block_height: None,
+            })
+        );
+        assert_eq!(progress(&storage).await, 20);
+    }
+}
@@ -1,16 +1,81 @@
 pub use redis::{self, aio::ConnectionManager, FromRedisValue, ToRedisArgs};
 
+pub use batch::{WriteBatch, WriteCommand};
+pub use connection::{connect, RedisConnection, RedisTopology, SentinelConnection};
+pub use consumer_group::ConsumerGroupProgress;
+pub use memory::InMemoryStorage;
+pub use namespace::{migrate_keys, KeyNamespace, NamespacedStorage};
+pub use retention::{trim_streams, RetentionPolicy, StreamTrim, StreamUsage};
+pub use streamer_message::{
+    decode_streamer_message, encode_streamer_message, to_camel_case_json, StreamerMessageEncoding,
+    ZSTD_STREAMER_MESSAGE_MARKER,
+};
+
+mod batch;
+mod connection;
+mod consumer_group;
+mod memory;
+mod namespace;
+mod retention;
+mod streamer_message;
+
 const STORAGE: &str = "storage_alertexer";
 
 pub const LAKE_BUCKET_PREFIX: &str = "near-lake-data-";
 pub const STREAMS_SET_KEY: &str = "streams";
+/// Consumer group the runner reads indexer streams through, acknowledging processed blocks
+pub const RUNNER_CONSUMER_GROUP: &str = "runner";
+pub const RECEIPT_WATCHING_LIST_EXPIRATION_SECONDS: usize = 60 * 60 * 24;
+pub const PENDING_TRANSACTION_EXPIRATION_SECONDS: usize = 60 * 60 * 24;
+pub const STREAMER_MESSAGE_EXPIRATION_SECONDS: usize = 60;
 
-pub async fn get_redis_client(redis_connection_str: &str) -> redis::Client {
-    redis::Client::open(redis_connection_str).expect("can create redis client")
-}
+/// Runs the commands of a [WriteBatch], `KEYS` holds the key of each command while `ARGV` holds
+/// the command name, the error it ignores, the count of its arguments and the arguments themselves.
+/// The script stops at the first failing command, leaving the commands after it unapplied and
+/// the ones before it applied, as Redis has no rollback. The batch is still applied all or nothing
+/// from the point of view of the checkpoint, which goes last: a failed block is committed again
+/// with the same writes, which replay without effect, stream entries keeping their `{height}-0`
+/// ids and sets, values and consumer groups being overwritten with the same content.
+const COMMIT_SCRIPT: &str = r"
+local argv_index = 1
+for _ = 1, #KEYS do
+    local command = ARGV[argv_index]
+    local ignored_error = ARGV[argv_index + 1]
+    local arg_count = tonumber(ARGV[argv_index + 2])
+    local result = redis.pcall(command, unpack(ARGV, argv_index + 3, argv_index + 2 + arg_count))
+    if type(result) == 'table' and result.err then
+        if ignored_error == '' or not string.find(result.err, ignored_error, 1, true) then
+            return result
+        end
+    end
+    argv_index = argv_index + 3 + arg_count
+end
+return #KEYS
+";
+
+/// Redis error of a stream entry added after the same or a later one
+const STALE_STREAM_ENTRY_ERROR: &str = "equal or smaller";
+/// Redis error of a consumer group created twice
+const EXISTING_CONSUMER_GROUP_ERROR: &str = "BUSYGROUP";
+
+const REAL_TIME_STREAM_SUFFIX: &str = ":real_time:stream";
+const HISTORICAL_STREAM_SUFFIX: &str = ":historical:stream";
 
 pub fn generate_real_time_stream_key(prefix: &str) -> String {
-    format!("{}:real_time:stream", prefix)
+    format!("{}{}", prefix, REAL_TIME_STREAM_SUFFIX)
+}
+
+pub fn is_real_time_stream_key(stream_key: &str) -> bool {
+    stream_key.ends_with(REAL_TIME_STREAM_SUFFIX)
+}
+
+/// Length of the indexer function name starting the keys of its streams, e.g. the stream
+/// itself, its storage and processed block height keys
+pub(crate) fn indexer_function_name_len(key: &str) -> Option<usize> {
+    [REAL_TIME_STREAM_SUFFIX, HISTORICAL_STREAM_SUFFIX]
+        .iter()
+        .filter_map(|suffix| key.find(suffix))
+        .min()
 }
 
 pub fn generate_real_time_streamer_message_key(block_height: u64) -> String {
@@ -18,38 +83,437 @@ pub fn generate_real_time_streamer_message_key(block_height: u64) -> String {
 }
 
 pub fn generate_real_time_storage_key(prefix: &str) -> String {
-    format!("{}:real_time:stream:storage", prefix)
+    format!("{}:storage", generate_real_time_stream_key(prefix))
 }
 
 pub fn generate_historical_stream_key(prefix: &str) -> String {
-    format!("{}:historical:stream", prefix)
+    format!("{}{}", prefix, HISTORICAL_STREAM_SUFFIX)
 }
 
 pub fn generate_historical_storage_key(prefix: &str) -> String {
-    format!("{}:historical:stream:storage", prefix)
+    format!("{}:storage", generate_historical_stream_key(prefix))
 }
 
-pub async fn connect(redis_connection_str: &str) -> anyhow::Result<ConnectionManager> {
-    Ok(get_redis_client(redis_connection_str)
-        .await
-        .get_tokio_connection_manager()
-        .await?)
+/// Stream entries are identified by the height of their block, so Redis rejects a block
+/// added twice, e.g. when resuming from an interruption
+pub fn generate_stream_entry_id(block_height: u64) -> String {
+    format!("{}-0", block_height)
+}
+
+pub(crate) fn parse_stream_entry_id(id: &str) -> Option<u64> {
+    id.split('-').next()?.parse().ok()
+}
+
+/// Height up to which the runner processed the blocks of the stream, published by the
+/// coordinator from the consumer group of the stream
+pub fn generate_processed_block_height_key(stream_key: &str) -> String {
+    format!("{}:processed_block_height", stream_key)
+}
+
+/// Whether Redis rejected a stream entry as its stream already holds the same or a later one
+fn is_stale_stream_entry(error: &redis::RedisError) -> bool {
+    error.to_string().contains(STALE_STREAM_ENTRY_ERROR)
+}
+
+pub fn generate_receipt_key(receipt_id: &str) -> String {
+    format!("receipt:{}", receipt_id)
+}
+
+pub fn generate_pending_transaction_key(transaction_hash: &str) -> String {
+    format!("transaction:pending:{}", transaction_hash)
+}
+
+/// Operations the coordinator needs from its storage, so its logic can run against
+/// [InMemoryStorage] in tests instead of a live Redis
+#[async_trait::async_trait]
+pub trait Storage: Send + Sync {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<String>>;
+
+    /// Values of all `keys` in a single round trip, in the order of the keys
+    async fn mget(&self, keys: &[String]) -> anyhow::Result<Vec<Option<String>>>;
+
+    async fn set(
+        &self,
+        key: &str,
+        value: &str,
+        expiration_seconds: Option<usize>,
+    ) -> anyhow::Result<()>;
+
+    async fn del(&self, key: &str) -> anyhow::Result<()>;
+
+    /// Adds `delta` to the integer stored at `key`, a missing key counts as 0
+    async fn incr(&self, key: &str, delta: i64) -> anyhow::Result<i64>;
+
+    async fn sadd(&self, key: &str, member: &str) -> anyhow::Result<()>;
+
+    async fn smembers(&self, key: &str) -> anyhow::Result<Vec<String>>;
+
+    /// Adds the stream entry of `block_height`, returns `false` without adding it when the
+    /// stream already has an entry for this block or a later one
+    async fn xadd(
+        &self,
+        stream_key: &str,
+        block_height: u64,
+        fields: &[(&str, String)],
+    ) -> anyhow::Result<bool>;
+
+    async fn xlen(&self, stream_key: &str) -> anyhow::Result<u64>;
+
+    /// Keys matching the glob-style `pattern`
+    async fn scan(&self, pattern: &str) -> anyhow::Result<Vec<String>>;
+
+    /// Renames `key` along with its expiration, returns `false` without renaming it when
+    /// `key` does not exist or `new_key` does
+    async fn rename(&self, key: &str, new_key: &str) -> anyhow::Result<bool>;
+
+    /// Removes the oldest entries of the stream, returns how many were removed
+    async fn xtrim(&self, stream_key: &str, trim: StreamTrim) -> anyhow::Result<u64>;
+
+    /// Bytes taken by the key and its value, `None` if the key does not exist
+    async fn memory_usage(&self, key: &str) -> anyhow::Result<Option<u64>>;
+
+    /// Bytes taken by all keys
+    async fn used_memory(&self) -> anyhow::Result<u64>;
+
+    /// Creates the stream with its runner consumer group unless the group exists
+    async fn create_consumer_group(&self, stream_key: &str) -> anyhow::Result<()> {
+        let mut batch = WriteBatch::new();
+        batch.create_consumer_group(stream_key);
+        self.commit(batch).await
+    }
+
+    /// Progress of the runner consumer group, `None` if the stream or its group do not exist
+    async fn consumer_group_progress(
+        &self,
+        stream_key: &str,
+    ) -> anyhow::Result<Option<ConsumerGroupProgress>>;
+
+    /// Applies the writes of the batch in order in a single round trip, without other clients
+    /// observing part of them. The first failing write stops the batch, the writes before it
+    /// stay applied, so a failed batch is recovered by committing it again: its writes must be
+    /// idempotent up to the checkpoint, which goes last.
+    async fn commit(&self, batch: WriteBatch) -> anyhow::Result<()>;
+
+    async fn get_last_indexed_block(&self) -> anyhow::Result<u64> {
+        match self.get("last_indexed_block").await? {
+            Some(block_height) => Ok(block_height.parse()?),
+            None => anyhow::bail!("last_indexed_block is not set"),
+        }
+    }
+}
+
+pub struct RedisStorage {
+    redis_connection: RedisConnection,
+    commit_script: redis::Script,
+}
+
+impl RedisStorage {
+    pub async fn connect(redis_topology: &RedisTopology) -> anyhow::Result<Self> {
+        Ok(Self {
+            redis_connection: connect(redis_topology).await?,
+            commit_script: redis::Script::new(COMMIT_SCRIPT),
+        })
+    }
+
+    pub fn connection(&self) -> &RedisConnection {
+        &self.redis_connection
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for RedisStorage {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        get(&self.redis_connection, key).await
+    }
+
+    async fn mget(&self, keys: &[String]) -> anyhow::Result<Vec<Option<String>>> {
+        if keys.is_empty() {
+            return Ok(vec![]);
+        }
+        let values = redis::cmd("MGET")
+            .arg(keys)
+            .query_async(&mut self.redis_connection.clone())
+            .await?;
+        tracing::debug!(target: STORAGE, "MGET: {} keys", keys.len());
+        Ok(values)
+    }
+
+    async fn set(
+        &self,
+        key: &str,
+        value: &str,
+        expiration_seconds: Option<usize>,
+    ) -> anyhow::Result<()> {
+        set(&self.redis_connection, key, value, expiration_seconds).await
+    }
+
+    async fn del(&self, key: &str) -> anyhow::Result<()> {
+        del(&self.redis_connection, key).await
+    }
+
+    async fn incr(&self, key: &str, delta: i64) -> anyhow::Result<i64> {
+        let value = redis::cmd("INCRBY")
+            .arg(key)
+            .arg(delta)
+            .query_async(&mut self.redis_connection.clone())
+            .await?;
+        tracing::debug!(target: STORAGE, "INCRBY: {:?}: {:?}", key, delta);
+        Ok(value)
+    }
+
+    async fn sadd(&self, key: &str, member: &str) -> anyhow::Result<()> {
+        sadd(&self.redis_connection, key, member).await
+    }
+
+    async fn smembers(&self, key: &str) -> anyhow::Result<Vec<String>> {
+        let members = redis::cmd("SMEMBERS")
+            .arg(key)
+            .query_async(&mut self.redis_connection.clone())
+            .await?;
+        tracing::debug!(target: STORAGE, "SMEMBERS: {:?}: {:?}", key, members);
+        Ok(members)
+    }
+
+    async fn xadd(
+        &self,
+        stream_key: &str,
+        block_height: u64,
+        fields: &[(&str, String)],
+    ) -> anyhow::Result<bool> {
+        xadd(&self.redis_connection, stream_key, block_height, fields).await
+    }
+
+    async fn xlen(&self, stream_key: &str) -> anyhow::Result<u64> {
+        Ok(redis::cmd("XLEN")
+            .arg(stream_key)
+            .query_async(&mut self.redis_connection.clone())
+            .await?)
+    }
+
+    async fn scan(&self, pattern: &str) -> anyhow::Result<Vec<String>> {
+        // SCAN cursors belong to one node, KEYS is sent to every master of a cluster
+        if self.redis_connection.is_cluster() {
+            let keys: Vec<String> = redis::cmd("KEYS")
+                .arg(pattern)
+                .query_async(&mut self.redis_connection.clone())
+                .await?;
+            tracing::debug!(target: STORAGE, "KEYS: {:?}, {} keys", pattern, keys.len());
+            return Ok(keys);
+        }
+
+        let mut keys = vec![];
+        let mut cursor: u64 = 0;
+        loop {
+            let (next_cursor, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(pattern)
+                .arg("COUNT")
+                .arg(1000)
+                .query_async(&mut self.redis_connection.clone())
+                .await?;
+            keys.extend(batch);
+            if next_cursor == 0 {
+                break;
+            }
+            cursor = next_cursor;
+        }
+        tracing::debug!(target: STORAGE, "SCAN: {:?}, {} keys", pattern, keys.len());
+        Ok(keys)
+    }
+
+    async fn rename(&self, key: &str, new_key: &str) -> anyhow::Result<bool> {
+        tracing::debug!(target: STORAGE, "RENAMENX: {:?}: {:?}", key, new_key);
+        match redis::cmd("RENAMENX")
+            .arg(key)
+            .arg(new_key)
+            .query_async::<_, bool>(&mut self.redis_connection.clone())
+            .await
+        {
+            Ok(renamed) => Ok(renamed),
+            Err(error) if error.to_string().contains("no such key") => Ok(false),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    async fn xtrim(&self, stream_key: &str, trim: StreamTrim) -> anyhow::Result<u64> {
+        let mut cmd = redis::cmd("XTRIM");
+        cmd.arg(stream_key);
+        match trim {
+            StreamTrim::MaxLength(max_length) => cmd.arg("MAXLEN").arg(max_length),
+            StreamTrim::MinBlockHeight(block_height) => {
+                cmd.arg("MINID").arg(generate_stream_entry_id(block_height))
+            }
+        };
+        let trimmed_entries = cmd.query_async(&mut self.redis_connection.clone()).await?;
+        tracing::debug!(
+            target: STORAGE,
+            "XTRIM: {:?}: {:?}, {} trimmed",
+            stream_key,
+            trim,
+            trimmed_entries
+        );
+        Ok(trimmed_entries)
+    }
+
+    async fn memory_usage(&self, key: &str) -> anyhow::Result<Option<u64>> {
+        Ok(redis::cmd("MEMORY")
+            .arg("USAGE")
+            .arg(key)
+            .query_async(&mut self.redis_connection.clone())
+            .await?)
+    }
+
+    async fn used_memory(&self) -> anyhow::Result<u64> {
+        let mut info = redis::cmd("INFO");
+        info.arg("memory");
+        // a cluster answers with the info of each master, by address
+        let infos: Vec<String> = if self.redis_connection.is_cluster() {
+            info.query_async::<_, Vec<(String, String)>>(&mut self.redis_connection.clone())
+                .await?
+                .into_iter()
+                .map(|(_, info)| info)
+                .collect()
+        } else {
+            vec![info.query_async(&mut self.redis_connection.clone()).await?]
+        };
+        infos.iter().try_fold(0, |used_memory, info| {
+            let node_used_memory: u64 = info
+                .lines()
+                .find_map(|line| line.strip_prefix("used_memory:"))
+                .ok_or_else(|| anyhow::anyhow!("INFO memory has no used_memory"))?
+                .trim()
+                .parse()
+                .map_err(|err| anyhow::anyhow!("Invalid used_memory: {}", err))?;
+            Ok(used_memory + node_used_memory)
+        })
+    }
+
+    async fn consumer_group_progress(
+        &self,
+        stream_key: &str,
+    ) -> anyhow::Result<Option<ConsumerGroupProgress>> {
+        let mut connection = self.redis_connection.clone();
+
+        let stream_exists: bool = redis::cmd("EXISTS")
+            .arg(stream_key)
+            .query_async(&mut connection)
+            .await?;
+        if !stream_exists {
+            return Ok(None);
+        }
+
+        let groups: redis::streams::StreamInfoGroupsReply = redis::cmd("XINFO")
+            .arg("GROUPS")
+            .arg(stream_key)
+            .query_async(&mut connection)
+            .await?;
+        let group = match groups
+            .groups
+            .into_iter()
+            .find(|group| group.name == RUNNER_CONSUMER_GROUP)
+        {
+            Some(group) => group,
+            None => return Ok(None),
+        };
+
+        let pending: redis::streams::StreamPendingReply = redis::cmd("XPENDING")
+            .arg(stream_key)
+            .arg(RUNNER_CONSUMER_GROUP)
+            .query_async(&mut connection)
+            .await?;
+        let oldest_pending_block_height = match &pending {
+            redis::streams::StreamPendingReply::Data(pending) => {
+                parse_stream_entry_id(&pending.start_id)
+            }
+            redis::streams::StreamPendingReply::Empty => None,
+        };
+
+        let next_undelivered: redis::streams::StreamRangeReply = redis::cmd("XRANGE")
+            .arg(stream_key)
+            .arg(format!("({}", group.last_delivered_id))
+            .arg("+")
+            .arg("COUNT")
+            .arg(1)
+            .query_async(&mut connection)
+            .await?;
+
+        let progress = ConsumerGroupProgress {
+            last_delivered_block_height: parse_stream_entry_id(&group.last_delivered_id)
+                .filter(|block_height| *block_height > 0),
+            pending_entries: pending.count() as u64,
+            oldest_pending_block_height,
+            next_undelivered_block_height: next_undelivered
+                .ids
+                .first()
+                .and_then(|entry| parse_stream_entry_id(&entry.id)),
+        };
+        tracing::debug!(
+            target: STORAGE,
+            "CONSUMER GROUP PROGRESS: {:?}: {:?}",
+            stream_key,
+            progress
+        );
+        Ok(Some(progress))
+    }
+
+    /// In a cluster a script only writes keys of one hash slot, so the batch is committed by
+    /// one script per slot, see [WriteBatch::split_by_slot]. The checkpoint goes in the last
+    /// one, a failure in between leaves the block to be committed again. The stream entries of
+    /// a block can then be read before its streamer message is cached, the runner reads the
+    /// block from S3 in that case.
+    async fn commit(&self, batch: WriteBatch) -> anyhow::Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        if self.redis_connection.is_cluster() {
+            for slot_batch in batch.split_by_slot() {
+                commit(&self.redis_connection, &self.commit_script, &slot_batch).await?;
+            }
+        } else {
+            commit(&self.redis_connection, &self.commit_script, &batch).await?;
+        }
+        tracing::debug!(target: STORAGE, "COMMIT: {:?}", batch);
+        Ok(())
+    }
+}
+
+async fn commit(
+    redis_connection: &RedisConnection,
+    script: &redis::Script,
+    batch: &WriteBatch,
+) -> anyhow::Result<()> {
+    let mut invocation = script.prepare_invoke();
+    for command in batch.commands() {
+        let (name, args) = command.redis_args();
+        invocation
+            .key(command.key())
+            .arg(name)
+            .arg(command.ignored_error().unwrap_or_default())
+            .arg(args.len())
+            .arg(args);
+    }
+    invocation
+        .invoke_async::<_, usize>(&mut redis_connection.clone())
+        .await?;
+    Ok(())
 }
 
 pub async fn del(
-    redis_connection_manager: &ConnectionManager,
+    redis_connection: &RedisConnection,
     key: impl ToRedisArgs + std::fmt::Debug,
 ) -> anyhow::Result<()> {
     redis::cmd("DEL")
         .arg(&key)
-        .query_async(&mut redis_connection_manager.clone())
+        .query_async::<_, ()>(&mut redis_connection.clone())
         .await?;
     tracing::debug!(target: STORAGE, "DEL: {:?}", key);
     Ok(())
 }
 
 pub async fn set(
-    redis_connection_manager: &ConnectionManager,
+    redis_connection: &RedisConnection,
     key: impl ToRedisArgs + std::fmt::Debug,
     value: impl ToRedisArgs + std::fmt::Debug,
     expiration_seconds: Option<usize>,
@@ -62,26 +526,26 @@ pub async fn set(
         cmd.arg("EX").arg(expiration_seconds);
     }
 
-    cmd.query_async(&mut redis_connection_manager.clone())
+    cmd.query_async::<_, ()>(&mut redis_connection.clone())
         .await?;
     tracing::debug!(target: STORAGE, "SET: {:?}: {:?} Ex: {:?}", key, value, expiration_seconds);
     Ok(())
 }
 
 pub async fn get<V: FromRedisValue + std::fmt::Debug>(
-    redis_connection_manager: &ConnectionManager,
+    redis_connection: &RedisConnection,
     key: impl ToRedisArgs + std::fmt::Debug,
 ) -> anyhow::Result<V> {
     let value: V = redis::cmd("GET")
         .arg(&key)
-        .query_async(&mut redis_connection_manager.clone())
+        .query_async(&mut redis_connection.clone())
         .await?;
     tracing::debug!(target: STORAGE, "GET: {:?}: {:?}", &key, &value,);
     Ok(value)
 }
 
 pub async fn sadd(
-    redis_connection_manager: &ConnectionManager,
+    redis_connection: &RedisConnection,
     key: impl ToRedisArgs + std::fmt::Debug,
     value: impl ToRedisArgs + std::fmt::Debug,
 ) -> anyhow::Result<()> {
@@ -90,107 +554,103 @@ pub async fn sadd(
     redis::cmd("SADD")
         .arg(key)
         .arg(value)
-        .query_async(&mut redis_connection_manager.clone())
+        .query_async::<_, ()>(&mut redis_connection.clone())
         .await?;
 
     Ok(())
 }
 
 pub async fn xadd(
-    redis_connection_manager: &ConnectionManager,
+    redis_connection: &RedisConnection,
     stream_key: impl ToRedisArgs + std::fmt::Debug,
+    block_height: u64,
     fields: &[(&str, impl ToRedisArgs + std::fmt::Debug)],
-) -> anyhow::Result<()> {
-    tracing::debug!(target: STORAGE, "XADD: {:?}, {:?}", stream_key, fields);
+) -> anyhow::Result<bool> {
+    tracing::debug!(
+        target: STORAGE,
+        "XADD: {:?}, {}, {:?}",
+        stream_key,
+        block_height,
+        fields
+    );
 
     let mut cmd = redis::cmd("XADD");
-    cmd.arg(stream_key).arg("*");
+    cmd.arg(stream_key)
+        .arg(generate_stream_entry_id(block_height));
 
     for (field, value) in fields {
         cmd.arg(*field).arg(value);
     }
 
-    cmd.query_async(&mut redis_connection_manager.clone())
-        .await?;
-
-    Ok(())
+    match cmd
+        .query_async::<_, String>(&mut redis_connection.clone())
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(error) if is_stale_stream_entry(&error) => {
+            tracing::debug!(
+                target: STORAGE,
+                "XADD: block {} is already streamed",
+                block_height
+            );
+            Ok(false)
+        }
+        Err(error) => Err(error.into()),
+    }
 }
 
-/// Sets the key `receipt_id: &str` with value `transaction_hash: &str` to the Redis storage.
-/// Increments the counter `receipts_{transaction_hash}` by one.
-/// The counter holds how many Receipts related to the Transaction are in watching list
-pub async fn push_receipt_to_watching_list(
-    redis_connection_manager: &ConnectionManager,
+/// Records what created `receipt_id`, a transaction or another receipt, until the receipt
+/// is executed in a later block. The record expires instead of being removed once read, so a
+/// replayed block resolves the same way.
+pub fn push_receipt_to_watching_list(
+    batch: &mut WriteBatch,
     receipt_id: &str,
-    cache_value: &[u8],
-) -> anyhow::Result<()> {
-    set(redis_connection_manager, receipt_id, cache_value, None).await?;
-    // redis::cmd("INCR")
-    //     .arg(format!("receipts_{}", transaction_hash))
-    //     .query_async(&mut redis_connection_manager.clone())
-    //     .await?;
-    Ok(())
+    origin: &str,
+    expiration_seconds: usize,
+) {
+    batch.set(
+        &generate_receipt_key(receipt_id),
+        origin,
+        Some(expiration_seconds),
+    );
+}
+
+/// Origins recorded by [push_receipt_to_watching_list] of each of `receipt_ids`
+pub async fn get_receipts_from_watching_list(
+    storage: &dyn Storage,
+    receipt_ids: &[String],
+) -> anyhow::Result<Vec<Option<String>>> {
+    let keys: Vec<String> = receipt_ids
+        .iter()
+        .map(|receipt_id| generate_receipt_key(receipt_id))
+        .collect();
+    storage.mget(&keys).await
 }
 
-/// Removes key `receipt_id: &str` from Redis storage.
-/// If the key exists in the storage decreases the `receipts_{transaction_hash}` counter.
-// pub async fn remove_receipt_from_watching_list(
-//     redis_connection_manager: &ConnectionManager,
-//     receipt_id: &str,
-// ) -> anyhow::Result<Option<String>> {
-//     match get::<Option<String>>(redis_connection_manager, receipt_id).await {
-//         Ok(maybe_transaction_hash) => {
-//             if let Some(ref transaction_hash) = maybe_transaction_hash {
-//                 redis::cmd("DECR")
-//                     .arg(format!("receipts_{}", transaction_hash))
-//                     .query_async(&mut redis_connection_manager.clone())
-//                     .await?;
-//                 tracing::debug!(target: STORAGE, "DECR: receipts_{}", transaction_hash);
-//                 del(redis_connection_manager, receipt_id).await?;
-//             }
-//             Ok(maybe_transaction_hash)
-//         }
-//         Err(e) => {
-//             anyhow::bail!(e)
-//         }
-//     }
-// }
-
-/// Returns the value of the `receipts_{transaction_hash}` counter
-pub async fn receipts_transaction_hash_count(
-    redis_connection_manager: &ConnectionManager,
+/// Stores the serialized state of a Transaction whose receipts are still executing.
+/// Transactions which never complete, e.g. tracked since the middle of their execution, expire.
+pub fn set_pending_transaction(
+    batch: &mut WriteBatch,
     transaction_hash: &str,
-) -> anyhow::Result<u64> {
-    get::<u64>(
-        redis_connection_manager,
-        format!("receipts_{}", transaction_hash),
-    )
-    .await
+    pending_transaction: &str,
+    expiration_seconds: usize,
+) {
+    batch.set(
+        &generate_pending_transaction_key(transaction_hash),
+        pending_transaction,
+        Some(expiration_seconds),
+    );
 }
 
-pub async fn update_last_indexed_block(
-    redis_connection_manager: &ConnectionManager,
-    block_height: u64,
-) -> anyhow::Result<()> {
-    set(
-        redis_connection_manager,
-        "last_indexed_block",
-        block_height,
-        None,
-    )
-    .await?;
-    redis::cmd("INCR")
-        .arg("blocks_processed")
-        .query_async(&mut redis_connection_manager.clone())
-        .await?;
-    Ok(())
+pub async fn get_pending_transaction(
+    storage: &dyn Storage,
+    transaction_hash: &str,
+) -> anyhow::Result<Option<String>> {
+    storage
+        .get(&generate_pending_transaction_key(transaction_hash))
+        .await
 }
 
-pub async fn get_last_indexed_block(
-    redis_connection_manager: &ConnectionManager,
-) -> anyhow::Result<u64> {
-    Ok(redis::cmd("GET")
-        .arg("last_indexed_block")
-        .query_async(&mut redis_connection_manager.clone())
-        .await?)
+pub fn remove_pending_transaction(batch: &mut WriteBatch, transaction_hash: &str) {
+    batch.del(&generate_pending_transaction_key(transaction_hash));
 }
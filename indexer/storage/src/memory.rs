@@ -0,0 +1,551 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{
+    parse_stream_entry_id, ConsumerGroupProgress, Storage, StreamTrim, WriteBatch, WriteCommand,
+};
+
+/// Stream entry as added by `xadd`, its id and field/value pairs
+pub type StreamEntry = (String, Vec<(String, String)>);
+
+#[derive(Default)]
+struct State {
+    values: HashMap<String, (Vec<u8>, Option<Instant>)>,
+    sets: HashMap<String, BTreeSet<String>>,
+    streams: HashMap<String, Vec<StreamEntry>>,
+    // like Redis, the last block of a stream is kept even once its entries are deleted
+    last_stream_block_heights: HashMap<String, u64>,
+    consumer_groups: HashMap<String, ConsumerGroup>,
+}
+
+/// Runner consumer group of a stream
+#[derive(Default)]
+struct ConsumerGroup {
+    last_delivered_block_height: u64,
+    pending_block_heights: BTreeSet<u64>,
+}
+
+impl State {
+    fn value(&mut self, key: &str) -> Option<&Vec<u8>> {
+        if let Some((_, Some(expires_at))) = self.values.get(key) {
+            if *expires_at <= Instant::now() {
+                self.values.remove(key);
+            }
+        }
+        self.values.get(key).map(|(value, _)| value)
+    }
+
+    /// Like Redis, the value is made of any bytes while [Storage::get] reads it as UTF-8
+    fn set(&mut self, key: &str, value: &[u8], expiration_seconds: Option<usize>) {
+        let expires_at = expiration_seconds.map(|expiration_seconds| {
+            Instant::now() + Duration::from_secs(expiration_seconds as u64)
+        });
+        self.values
+            .insert(key.to_string(), (value.to_vec(), expires_at));
+    }
+
+    fn string_value(&mut self, key: &str) -> anyhow::Result<Option<String>> {
+        Ok(match self.value(key) {
+            Some(value) => Some(String::from_utf8(value.clone())?),
+            None => None,
+        })
+    }
+
+    fn del(&mut self, key: &str) {
+        self.values.remove(key);
+        self.sets.remove(key);
+        self.streams.remove(key);
+        self.last_stream_block_heights.remove(key);
+        self.consumer_groups.remove(key);
+    }
+
+    fn incr(&mut self, key: &str, delta: i64) -> anyhow::Result<i64> {
+        let value = match self.value(key) {
+            Some(value) => std::str::from_utf8(value)
+                .ok()
+                .and_then(|value| value.parse::<i64>().ok())
+                .ok_or_else(|| anyhow::anyhow!("value of {} is not an integer", key))?,
+            None => 0,
+        } + delta;
+        let expires_at = self.values.get(key).and_then(|(_, expires_at)| *expires_at);
+        self.values.insert(
+            key.to_string(),
+            (value.to_string().into_bytes(), expires_at),
+        );
+        Ok(value)
+    }
+
+    fn sadd(&mut self, key: &str, member: &str) {
+        self.sets
+            .entry(key.to_string())
+            .or_default()
+            .insert(member.to_string());
+    }
+
+    fn xadd(&mut self, stream_key: &str, block_height: u64, fields: Vec<(String, String)>) -> bool {
+        match self.last_stream_block_heights.get(stream_key) {
+            Some(last_block_height) if *last_block_height >= block_height => return false,
+            _ => {}
+        }
+        self.last_stream_block_heights
+            .insert(stream_key.to_string(), block_height);
+        self.streams
+            .entry(stream_key.to_string())
+            .or_default()
+            .push((crate::generate_stream_entry_id(block_height), fields));
+        true
+    }
+
+    fn xtrim(&mut self, stream_key: &str, trim: StreamTrim) -> u64 {
+        let entries = match self.streams.get_mut(stream_key) {
+            Some(entries) => entries,
+            None => return 0,
+        };
+        let trimmed_entries = match trim {
+            StreamTrim::MaxLength(max_length) => entries.len().saturating_sub(max_length),
+            StreamTrim::MinBlockHeight(block_height) => entries
+                .iter()
+                .take_while(|(id, _)| parse_stream_entry_id(id).unwrap_or_default() < block_height)
+                .count(),
+        };
+        entries.drain(..trimmed_entries);
+        trimmed_entries as u64
+    }
+
+    /// Rough size of the key and its value, which unlike Redis leaves out any overhead
+    fn memory_usage(&mut self, key: &str) -> Option<u64> {
+        let value_bytes = if let Some(value) = self.value(key) {
+            value.len()
+        } else if let Some(members) = self.sets.get(key) {
+            members.iter().map(String::len).sum()
+        } else if let Some(entries) = self.streams.get(key) {
+            entries
+                .iter()
+                .map(|(id, fields)| {
+                    id.len()
+                        + fields
+                            .iter()
+                            .map(|(field, value)| field.len() + value.len())
+                            .sum::<usize>()
+                })
+                .sum()
+        } else {
+            return None;
+        };
+        Some((key.len() + value_bytes) as u64)
+    }
+
+    fn contains_key(&mut self, key: &str) -> bool {
+        self.value(key).is_some() || self.sets.contains_key(key) || self.streams.contains_key(key)
+    }
+
+    fn keys(&mut self) -> Vec<String> {
+        let keys: Vec<String> = self
+            .values
+            .keys()
+            .chain(self.sets.keys())
+            .chain(self.streams.keys())
+            .cloned()
+            .collect();
+        keys.into_iter()
+            .filter(|key| self.contains_key(key))
+            .collect()
+    }
+
+    fn rename(&mut self, key: &str, new_key: &str) -> bool {
+        if !self.contains_key(key) || self.contains_key(new_key) {
+            return false;
+        }
+        if let Some(value) = self.values.remove(key) {
+            self.values.insert(new_key.to_string(), value);
+        }
+        if let Some(members) = self.sets.remove(key) {
+            self.sets.insert(new_key.to_string(), members);
+        }
+        if let Some(entries) = self.streams.remove(key) {
+            self.streams.insert(new_key.to_string(), entries);
+        }
+        if let Some(block_height) = self.last_stream_block_heights.remove(key) {
+            self.last_stream_block_heights
+                .insert(new_key.to_string(), block_height);
+        }
+        if let Some(consumer_group) = self.consumer_groups.remove(key) {
+            self.consumer_groups
+                .insert(new_key.to_string(), consumer_group);
+        }
+        true
+    }
+
+    fn consumer_group_progress(&self, stream_key: &str) -> Option<ConsumerGroupProgress> {
+        let consumer_group = self.consumer_groups.get(stream_key)?;
+        Some(ConsumerGroupProgress {
+            last_delivered_block_height: Some(consumer_group.last_delivered_block_height)
+                .filter(|block_height| *block_height > 0),
+            pending_entries: consumer_group.pending_block_heights.len() as u64,
+            oldest_pending_block_height: consumer_group.pending_block_heights.first().copied(),
+            next_undelivered_block_height: self
+                .streams
+                .get(stream_key)?
+                .iter()
+                .filter_map(|(id, _)| parse_stream_entry_id(id))
+                .find(|block_height| *block_height > consumer_group.last_delivered_block_height),
+        })
+    }
+
+    fn apply(&mut self, command: &WriteCommand) -> anyhow::Result<()> {
+        match command {
+            WriteCommand::Set {
+                key,
+                value,
+                expiration_seconds,
+            } => self.set(key, value, *expiration_seconds),
+            WriteCommand::Del { key } => self.del(key),
+            WriteCommand::IncrBy { key, delta } => {
+                self.incr(key, *delta)?;
+            }
+            WriteCommand::SAdd { key, member } => self.sadd(key, member),
+            WriteCommand::XAdd {
+                stream_key,
+                block_height,
+                fields,
+            } => {
+                self.xadd(stream_key, *block_height, fields.clone());
+            }
+            WriteCommand::CreateConsumerGroup { stream_key } => {
+                self.streams.entry(stream_key.clone()).or_default();
+                self.consumer_groups.entry(stream_key.clone()).or_default();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Glob-style match supporting `*` only, the one wildcard the coordinator scans with
+fn matches_pattern(pattern: &str, key: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first_part = parts.next().unwrap_or_default();
+    let mut rest = match key.strip_prefix(first_part) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts: Vec<&str> = parts.collect();
+    match parts.split_last() {
+        None => rest.is_empty(),
+        Some((last_part, middle_parts)) => {
+            for part in middle_parts {
+                match rest.find(part) {
+                    Some(index) => rest = &rest[index + part.len()..],
+                    None => return false,
+                }
+            }
+            rest.ends_with(last_part)
+        }
+    }
+}
+
+/// [Storage] kept in process memory, with the key, set and stream semantics of Redis
+/// the coordinator relies on
+#[derive(Default)]
+pub struct InMemoryStorage {
+    state: Mutex<State>,
+}
+
+impl InMemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_members(&self, key: &str) -> Vec<String> {
+        self.state
+            .lock()
+            .unwrap()
+            .sets
+            .get(key)
+            .map(|members| members.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn stream_entries(&self, stream_key: &str) -> Vec<StreamEntry> {
+        self.state
+            .lock()
+            .unwrap()
+            .streams
+            .get(stream_key)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Reads the next `count` entries through the runner consumer group, as the runner does
+    pub fn read_group(&self, stream_key: &str, count: usize) -> Vec<StreamEntry> {
+        let mut state = self.state.lock().unwrap();
+        let entries = state.streams.get(stream_key).cloned().unwrap_or_default();
+        let consumer_group = match state.consumer_groups.get_mut(stream_key) {
+            Some(consumer_group) => consumer_group,
+            None => return vec![],
+        };
+        let delivered_entries: Vec<StreamEntry> = entries
+            .into_iter()
+            .filter(|(id, _)| {
+                parse_stream_entry_id(id).unwrap_or_default()
+                    > consumer_group.last_delivered_block_height
+            })
+            .take(count)
+            .collect();
+        for (id, _) in delivered_entries.iter() {
+            let block_height = parse_stream_entry_id(id).unwrap_or_default();
+            consumer_group.last_delivered_block_height = block_height;
+            consumer_group.pending_block_heights.insert(block_height);
+        }
+        delivered_entries
+    }
+
+    /// Acknowledges the entry of `block_height` in the runner consumer group, as the runner
+    /// does once it processed the block
+    pub fn acknowledge(&self, stream_key: &str, block_height: u64) {
+        if let Some(consumer_group) = self
+            .state
+            .lock()
+            .unwrap()
+            .consumer_groups
+            .get_mut(stream_key)
+        {
+            consumer_group.pending_block_heights.remove(&block_height);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for InMemoryStorage {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        self.state.lock().unwrap().string_value(key)
+    }
+
+    async fn mget(&self, keys: &[String]) -> anyhow::Result<Vec<Option<String>>> {
+        let mut state = self.state.lock().unwrap();
+        keys.iter().map(|key| state.string_value(key)).collect()
+    }
+
+    async fn set(
+        &self,
+        key: &str,
+        value: &str,
+        expiration_seconds: Option<usize>,
+    ) -> anyhow::Result<()> {
+        self.state
+            .lock()
+            .unwrap()
+            .set(key, value.as_bytes(), expiration_seconds);
+        Ok(())
+    }
+
+    async fn del(&self, key: &str) -> anyhow::Result<()> {
+        self.state.lock().unwrap().del(key);
+        Ok(())
+    }
+
+    async fn incr(&self, key: &str, delta: i64) -> anyhow::Result<i64> {
+        self.state.lock().unwrap().incr(key, delta)
+    }
+
+    async fn sadd(&self, key: &str, member: &str) -> anyhow::Result<()> {
+        self.state.lock().unwrap().sadd(key, member);
+        Ok(())
+    }
+
+    async fn smembers(&self, key: &str) -> anyhow::Result<Vec<String>> {
+        Ok(self.set_members(key))
+    }
+
+    async fn xadd(
+        &self,
+        stream_key: &str,
+        block_height: u64,
+        fields: &[(&str, String)],
+    ) -> anyhow::Result<bool> {
+        Ok(self.state.lock().unwrap().xadd(
+            stream_key,
+            block_height,
+            fields
+                .iter()
+                .map(|(field, value)| (field.to_string(), value.clone()))
+                .collect(),
+        ))
+    }
+
+    async fn xlen(&self, stream_key: &str) -> anyhow::Result<u64> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .streams
+            .get(stream_key)
+            .map_or(0, |entries| entries.len() as u64))
+    }
+
+    async fn scan(&self, pattern: &str) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .keys()
+            .into_iter()
+            .filter(|key| matches_pattern(pattern, key))
+            .collect())
+    }
+
+    async fn rename(&self, key: &str, new_key: &str) -> anyhow::Result<bool> {
+        Ok(self.state.lock().unwrap().rename(key, new_key))
+    }
+
+    async fn xtrim(&self, stream_key: &str, trim: StreamTrim) -> anyhow::Result<u64> {
+        Ok(self.state.lock().unwrap().xtrim(stream_key, trim))
+    }
+
+    async fn memory_usage(&self, key: &str) -> anyhow::Result<Option<u64>> {
+        Ok(self.state.lock().unwrap().memory_usage(key))
+    }
+
+    async fn used_memory(&self) -> anyhow::Result<u64> {
+        let mut state = self.state.lock().unwrap();
+        let keys = state.keys();
+        Ok(keys.iter().filter_map(|key| state.memory_usage(key)).sum())
+    }
+
+    async fn consumer_group_progress(
+        &self,
+        stream_key: &str,
+    ) -> anyhow::Result<Option<ConsumerGroupProgress>> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .consumer_group_progress(stream_key))
+    }
+
+    async fn commit(&self, batch: WriteBatch) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        for command in batch.commands() {
+            state.apply(command)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InMemoryStorage;
+    use crate::{
+        get_receipts_from_watching_list, push_receipt_to_watching_list, Storage, WriteBatch,
+    };
+
+    #[tokio::test]
+    async fn expire_values() {
+        let storage = InMemoryStorage::new();
+        storage.set("key", "value", Some(0)).await.unwrap();
+        storage.set("other_key", "value", None).await.unwrap();
+
+        assert_eq!(storage.get("key").await.unwrap(), None);
+        assert_eq!(
+            storage.get("other_key").await.unwrap(),
+            Some("value".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn track_receipts_in_watching_list() {
+        let storage = InMemoryStorage::new();
+        let mut batch = WriteBatch::new();
+        push_receipt_to_watching_list(&mut batch, "receipt", "transaction:hash", 60);
+        push_receipt_to_watching_list(&mut batch, "expired_receipt", "transaction:hash", 0);
+        storage.commit(batch).await.unwrap();
+
+        let receipt_ids = ["receipt", "expired_receipt", "unknown_receipt"].map(String::from);
+        // reading the watching list leaves it unchanged, e.g. for a replayed block
+        for _ in 0..2 {
+            assert_eq!(
+                get_receipts_from_watching_list(&storage, &receipt_ids)
+                    .await
+                    .unwrap(),
+                vec![Some("transaction:hash".to_string()), None, None]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn count_indexed_blocks() {
+        let storage = InMemoryStorage::new();
+        assert!(storage.get_last_indexed_block().await.is_err());
+
+        for block_height in [10, 11] {
+            let mut batch = WriteBatch::new();
+            batch.update_last_indexed_block(block_height);
+            storage.commit(batch).await.unwrap();
+        }
+
+        assert_eq!(storage.get_last_indexed_block().await.unwrap(), 11);
+        assert_eq!(
+            storage.get("blocks_processed").await.unwrap(),
+            Some("2".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn stop_commit_at_first_failing_write() {
+        let storage = InMemoryStorage::new();
+        storage.set("not_a_number", "value", None).await.unwrap();
+
+        let mut batch = WriteBatch::new();
+        batch
+            .set("streamer:message:1", "{}", None)
+            .sadd("streams", "a:stream")
+            .xadd("a:stream", 1, &[("block_height", "1".to_string())])
+            .incr("not_a_number", 1)
+            .sadd("streams", "b:stream")
+            .xadd("b:stream", 1, &[("block_height", "1".to_string())])
+            .update_last_indexed_block(1);
+        assert!(storage.commit(batch.clone()).await.is_err());
+        assert_eq!(storage.stream_entries("a:stream").len(), 1);
+        assert!(storage.stream_entries("b:stream").is_empty());
+        assert!(storage.get_last_indexed_block().await.is_err());
+
+        // once the cause of the failure is gone the same block is committed again, the writes
+        // applied before the failure replay without effect
+        storage.set("not_a_number", "0", None).await.unwrap();
+        storage.commit(batch).await.unwrap();
+        assert_eq!(storage.stream_entries("a:stream").len(), 1);
+        assert_eq!(storage.stream_entries("b:stream").len(), 1);
+        assert_eq!(
+            storage.set_members("streams"),
+            vec!["a:stream".to_string(), "b:stream".to_string()]
+        );
+        assert_eq!(storage.get_last_indexed_block().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn reject_replayed_blocks() {
+        let storage = InMemoryStorage::new();
+        for block_height in [10, 11, 10, 11] {
+            let mut batch = WriteBatch::new();
+            batch
+                .xadd(
+                    "stream",
+                    block_height,
+                    &[("block_height", block_height.to_string())],
+                )
+                .update_last_indexed_block(block_height);
+            storage.commit(batch).await.unwrap();
+        }
+
+        let ids: Vec<String> = storage
+            .stream_entries("stream")
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, vec!["10-0".to_string(), "11-0".to_string()]);
+        assert!(!storage
+            .xadd("stream", 9, &[("block_height", "9".to_string())])
+            .await
+            .unwrap());
+    }
+}
@@ -0,0 +1,423 @@
+use crate::{
+    generate_processed_block_height_key, indexer_function_name_len, ConsumerGroupProgress, Storage,
+    StreamTrim, WriteBatch, STREAMS_SET_KEY,
+};
+
+/// Prefix of every key written by a coordinator, so coordinators of different chains or
+/// deployments can share one Redis
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyNamespace {
+    name: String,
+    hash_tagged: bool,
+}
+
+impl KeyNamespace {
+    /// Keys prefixed with `{deployment}:{chain_id}:`, or `{chain_id}:` without a deployment
+    pub fn new(chain_id: &str, deployment: Option<&str>) -> Self {
+        Self {
+            name: Self::join(chain_id, deployment),
+            hash_tagged: false,
+        }
+    }
+
+    /// Same namespace with hash tags spreading the keys over the slots of a Redis Cluster by
+    /// indexer function. The keys of the streams of a function share one slot, e.g.
+    /// `{mainnet:test.near/indexer}:real_time:stream`, while the other keys share the slot of
+    /// the chain, e.g. `{mainnet}:last_indexed_block`.
+    pub fn hash_tagged(chain_id: &str, deployment: Option<&str>) -> Self {
+        Self {
+            name: Self::join(chain_id, deployment),
+            hash_tagged: true,
+        }
+    }
+
+    fn join(chain_id: &str, deployment: Option<&str>) -> String {
+        match deployment {
+            Some(deployment) => format!("{}:{}", deployment, chain_id),
+            None => chain_id.to_string(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn key(&self, key: &str) -> String {
+        if !self.hash_tagged {
+            return format!("{}:{}", self.name, key);
+        }
+        match indexer_function_name_len(key) {
+            Some(len) => format!("{{{}:{}}}{}", self.name, &key[..len], &key[len..]),
+            None => format!("{{{}}}:{}", self.name, key),
+        }
+    }
+
+    /// Key as passed to [KeyNamespace::key], `None` for keys of other namespaces
+    pub fn strip(&self, namespaced_key: &str) -> Option<String> {
+        if !self.hash_tagged {
+            return namespaced_key
+                .strip_prefix(self.name.as_str())?
+                .strip_prefix(':')
+                .map(ToString::to_string);
+        }
+        let tagged_key = namespaced_key
+            .strip_prefix('{')?
+            .strip_prefix(self.name.as_str())?;
+        if let Some(key) = tagged_key.strip_prefix("}:") {
+            return Some(key.to_string());
+        }
+        let (function_name, rest) = tagged_key.strip_prefix(':')?.split_once('}')?;
+        Some(format!("{}{}", function_name, rest))
+    }
+}
+
+/// [Storage] whose keys all live in a [KeyNamespace]. Keys stored as values, e.g. the stream
+/// keys of [STREAMS_SET_KEY], are kept without the prefix, the runner adds it the same way.
+pub struct NamespacedStorage<S> {
+    storage: S,
+    namespace: KeyNamespace,
+}
+
+impl<S: Storage> NamespacedStorage<S> {
+    pub fn new(storage: S, namespace: KeyNamespace) -> Self {
+        Self { storage, namespace }
+    }
+
+    pub fn namespace(&self) -> &KeyNamespace {
+        &self.namespace
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: Storage> Storage for NamespacedStorage<S> {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        self.storage.get(&self.namespace.key(key)).await
+    }
+
+    async fn mget(&self, keys: &[String]) -> anyhow::Result<Vec<Option<String>>> {
+        let keys: Vec<String> = keys.iter().map(|key| self.namespace.key(key)).collect();
+        self.storage.mget(&keys).await
+    }
+
+    async fn set(
+        &self,
+        key: &str,
+        value: &str,
+        expiration_seconds: Option<usize>,
+    ) -> anyhow::Result<()> {
+        self.storage
+            .set(&self.namespace.key(key), value, expiration_seconds)
+            .await
+    }
+
+    async fn del(&self, key: &str) -> anyhow::Result<()> {
+        self.storage.del(&self.namespace.key(key)).await
+    }
+
+    async fn incr(&self, key: &str, delta: i64) -> anyhow::Result<i64> {
+        self.storage.incr(&self.namespace.key(key), delta).await
+    }
+
+    async fn sadd(&self, key: &str, member: &str) -> anyhow::Result<()> {
+        self.storage.sadd(&self.namespace.key(key), member).await
+    }
+
+    async fn smembers(&self, key: &str) -> anyhow::Result<Vec<String>> {
+        self.storage.smembers(&self.namespace.key(key)).await
+    }
+
+    async fn xadd(
+        &self,
+        stream_key: &str,
+        block_height: u64,
+        fields: &[(&str, String)],
+    ) -> anyhow::Result<bool> {
+        self.storage
+            .xadd(&self.namespace.key(stream_key), block_height, fields)
+            .await
+    }
+
+    async fn xlen(&self, stream_key: &str) -> anyhow::Result<u64> {
+        self.storage.xlen(&self.namespace.key(stream_key)).await
+    }
+
+    async fn scan(&self, pattern: &str) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .storage
+            .scan(&self.namespace.key(pattern))
+            .await?
+            .into_iter()
+            .filter_map(|key| self.namespace.strip(&key))
+            .collect())
+    }
+
+    async fn rename(&self, key: &str, new_key: &str) -> anyhow::Result<bool> {
+        self.storage
+            .rename(&self.namespace.key(key), &self.namespace.key(new_key))
+            .await
+    }
+
+    async fn xtrim(&self, stream_key: &str, trim: StreamTrim) -> anyhow::Result<u64> {
+        self.storage
+            .xtrim(&self.namespace.key(stream_key), trim)
+            .await
+    }
+
+    async fn memory_usage(&self, key: &str) -> anyhow::Result<Option<u64>> {
+        self.storage.memory_usage(&self.namespace.key(key)).await
+    }
+
+    async fn used_memory(&self) -> anyhow::Result<u64> {
+        self.storage.used_memory().await
+    }
+
+    async fn consumer_group_progress(
+        &self,
+        stream_key: &str,
+    ) -> anyhow::Result<Option<ConsumerGroupProgress>> {
+        self.storage
+            .consumer_group_progress(&self.namespace.key(stream_key))
+            .await
+    }
+
+    async fn commit(&self, batch: WriteBatch) -> anyhow::Result<()> {
+        self.storage
+            .commit(batch.with_namespace(&self.namespace))
+            .await
+    }
+}
+
+/// Patterns of the keys written by coordinators before [KeyNamespace]. Receipts of the
+/// watching list were keyed by their id alone, they are left to expire.
+const LEGACY_KEY_PATTERNS: [&str; 2] = ["streamer:message:*", "transaction:pending:*"];
+/// Pattern of the receipt counters written by coordinators before [KeyNamespace], which
+/// nothing reads anymore
+const LEGACY_COUNTER_PATTERN: &str = "receipts_*";
+
+/// Moves the keys written by coordinators before [KeyNamespace] into `namespace`, deletes
+/// the legacy receipt counters and returns how many keys were moved or deleted. A key already
+/// present in the namespace is left in place. A cluster rejects renames across hash slots, so
+/// only keys of a single node or sentinel deployment can be migrated.
+pub async fn migrate_keys(storage: &dyn Storage, namespace: &KeyNamespace) -> anyhow::Result<u64> {
+    let mut keys: Vec<String> = ["last_indexed_block", "blocks_processed"]
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+    let stream_keys = storage.smembers(STREAMS_SET_KEY).await?;
+    for stream_key in &stream_keys {
+        keys.push(format!("{}:storage", stream_key));
+        keys.push(generate_processed_block_height_key(stream_key));
+        keys.push(stream_key.clone());
+    }
+    for pattern in LEGACY_KEY_PATTERNS {
+        keys.extend(storage.scan(pattern).await?);
+    }
+
+    let mut migrated_keys = 0;
+    for key in storage.scan(LEGACY_COUNTER_PATTERN).await? {
+        storage.del(&key).await?;
+        migrated_keys += 1;
+    }
+
+    for key in keys {
+        let new_key = namespace.key(&key);
+        if storage.rename(&key, &new_key).await? {
+            migrated_keys += 1;
+        } else {
+            tracing::debug!(
+                target: crate::STORAGE,
+                "Not migrating {}, it or {} is missing",
+                key,
+                new_key
+            );
+        }
+    }
+
+    // the streams are added to the set of the namespace, which may list streams already
+    if !stream_keys.is_empty() {
+        let namespaced_streams_set_key = namespace.key(STREAMS_SET_KEY);
+        let mut batch = WriteBatch::new();
+        for stream_key in &stream_keys {
+            batch.sadd(&namespaced_streams_set_key, stream_key);
+        }
+        batch.del(STREAMS_SET_KEY);
+        storage.commit(batch).await?;
+        migrated_keys += 1;
+    }
+    Ok(migrated_keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{migrate_keys, KeyNamespace, NamespacedStorage};
+    use crate::{
+        generate_processed_block_height_key, generate_real_time_storage_key,
+        generate_real_time_stream_key, InMemoryStorage, Storage, WriteBatch, STREAMS_SET_KEY,
+    };
+
+    const RECEIPT_ID: &str = "9Ziwq5yU8CZ5aDc1P2vF8hT2vVQz6sCfknH3uBMoXTSi";
+
+    #[tokio::test]
+    async fn migrate_legacy_keys_into_namespace() {
+        let storage = InMemoryStorage::new();
+        let legacy_stream_key = "test.near/indexer:real_time:stream";
+        let stream_key = generate_real_time_stream_key("test.near/indexer");
+
+        let mut batch = WriteBatch::new();
+        batch
+            .create_consumer_group(legacy_stream_key)
+            .sadd(STREAMS_SET_KEY, legacy_stream_key)
+            .set(&format!("{}:storage", legacy_stream_key), "{}", None)
+            .xadd(legacy_stream_key, 10, &[("block_height", "10".to_string())])
+            .set("streamer:message:10", "{}", Some(60))
+            .set("unrelated", "value", None)
+            .update_last_indexed_block(10)
+            // receipts watching list as written before it was namespaced
+            .set(RECEIPT_ID, "transaction", Some(60))
+            .incr("receipts_transaction", 1);
+        storage.commit(batch).await.unwrap();
+
+        let namespace = KeyNamespace::new("mainnet", Some("queryapi"));
+        assert_eq!(migrate_keys(&storage, &namespace).await.unwrap(), 7);
+        assert_eq!(
+            storage.get("unrelated").await.unwrap(),
+            Some("value".to_string())
+        );
+        assert_eq!(storage.get("last_indexed_block").await.unwrap(), None);
+        assert_eq!(storage.get("receipts_transaction").await.unwrap(), None);
+        // watched receipts are left to expire
+        assert_eq!(
+            storage.get(RECEIPT_ID).await.unwrap(),
+            Some("transaction".to_string())
+        );
+
+        let namespaced_storage = NamespacedStorage::new(storage, namespace.clone());
+        assert_eq!(
+            namespaced_storage.get_last_indexed_block().await.unwrap(),
+            10
+        );
+        assert_eq!(
+            namespaced_storage.smembers(STREAMS_SET_KEY).await.unwrap(),
+            vec![stream_key.clone()]
+        );
+        assert_eq!(namespaced_storage.xlen(&stream_key).await.unwrap(), 1);
+        assert_eq!(
+            namespaced_storage
+                .get(&generate_real_time_storage_key("test.near/indexer"))
+                .await
+                .unwrap(),
+            Some("{}".to_string())
+        );
+        assert!(namespaced_storage
+            .consumer_group_progress(&stream_key)
+            .await
+            .unwrap()
+            .is_some());
+
+        // migrating twice leaves the namespace as it is
+        assert_eq!(
+            migrate_keys(namespaced_storage.inner(), &namespace)
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn resume_from_checkpoint_without_namespace_once_migrated() {
+        let storage = InMemoryStorage::new();
+        storage
+            .set("last_indexed_block", "100", None)
+            .await
+            .unwrap();
+
+        // the checkpoint without namespace may belong to another chain, it is only read by
+        // the migration which moves it
+        let namespace = KeyNamespace::new("testnet", None);
+        let namespaced_storage = NamespacedStorage::new(storage, namespace.clone());
+        assert!(namespaced_storage.get_last_indexed_block().await.is_err());
+
+        migrate_keys(namespaced_storage.inner(), &namespace)
+            .await
+            .unwrap();
+        assert_eq!(
+            namespaced_storage.get_last_indexed_block().await.unwrap(),
+            100
+        );
+    }
+
+    #[test]
+    fn hash_tag_namespace_by_indexer_function() {
+        let namespace = KeyNamespace::hash_tagged("mainnet", Some("queryapi"));
+        let stream_key = generate_real_time_stream_key("test.near/indexer");
+        for (key, namespaced_key) in [
+            (
+                "last_indexed_block",
+                "{queryapi:mainnet}:last_indexed_block",
+            ),
+            (
+                &stream_key,
+                "{queryapi:mainnet:test.near/indexer}:real_time:stream",
+            ),
+            (
+                &generate_real_time_storage_key("test.near/indexer"),
+                "{queryapi:mainnet:test.near/indexer}:real_time:stream:storage",
+            ),
+            (
+                &generate_processed_block_height_key(&stream_key),
+                "{queryapi:mainnet:test.near/indexer}:real_time:stream:processed_block_height",
+            ),
+        ] {
+            assert_eq!(namespace.key(key), namespaced_key);
+            assert_eq!(namespace.strip(namespaced_key).as_deref(), Some(key));
+        }
+        assert_eq!(
+            namespace.strip("{queryapi:testnet}:last_indexed_block"),
+            None
+        );
+        assert_eq!(
+            KeyNamespace::new("mainnet", None).strip("mainnet2:last_indexed_block"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn keep_chains_apart() {
+        let mainnet_storage =
+            NamespacedStorage::new(InMemoryStorage::new(), KeyNamespace::new("mainnet", None));
+        let mut batch = WriteBatch::new();
+        batch.update_last_indexed_block(100);
+        mainnet_storage.commit(batch).await.unwrap();
+
+        let testnet_storage = NamespacedStorage::new(
+            mainnet_storage.into_inner(),
+            KeyNamespace::new("testnet", None),
+        );
+        assert!(testnet_storage.get_last_indexed_block().await.is_err());
+        let mut batch = WriteBatch::new();
+        batch.update_last_indexed_block(200);
+        testnet_storage.commit(batch).await.unwrap();
+
+        assert_eq!(testnet_storage.get_last_indexed_block().await.unwrap(), 200);
+        assert_eq!(
+            testnet_storage
+                .inner()
+                .get("mainnet:last_indexed_block")
+                .await
+                .unwrap(),
+            Some("100".to_string())
+        );
+        let mut keys = testnet_storage.scan("*").await.unwrap();
+        keys.sort();
+        assert_eq!(keys, ["blocks_processed", "last_indexed_block"]);
+    }
+}
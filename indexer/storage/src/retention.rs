@@ -0,0 +1,223 @@
+use crate::{
+    is_real_time_stream_key, Storage, PENDING_TRANSACTION_EXPIRATION_SECONDS,
+    RECEIPT_WATCHING_LIST_EXPIRATION_SECONDS, STREAMER_MESSAGE_EXPIRATION_SECONDS, STREAMS_SET_KEY,
+};
+
+/// How long the coordinator keeps what it writes to Redis
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub streamer_message_expiration_seconds: usize,
+    pub receipt_watching_list_expiration_seconds: usize,
+    pub pending_transaction_expiration_seconds: usize,
+    /// Real-time stream entries of blocks this far behind the last indexed block are trimmed
+    /// even if the runner has not processed them, `None` keeps them
+    pub real_time_stream_retention_blocks: Option<u64>,
+    /// Oldest entries of a real-time stream beyond this length are trimmed even if the runner
+    /// has not processed them, `None` keeps them
+    pub real_time_stream_max_length: Option<usize>,
+    /// Oldest entries of a historical stream beyond this length are trimmed, `None` keeps
+    /// the whole backfill
+    pub historical_stream_max_length: Option<usize>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            streamer_message_expiration_seconds: STREAMER_MESSAGE_EXPIRATION_SECONDS,
+            receipt_watching_list_expiration_seconds: RECEIPT_WATCHING_LIST_EXPIRATION_SECONDS,
+            pending_transaction_expiration_seconds: PENDING_TRANSACTION_EXPIRATION_SECONDS,
+            real_time_stream_retention_blocks: None,
+            real_time_stream_max_length: None,
+            historical_stream_max_length: None,
+        }
+    }
+}
+
+/// Which entries [Storage::xtrim] removes from the start of a stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamTrim {
+    /// Keeps the newest entries up to the length
+    MaxLength(usize),
+    /// Keeps the entries of this block height and later ones
+    MinBlockHeight(u64),
+}
+
+/// Size of a stream after [trim_streams]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamUsage {
+    pub stream_key: String,
+    pub length: u64,
+    pub memory_usage_bytes: u64,
+    /// Entries trimmed before the runner processed them
+    pub trimmed_entries: u64,
+}
+
+/// Trims every stream of [STREAMS_SET_KEY] up to the blocks its runner consumer group
+/// processed, streams without a group are left as they are. The policy may also trim the
+/// oldest part of the backlog of an indexer lagging behind, which would otherwise grow until
+/// Redis runs out of memory.
+pub async fn trim_streams(
+    storage: &dyn Storage,
+    retention_policy: &RetentionPolicy,
+    last_indexed_block: u64,
+) -> anyhow::Result<Vec<StreamUsage>> {
+    let mut stream_usages = vec![];
+    for stream_key in storage.smembers(STREAMS_SET_KEY).await? {
+        if let Some(progress) = storage.consumer_group_progress(&stream_key).await? {
+            let processed_block_height =
+                progress.stream_processed_block_height(&stream_key, last_indexed_block);
+            storage
+                .xtrim(
+                    &stream_key,
+                    StreamTrim::MinBlockHeight(processed_block_height + 1),
+                )
+                .await?;
+        }
+
+        let mut trimmed_entries = 0;
+        if is_real_time_stream_key(&stream_key) {
+            if let Some(retention_blocks) = retention_policy.real_time_stream_retention_blocks {
+                trimmed_entries += storage
+                    .xtrim(
+                        &stream_key,
+                        StreamTrim::MinBlockHeight(
+                            last_indexed_block.saturating_sub(retention_blocks),
+                        ),
+                    )
+                    .await?;
+            }
+            if let Some(max_length) = retention_policy.real_time_stream_max_length {
+                trimmed_entries += storage
+                    .xtrim(&stream_key, StreamTrim::MaxLength(max_length))
+                    .await?;
+            }
+        } else if let Some(max_length) = retention_policy.historical_stream_max_length {
+            trimmed_entries += storage
+                .xtrim(&stream_key, StreamTrim::MaxLength(max_length))
+                .await?;
+        }
+
+        stream_usages.push(StreamUsage {
+            length: storage.xlen(&stream_key).await?,
+            memory_usage_bytes: storage.memory_usage(&stream_key).await?.unwrap_or(0),
+            trimmed_entries,
+            stream_key,
+        });
+    }
+    Ok(stream_usages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{trim_streams, RetentionPolicy};
+    use crate::{
+        generate_historical_stream_key, generate_real_time_stream_key, InMemoryStorage, Storage,
+        WriteBatch, STREAMS_SET_KEY,
+    };
+
+    #[tokio::test]
+    async fn trim_lagging_real_time_streams_only() {
+        let storage = InMemoryStorage::new();
+        let real_time_stream_key = generate_real_time_stream_key("lagging.near/indexer");
+        let historical_stream_key = generate_historical_stream_key("lagging.near/indexer");
+
+        let mut batch = WriteBatch::new();
+        batch
+            .sadd(STREAMS_SET_KEY, &real_time_stream_key)
+            .sadd(STREAMS_SET_KEY, &historical_stream_key);
+        for block_height in 1..=10 {
+            batch
+                .xadd(
+                    &real_time_stream_key,
+                    block_height,
+                    &[("block_height", block_height.to_string())],
+                )
+                .xadd(
+                    &historical_stream_key,
+                    block_height,
+                    &[("block_height", block_height.to_string())],
+                );
+        }
+        storage.commit(batch).await.unwrap();
+
+        let retention_policy = RetentionPolicy {
+            real_time_stream_retention_blocks: Some(5),
+            real_time_stream_max_length: Some(3),
+            ..RetentionPolicy::default()
+        };
+        let mut stream_usages = trim_streams(&storage, &retention_policy, 12).await.unwrap();
+        stream_usages.sort_by(|a, b| a.stream_key.cmp(&b.stream_key));
+
+        let usages: Vec<(&str, u64, u64)> = stream_usages
+            .iter()
+            .map(|usage| {
+                (
+                    usage.stream_key.as_str(),
+                    usage.length,
+                    usage.trimmed_entries,
+                )
+            })
+            .collect();
+        assert_eq!(
+            usages,
+            vec![
+                (historical_stream_key.as_str(), 10, 0),
+                (real_time_stream_key.as_str(), 3, 7),
+            ]
+        );
+        assert!(stream_usages
+            .iter()
+            .all(|usage| usage.memory_usage_bytes > 0));
+        let ids: Vec<String> = storage
+            .stream_entries(&real_time_stream_key)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, ["8-0", "9-0", "10-0"]);
+    }
+
+    #[tokio::test]
+    async fn trim_blocks_processed_by_runner() {
+        let storage = InMemoryStorage::new();
+        let consumed_stream_key = generate_real_time_stream_key("consumed.near/indexer");
+        let unconsumed_stream_key = generate_real_time_stream_key("unconsumed.near/indexer");
+
+        let mut batch = WriteBatch::new();
+        batch
+            .create_consumer_group(&consumed_stream_key)
+            .sadd(STREAMS_SET_KEY, &consumed_stream_key)
+            .sadd(STREAMS_SET_KEY, &unconsumed_stream_key);
+        for block_height in 1..=5 {
+            batch
+                .xadd(
+                    &consumed_stream_key,
+                    block_height,
+                    &[("block_height", block_height.to_string())],
+                )
+                .xadd(
+                    &unconsumed_stream_key,
+                    block_height,
+                    &[("block_height", block_height.to_string())],
+                );
+        }
+        storage.commit(batch).await.unwrap();
+
+        storage.read_group(&consumed_stream_key, 3);
+        storage.acknowledge(&consumed_stream_key, 1);
+        storage.acknowledge(&consumed_stream_key, 2);
+        let stream_usages = trim_streams(&storage, &RetentionPolicy::default(), 5)
+            .await
+            .unwrap();
+        assert!(stream_usages.iter().all(|usage| usage.trimmed_entries == 0));
+
+        let ids = |stream_key| -> Vec<String> {
+            storage
+                .stream_entries(stream_key)
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect()
+        };
+        assert_eq!(ids(&consumed_stream_key), ["3-0", "4-0", "5-0"]);
+        assert_eq!(ids(&unconsumed_stream_key).len(), 5);
+    }
+}
@@ -0,0 +1,170 @@
+use std::io;
+
+use serde::Serialize;
+use serde_json::ser::{CompactFormatter, Formatter};
+
+/// Marker prepended to a cached streamer message compressed by zstd, the rest of the value is
+/// the zstd frame of the JSON. Values without a marker are plain JSON.
+pub const ZSTD_STREAMER_MESSAGE_MARKER: &[u8] = b"zstd:";
+const ZSTD_LEVEL: i32 = 3;
+
+/// Format of the streamer messages cached under [crate::generate_real_time_streamer_message_key]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StreamerMessageEncoding {
+    /// camelCase JSON, readable by runners predating the encodings
+    #[default]
+    Json,
+    /// camelCase JSON compressed by zstd, behind [ZSTD_STREAMER_MESSAGE_MARKER]
+    Zstd,
+}
+
+impl std::str::FromStr for StreamerMessageEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(encoding: &str) -> Result<Self, Self::Err> {
+        match encoding {
+            "json" => Ok(StreamerMessageEncoding::Json),
+            "zstd" => Ok(StreamerMessageEncoding::Zstd),
+            _ => anyhow::bail!(
+                "Unknown streamer message encoding {}, expected json or zstd",
+                encoding
+            ),
+        }
+    }
+}
+
+/// Compact JSON with the keys of every object turned from snake_case to camelCase as they are
+/// written, e.g. `receipt_execution_outcomes` to `receiptExecutionOutcomes`
+#[derive(Default)]
+struct CamelCaseFormatter {
+    writing_key: bool,
+    capitalize_next: bool,
+}
+
+impl Formatter for CamelCaseFormatter {
+    fn begin_object_key<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.writing_key = true;
+        self.capitalize_next = false;
+        CompactFormatter.begin_object_key(writer, first)
+    }
+
+    fn end_object_key<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.writing_key = false;
+        CompactFormatter.end_object_key(writer)
+    }
+
+    fn write_string_fragment<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        fragment: &str,
+    ) -> io::Result<()> {
+        if !self.writing_key {
+            return writer.write_all(fragment.as_bytes());
+        }
+
+        let mut rest = fragment;
+        loop {
+            if self.capitalize_next {
+                if let Some(first_char) = rest.chars().next() {
+                    write!(writer, "{}", first_char.to_uppercase())?;
+                    rest = &rest[first_char.len_utf8()..];
+                    self.capitalize_next = false;
+                }
+            }
+            match rest.find('_') {
+                Some(underscore_index) => {
+                    writer.write_all(&rest.as_bytes()[..underscore_index])?;
+                    rest = &rest[underscore_index + 1..];
+                    self.capitalize_next = true;
+                }
+                None => return writer.write_all(rest.as_bytes()),
+            }
+        }
+    }
+}
+
+/// Serializes `value` to JSON with camelCase keys in a single pass
+pub fn to_camel_case_json<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<Vec<u8>> {
+    let mut json = Vec::with_capacity(128);
+    let mut serializer =
+        serde_json::Serializer::with_formatter(&mut json, CamelCaseFormatter::default());
+    value.serialize(&mut serializer)?;
+    Ok(json)
+}
+
+/// Value cached for the runner under [crate::generate_real_time_streamer_message_key], stored
+/// as a binary Redis value
+pub fn encode_streamer_message<T: Serialize + ?Sized>(
+    streamer_message: &T,
+    encoding: StreamerMessageEncoding,
+) -> anyhow::Result<Vec<u8>> {
+    let json = to_camel_case_json(streamer_message)?;
+    Ok(match encoding {
+        StreamerMessageEncoding::Json => json,
+        StreamerMessageEncoding::Zstd => {
+            let mut value = ZSTD_STREAMER_MESSAGE_MARKER.to_vec();
+            value.extend(zstd::bulk::compress(&json, ZSTD_LEVEL)?);
+            value
+        }
+    })
+}
+
+/// camelCase JSON of a value written by [encode_streamer_message] in any encoding
+pub fn decode_streamer_message(value: &[u8]) -> anyhow::Result<String> {
+    let json = match value.strip_prefix(ZSTD_STREAMER_MESSAGE_MARKER) {
+        Some(compressed) => zstd::stream::decode_all(compressed)?,
+        None => value.to_vec(),
+    };
+    Ok(String::from_utf8(json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode_streamer_message, encode_streamer_message, to_camel_case_json,
+        StreamerMessageEncoding, ZSTD_STREAMER_MESSAGE_MARKER,
+    };
+
+    #[derive(serde::Serialize)]
+    struct Shard {
+        shard_id: u64,
+        receipt_execution_outcomes: Vec<std::collections::BTreeMap<String, String>>,
+        state_changes: Option<String>,
+    }
+
+    fn shard() -> Shard {
+        Shard {
+            shard_id: 1,
+            receipt_execution_outcomes: vec![[
+                ("receiver_id".to_string(), "a_b.near".to_string()),
+                ("_leading".to_string(), "value_with\"quote".to_string()),
+            ]
+            .into_iter()
+            .collect()],
+            state_changes: None,
+        }
+    }
+
+    #[test]
+    fn camel_case_keys_only() {
+        assert_eq!(
+            String::from_utf8(to_camel_case_json(&shard()).unwrap()).unwrap(),
+            r#"{"shardId":1,"receiptExecutionOutcomes":[{"Leading":"value_with\"quote","receiverId":"a_b.near"}],"stateChanges":null}"#
+        );
+    }
+
+    #[test]
+    fn decode_every_encoding() {
+        let json = encode_streamer_message(&shard(), StreamerMessageEncoding::Json).unwrap();
+        let zstd = encode_streamer_message(&shard(), StreamerMessageEncoding::Zstd).unwrap();
+        assert!(zstd.starts_with(ZSTD_STREAMER_MESSAGE_MARKER));
+
+        let json = String::from_utf8(json).unwrap();
+        assert_eq!(decode_streamer_message(json.as_bytes()).unwrap(), json);
+        assert_eq!(decode_streamer_message(&zstd).unwrap(), json);
+    }
+}
@@ -6,7 +6,9 @@ use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::store::UnorderedMap;
 use near_sdk::{env, log, near_bindgen, serde_json, AccountId, BorshStorageKey, CryptoHash};
 
-use indexer_rule_type::indexer_rule::IndexerRule;
+use indexer_rule_type::indexer_rule::{
+    IndexerRule, IndexerRuleKind, MatchingRule, PayloadEnrichment,
+};
 
 type FunctionName = String;
 // Define the contract structure
@@ -74,10 +76,12 @@ pub struct IndexerConfig {
 // Migration types
 #[derive(BorshStorageKey, BorshSerialize)]
 pub enum StorageKeys {
-    Registry,            // can be removed after migration
-    Account(CryptoHash), // can be removed after migration
-    RegistryV1,
-    AccountV1(CryptoHash),
+    Registry,              // can be removed after migration
+    Account(CryptoHash),   // can be removed after migration
+    RegistryV1,            // can be removed after migration
+    AccountV1(CryptoHash), // can be removed after migration
+    RegistryV2,
+    AccountV2(CryptoHash),
 }
 
 #[near_bindgen]
@@ -95,6 +99,17 @@ pub struct OldIndexerConfig {
     code: String,
     start_block_height: Option<u64>,
     schema: Option<String>,
+    filter: OldIndexerRule,
+}
+
+/// [IndexerRule] as stored before `payload_enrichment` was added
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OldIndexerRule {
+    indexer_rule_kind: IndexerRuleKind,
+    matching_rule: MatchingRule,
+    id: Option<u32>,
+    name: Option<String>,
 }
 
 /// These roles are used to control access across the various contract methods.
@@ -175,14 +190,14 @@ impl Contract {
             env::panic_str("Failed to deserialize contract state");
         });
 
-        let mut registry = IndexersByAccount::new(StorageKeys::RegistryV1);
+        let mut registry = IndexersByAccount::new(StorageKeys::RegistryV2);
 
         state
             .registry
             .iter_mut()
             .for_each(|(account_id, functions)| {
                 let mut new_functions: IndexerConfigByFunctionName =
-                    IndexerConfigByFunctionName::new(StorageKeys::AccountV1(env::sha256_array(
+                    IndexerConfigByFunctionName::new(StorageKeys::AccountV2(env::sha256_array(
                         account_id.as_bytes(),
                     )));
                 functions
@@ -192,7 +207,16 @@ impl Contract {
                             code: old_indexer_config.code.clone(),
                             start_block_height: old_indexer_config.start_block_height,
                             schema: old_indexer_config.schema.clone(),
-                            filter: indexer_rule_type::near_social_indexer_rule(),
+                            filter: IndexerRule {
+                                indexer_rule_kind: old_indexer_config
+                                    .filter
+                                    .indexer_rule_kind
+                                    .clone(),
+                                matching_rule: old_indexer_config.filter.matching_rule.clone(),
+                                id: old_indexer_config.filter.id,
+                                name: old_indexer_config.filter.name.clone(),
+                                payload_enrichment: PayloadEnrichment::None,
+                            },
                         };
                         new_functions.insert(function_name.clone(), new_indexer_config);
                     });
@@ -205,7 +229,7 @@ impl Contract {
 
         Self {
             registry,
-            account_roles: Self::default().account_roles,
+            account_roles: state.account_roles,
         }
     }
 
@@ -345,6 +369,10 @@ impl Contract {
                         env::panic_str(&format!("Invalid filter JSON {}", filter_json));
                     });
 
+                filter_rule.matching_rule.validate().unwrap_or_else(|err| {
+                    env::panic_str(&format!("Invalid filter JSON {}: {}", filter_json, err));
+                });
+
                 filter_rule
             }
             None => indexer_rule_type::near_social_indexer_rule(),
@@ -439,14 +467,24 @@ impl Contract {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use indexer_rule_type::indexer_rule::{IndexerRuleKind, MatchingRule, Status};
+    use indexer_rule_type::indexer_rule::Status;
 
     #[test]
     fn migrate() {
-        let mut registry = OldIndexersByAccount::new(StorageKeys::Registry);
+        let old_filter = OldIndexerRule {
+            indexer_rule_kind: IndexerRuleKind::Action,
+            matching_rule: MatchingRule::ActionFunctionCall {
+                affected_account_id: "social.near".to_string(),
+                function: "set".to_string(),
+                status: Status::Any,
+            },
+            id: None,
+            name: None,
+        };
+        let mut registry = OldIndexersByAccount::new(StorageKeys::RegistryV1);
         let account_id = AccountId::new_unchecked("morgs.near".to_string());
         let mut funcs: OldIndexerConfigByFunctionName = OldIndexerConfigByFunctionName::new(
-            StorageKeys::Account(env::sha256_array(account_id.as_bytes())),
+            StorageKeys::AccountV1(env::sha256_array(account_id.as_bytes())),
         );
 
         funcs.insert(
@@ -455,6 +493,7 @@ mod tests {
                 code: "return block;".to_string(),
                 start_block_height: None,
                 schema: None,
+                filter: old_filter.clone(),
             },
         );
         funcs.insert(
@@ -463,12 +502,13 @@ mod tests {
                 code: "return block2;".to_string(),
                 start_block_height: None,
                 schema: None,
+                filter: old_filter.clone(),
             },
         );
         registry.insert(AccountId::new_unchecked("morgs.near".to_string()), funcs);
 
         let mut funcs: OldIndexerConfigByFunctionName = OldIndexerConfigByFunctionName::new(
-            StorageKeys::Account(env::sha256_array("root.near".as_bytes())),
+            StorageKeys::AccountV1(env::sha256_array("root.near".as_bytes())),
         );
         funcs.insert(
             "my_function".to_string(),
@@ -476,12 +516,21 @@ mod tests {
                 code: "var x = 1;".to_string(),
                 start_block_height: Some(1),
                 schema: None,
+                filter: OldIndexerRule {
+                    indexer_rule_kind: IndexerRuleKind::Action,
+                    matching_rule: MatchingRule::ActionAny {
+                        affected_account_id: "root.near".to_string(),
+                        status: Status::Success,
+                    },
+                    id: Some(1),
+                    name: Some("my_function".to_string()),
+                },
             },
         );
         registry.insert(AccountId::new_unchecked("root.near".to_string()), funcs);
 
         let mut funcs: OldIndexerConfigByFunctionName = OldIndexerConfigByFunctionName::new(
-            StorageKeys::Account(env::sha256_array("roshaan.near".as_bytes())),
+            StorageKeys::AccountV1(env::sha256_array("roshaan.near".as_bytes())),
         );
         funcs.insert(
             "another/function".to_string(),
@@ -489,13 +538,17 @@ mod tests {
                 code: "console.log('hello');".to_string(),
                 start_block_height: Some(1),
                 schema: None,
+                filter: old_filter,
             },
         );
         registry.insert(AccountId::new_unchecked("roshaan.near".to_string()), funcs);
 
         env::state_write(&OldState {
             registry,
-            account_roles: vec![],
+            account_roles: vec![AccountRole {
+                account_id: AccountId::new_unchecked("bob.near".to_string()),
+                role: Role::User,
+            }],
         });
 
         let contract = Contract::migrate();
@@ -517,7 +570,16 @@ mod tests {
                 .get("my_function")
                 .unwrap()
                 .filter,
-            indexer_rule_type::near_social_indexer_rule()
+            IndexerRule {
+                indexer_rule_kind: IndexerRuleKind::Action,
+                matching_rule: MatchingRule::ActionAny {
+                    affected_account_id: "root.near".to_string(),
+                    status: Status::Success,
+                },
+                id: Some(1),
+                name: Some("my_function".to_string()),
+                payload_enrichment: PayloadEnrichment::None,
+            }
         );
         assert_eq!(
             contract
@@ -528,7 +590,13 @@ mod tests {
             1
         );
 
-        assert_eq!(contract.account_roles.len(), 7);
+        assert_eq!(
+            contract.account_roles,
+            vec![AccountRole {
+                account_id: AccountId::new_unchecked("bob.near".to_string()),
+                role: Role::User,
+            }]
+        );
     }
 
     #[test]
@@ -935,6 +1003,7 @@ mod tests {
                 },
                 id: None,
                 name: None,
+                payload_enrichment: PayloadEnrichment::None,
             },
         };
 
@@ -979,6 +1048,7 @@ mod tests {
                 },
                 id: None,
                 name: None,
+                payload_enrichment: PayloadEnrichment::None,
             },
         };
 
@@ -1025,6 +1095,96 @@ mod tests {
         );
     }
 
+    #[test]
+    fn register_indexer_function_with_composed_filter() {
+        let mut contract = Contract {
+            registry: IndexersByAccount::new(StorageKeys::Registry),
+            account_roles: vec![AccountRole {
+                account_id: AccountId::new_unchecked("bob.near".to_string()),
+                role: Role::User,
+            }],
+        };
+        let config = IndexerConfig {
+            code: "var x= 1;".to_string(),
+            start_block_height: None,
+            schema: None,
+            filter: IndexerRule {
+                indexer_rule_kind: IndexerRuleKind::Action,
+                matching_rule: MatchingRule::All {
+                    matching_rules: vec![
+                        MatchingRule::Any {
+                            matching_rules: vec![
+                                MatchingRule::ActionFunctionCall {
+                                    affected_account_id: "a.near".to_string(),
+                                    function: "test".to_string(),
+                                    status: Status::Success,
+                                },
+                                MatchingRule::Event {
+                                    contract_account_id: "b.near".to_string(),
+                                    standard: "*".to_string(),
+                                    version: "*".to_string(),
+                                    event: "*".to_string(),
+                                },
+                            ]
+                            .into(),
+                        },
+                        MatchingRule::Not {
+                            matching_rules: vec![MatchingRule::ActionAny {
+                                affected_account_id: "spam.near".to_string(),
+                                status: Status::Any,
+                            }]
+                            .into(),
+                        },
+                    ]
+                    .into(),
+                },
+                id: None,
+                name: None,
+                payload_enrichment: PayloadEnrichment::None,
+            },
+        };
+
+        contract.register_indexer_function(
+            "test".to_string(),
+            config.code.clone(),
+            config.start_block_height,
+            config.schema.clone(),
+            None,
+            Some(r#"{"indexer_rule_kind":"Action","matching_rule":{"rule":"ALL","matching_rules":[{"rule":"ANY","matching_rules":[{"rule":"ACTION_FUNCTION_CALL","affected_account_id":"a.near","function":"test","status":"SUCCESS"},{"rule":"EVENT","contract_account_id":"b.near","standard":"*","version":"*","event":"*"}]},{"rule":"NOT","matching_rules":[{"rule":"ACTION_ANY","affected_account_id":"spam.near","status":"ANY"}]}]}}"#.to_string()),
+        );
+
+        assert_eq!(
+            contract
+                .registry
+                .get(&AccountId::new_unchecked("bob.near".to_string()))
+                .unwrap()
+                .get("test")
+                .unwrap(),
+            &config
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Composed rules must contain at least one rule")]
+    fn register_indexer_function_with_empty_composed_filter() {
+        let mut contract = Contract {
+            registry: IndexersByAccount::new(StorageKeys::Registry),
+            account_roles: vec![AccountRole {
+                account_id: AccountId::new_unchecked("bob.near".to_string()),
+                role: Role::User,
+            }],
+        };
+
+        contract.register_indexer_function(
+            "test".to_string(),
+            "var x= 1;".to_string(),
+            None,
+            None,
+            None,
+            Some(r#"{"indexer_rule_kind":"Action","matching_rule":{"rule":"ANY","matching_rules":[]}}"#.to_string()),
+        );
+    }
+
     #[test]
     fn register_indexer_function_for_existing_account() {
         let account_id = AccountId::new_unchecked("bob.near".to_string());
@@ -206,6 +206,38 @@ CREATE TABLE
     expect(mockFetch.mock.calls).toMatchSnapshot();
   });
 
+  test('Indexer.runFunctions() exposes the rule matches of the block', async () => {
+    const mockFetch = jest.fn(() => ({
+      status: 200,
+      json: async () => ({
+        errors: null,
+      }),
+    }));
+    const mockBlock = Block.fromStreamerMessage({
+      block: {
+        chunks: [],
+        header: {
+          height: 456
+        }
+      },
+      shards: {}
+    } as unknown as StreamerMessage) as unknown as Block;
+    const indexer = new Indexer({ fetch: mockFetch as unknown as typeof fetch });
+
+    const functions: Record<string, any> = {};
+    functions['buildnear.testnet/test'] = {
+      code: `
+            if (matches.length !== 1 || matches[0].receipt_id !== 'abc') {
+                throw new Error('Unexpected matches');
+            }
+        `,
+      schema: SIMPLE_SCHEMA
+    };
+
+    await expect(indexer.runFunctions(mockBlock, functions, false, { matches: [{ receipt_id: 'abc' }] })).resolves.toEqual([]);
+    await expect(indexer.runFunctions(mockBlock, functions, false)).rejects.toThrow('Unexpected matches');
+  });
+
   test('Indexer.transformIndexerFunction() applies the necessary transformations', () => {
     const indexer = new Indexer();
 
@@ -51,7 +51,7 @@ export default class Indexer {
     block: Block,
     functions: Record<string, IndexerFunction>,
     isHistorical: boolean,
-    options: { provision?: boolean } = { provision: false }
+    options: { provision?: boolean, matches?: unknown[] } = { provision: false }
   ): Promise<string[]> {
     const blockHeight = block.blockHeight;
 
@@ -93,6 +93,8 @@ export default class Indexer {
         const context = this.buildContext(indexerFunction.schema, functionName, blockHeight, hasuraRoleName);
 
         vm.freeze(block, 'block');
+        // rule matches of the block as published by the coordinator, empty for historical blocks
+        vm.freeze(options.matches ?? [], 'matches');
         vm.freeze(context, 'context');
         vm.freeze(context, 'console'); // provide console.log via context.log
 
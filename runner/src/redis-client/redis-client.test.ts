@@ -1,19 +1,21 @@
 import RedisClient from './redis-client';
 
 describe('RedisClient', () => {
-  it('returns the first message', async () => {
+  it('returns the first undelivered message', async () => {
     const mockClient = {
       on: jest.fn(),
       connect: jest.fn().mockResolvedValue(null),
-      xRead: jest.fn().mockResolvedValue(null),
+      xReadGroup: jest.fn().mockResolvedValue(null),
     } as any;
 
-    const client = new RedisClient(mockClient);
+    const client = new RedisClient(mockClient, 'mainnet');
 
     const message = await client.getStreamMessages('streamKey');
 
-    expect(mockClient.xRead).toHaveBeenCalledWith(
-      { key: 'streamKey', id: '0' },
+    expect(mockClient.xReadGroup).toHaveBeenCalledWith(
+      'runner',
+      'runner',
+      { key: 'mainnet:streamKey', id: '>' },
       { COUNT: 1 }
     );
     expect(message).toBeUndefined();
@@ -23,32 +25,79 @@ describe('RedisClient', () => {
     const mockClient = {
       on: jest.fn(),
       connect: jest.fn().mockResolvedValue(null),
-      xRead: jest.fn().mockResolvedValue(null),
+      xReadGroup: jest.fn().mockResolvedValue(null),
     } as any;
 
-    const client = new RedisClient(mockClient);
+    const client = new RedisClient(mockClient, 'mainnet');
 
     const message = await client.getStreamMessages('streamKey', '123-0', 10);
 
-    expect(mockClient.xRead).toHaveBeenCalledWith(
-      { key: 'streamKey', id: '123-0' },
+    expect(mockClient.xReadGroup).toHaveBeenCalledWith(
+      'runner',
+      'runner',
+      { key: 'mainnet:streamKey', id: '123-0' },
       { COUNT: 10 }
     );
     expect(message).toBeUndefined();
   });
 
-  it('deletes the stream message', async () => {
+  it('acknowledges the stream message', async () => {
     const mockClient = {
       on: jest.fn(),
       connect: jest.fn().mockResolvedValue(null),
-      xDel: jest.fn().mockResolvedValue(null),
+      xAck: jest.fn().mockResolvedValue(null),
     } as any;
 
-    const client = new RedisClient(mockClient);
+    const client = new RedisClient(mockClient, 'mainnet');
 
-    await client.deleteStreamMessage('streamKey', '1-1');
+    await client.acknowledgeStreamMessage('streamKey', '1-0');
 
-    expect(mockClient.xDel).toHaveBeenCalledWith('streamKey', '1-1');
+    expect(mockClient.xAck).toHaveBeenCalledWith('mainnet:streamKey', 'runner', '1-0');
+  });
+
+  it('creates the consumer group from the start of the stream', async () => {
+    const mockClient = {
+      on: jest.fn(),
+      connect: jest.fn().mockResolvedValue(null),
+      xGroupCreate: jest.fn().mockResolvedValue('OK'),
+    } as any;
+
+    const client = new RedisClient(mockClient, 'mainnet');
+
+    await client.createConsumerGroup('streamKey');
+
+    expect(mockClient.xGroupCreate).toHaveBeenCalledWith('mainnet:streamKey', 'runner', '0', { MKSTREAM: true });
+  });
+
+  it('keeps an existing consumer group', async () => {
+    const mockClient = {
+      on: jest.fn(),
+      connect: jest.fn().mockResolvedValue(null),
+      xGroupCreate: jest.fn().mockRejectedValue(new Error('BUSYGROUP Consumer Group name already exists')),
+    } as any;
+
+    const client = new RedisClient(mockClient, 'mainnet');
+
+    await expect(client.createConsumerGroup('streamKey')).resolves.toBeUndefined();
+  });
+
+  it('creates the consumer group again when reading a stream without it', async () => {
+    const mockClient = {
+      on: jest.fn(),
+      connect: jest.fn().mockResolvedValue(null),
+      xGroupCreate: jest.fn().mockResolvedValue('OK'),
+      xReadGroup: jest.fn()
+        .mockRejectedValueOnce(new Error('NOGROUP No such key or consumer group'))
+        .mockResolvedValueOnce(null),
+    } as any;
+
+    const client = new RedisClient(mockClient, 'mainnet');
+
+    const message = await client.getStreamMessages('streamKey');
+
+    expect(mockClient.xGroupCreate).toHaveBeenCalledWith('mainnet:streamKey', 'runner', '0', { MKSTREAM: true });
+    expect(mockClient.xReadGroup).toHaveBeenCalledTimes(2);
+    expect(message).toBeUndefined();
   });
 
   it('returns the range of messages after the passed id', async () => {
@@ -60,11 +109,11 @@ describe('RedisClient', () => {
       ]),
     } as any;
 
-    const client = new RedisClient(mockClient);
+    const client = new RedisClient(mockClient, 'mainnet');
 
     const unprocessedMessages = await client.getUnprocessedStreamMessages('streamKey');
 
-    expect(mockClient.xRange).toHaveBeenCalledWith('streamKey', '0', '+');
+    expect(mockClient.xRange).toHaveBeenCalledWith('mainnet:streamKey', '0', '+');
     expect(unprocessedMessages).toEqual([
       'data'
     ]);
@@ -77,11 +126,11 @@ describe('RedisClient', () => {
       get: jest.fn().mockResolvedValue(JSON.stringify({ account_id: '123', function_name: 'testFunc' })),
     } as any;
 
-    const client = new RedisClient(mockClient);
+    const client = new RedisClient(mockClient, 'mainnet');
 
     const storageData = await client.getStreamStorage('streamKey');
 
-    expect(mockClient.get).toHaveBeenCalledWith('streamKey:storage');
+    expect(mockClient.get).toHaveBeenCalledWith('mainnet:streamKey:storage');
     expect(storageData).toEqual({ account_id: '123', function_name: 'testFunc' });
   });
 
@@ -92,11 +141,11 @@ describe('RedisClient', () => {
       sMembers: jest.fn().mockResolvedValue(['streamKey1', 'streamKey2']),
     } as any;
 
-    const client = new RedisClient(mockClient);
+    const client = new RedisClient(mockClient, 'mainnet');
 
     const streams = await client.getStreams();
 
-    expect(mockClient.sMembers).toHaveBeenCalledWith('streams');
+    expect(mockClient.sMembers).toHaveBeenCalledWith('mainnet:streams');
     expect(streams).toEqual(['streamKey1', 'streamKey2']);
   });
 
@@ -104,12 +153,71 @@ describe('RedisClient', () => {
     const mockClient = {
       on: jest.fn(),
       connect: jest.fn().mockResolvedValue(null),
-      get: jest.fn(),
+      get: jest.fn().mockResolvedValue(Buffer.from('{"block":{}}')),
+    } as any;
+
+    const client = new RedisClient(mockClient, 'mainnet');
+    const streamerMessage = await client.getStreamerMessage(1000);
+
+    expect(mockClient.get).toHaveBeenCalledWith(expect.anything(), 'mainnet:streamer:message:1000');
+    expect(streamerMessage).toEqual('{"block":{}}');
+  });
+
+  it('returns null for a missing streamer message', async () => {
+    const mockClient = {
+      on: jest.fn(),
+      connect: jest.fn().mockResolvedValue(null),
+      get: jest.fn().mockResolvedValue(null),
+    } as any;
+
+    const client = new RedisClient(mockClient, 'mainnet');
+
+    expect(await client.getStreamerMessage(1000)).toBeNull();
+  });
+
+  it('decompresses streamer messages encoded with zstd', async () => {
+    const mockClient = {
+      on: jest.fn(),
+      connect: jest.fn().mockResolvedValue(null),
+      // '{"block":{}}' compressed by zstd
+      get: jest.fn().mockResolvedValue(Buffer.concat([
+        Buffer.from('zstd:'),
+        Buffer.from('KLUv/QRYYQAAeyJibG9jayI6e319csR2pw==', 'base64'),
+      ])),
+    } as any;
+
+    const client = new RedisClient(mockClient, 'mainnet');
+    const streamerMessage = await client.getStreamerMessage(1000);
+
+    expect(streamerMessage).toEqual('{"block":{}}');
+  });
+
+  it('prefixes keys with the configured namespace', async () => {
+    const mockClient = {
+      on: jest.fn(),
+      connect: jest.fn().mockResolvedValue(null),
+      sMembers: jest.fn().mockResolvedValue([]),
+    } as any;
+
+    const client = new RedisClient(mockClient, 'queryapi:testnet');
+    await client.getStreams();
+
+    expect(mockClient.sMembers).toHaveBeenCalledWith('queryapi:testnet:streams');
+  });
+
+  it('hash tags keys by indexer function in a cluster', async () => {
+    const mockClient = {
+      on: jest.fn(),
+      connect: jest.fn().mockResolvedValue(null),
+      sMembers: jest.fn().mockResolvedValue([]),
+      get: jest.fn().mockResolvedValue('{}'),
     } as any;
 
-    const client = new RedisClient(mockClient);
-    await client.getStreamerMessage(1000);
+    const client = new RedisClient(mockClient, 'mainnet', { mode: 'cluster', urls: ['redis://node-1'] });
+    await client.getStreams();
+    await client.getStreamStorage('test.near/indexer:real_time:stream');
 
-    expect(mockClient.get).toHaveBeenCalledWith('streamer:message:1000');
+    expect(mockClient.sMembers).toHaveBeenCalledWith('{mainnet}:streams');
+    expect(mockClient.get).toHaveBeenCalledWith('{mainnet:test.near/indexer}:real_time:stream:storage');
   });
 });
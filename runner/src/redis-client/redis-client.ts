@@ -1,9 +1,14 @@
-import { createClient, type RedisClientType } from 'redis';
+import { decompress } from 'fzstd';
+import { commandOptions, createClient, type RedisClientType } from 'redis';
+
+import { createClusterClient, redisKeyNamespaceFromEnv, redisTopologyFromEnv, resolveSentinelMaster, type RedisTopology } from './connection';
 
 interface StreamMessage {
   id: string
   message: {
     block_height: string
+    // JSON array of the rule matches of the block, only on real-time streams
+    matches?: string
   }
 }
 
@@ -19,18 +24,82 @@ export type StreamType = 'historical' | 'real-time';
 export default class RedisClient {
   SMALLEST_STREAM_ID = '0';
   LARGEST_STREAM_ID = '+';
+  UNDELIVERED_STREAM_ID = '>';
   STREAMS_SET_KEY = 'streams';
+  CONSUMER_GROUP = 'runner';
+  CONSUMER = 'runner';
   STREAMER_MESSAGE_HASH_KEY_BASE = 'streamer:message:';
+  ZSTD_STREAMER_MESSAGE_MARKER = 'zstd:';
+  SENTINEL_RETRY_DELAY_MS = 1000;
+
+  private client: Promise<RedisClientType>;
+  private disconnected = false;
 
+  // Prefix of every key, as namespaced by the coordinator of the chain, e.g. `mainnet` or `queryapi:mainnet`
   constructor (
-    private readonly client: RedisClientType = createClient({ url: process.env.REDIS_CONNECTION_STRING })
+    client?: RedisClientType,
+    private readonly namespace: string = redisKeyNamespaceFromEnv(),
+    private readonly topology: RedisTopology = redisTopologyFromEnv()
   ) {
+    if (client !== undefined) {
+      this.client = this.useClient(client);
+    } else if (topology.mode === 'cluster') {
+      this.client = this.useClient(createClusterClient(topology.urls));
+    } else if (topology.mode === 'sentinel') {
+      this.client = this.connectSentinelMaster();
+    } else {
+      this.client = this.useClient(createClient({ url: topology.urls[0] }));
+    }
+  }
+
+  private async useClient (client: RedisClientType): Promise<RedisClientType> {
     client.on('error', (err) => { console.log('Redis Client Error', err); });
     client.connect().catch(console.error);
+    return client;
+  }
+
+  // Waits for the sentinels to name a master reachable by the runner. A failover closes the connections to the
+  // former master, the sentinels are then asked for the new one.
+  private async connectSentinelMaster (): Promise<RedisClientType> {
+    while (true) {
+      try {
+        const masterUrl = await resolveSentinelMaster(this.topology.urls, this.topology.sentinelMasterName as string);
+        const client: RedisClientType = createClient({ url: masterUrl, socket: { reconnectStrategy: false } });
+        client.on('error', (err) => { console.log('Redis Client Error', err); });
+        await client.connect();
+        client.on('end', () => {
+          if (!this.disconnected) {
+            this.client = this.connectSentinelMaster();
+          }
+        });
+        return client;
+      } catch (err) {
+        console.log('Failed to connect to the master of the sentinels, retrying', err);
+        await new Promise((resolve) => setTimeout(resolve, this.SENTINEL_RETRY_DELAY_MS));
+      }
+    }
+  }
+
+  private async connection (): Promise<RedisClientType> {
+    return await this.client;
+  }
+
+  // In a cluster the keys are hash tagged like the coordinator does: the keys of the streams of an indexer function
+  // share a slot, e.g. `{mainnet:test.near/indexer}:real_time:stream:storage`, the others the slot of the chain,
+  // e.g. `{mainnet}:streams`
+  private namespaced (key: string): string {
+    if (this.topology.mode !== 'cluster') {
+      return `${this.namespace}:${key}`;
+    }
+    const streamKey = key.match(/^(.*?)(:real_time:stream|:historical:stream)(.*)$/);
+    if (streamKey === null) {
+      return `{${this.namespace}}:${key}`;
+    }
+    return `{${this.namespace}:${streamKey[1]}}${streamKey[2]}${streamKey[3]}`;
   }
 
   private generateStorageKey (streamkey: string): string {
-    return `${streamkey}:storage`;
+    return this.namespaced(`${streamkey}:storage`);
   };
 
   getStreamType (streamKey: string): StreamType {
@@ -41,41 +110,66 @@ export default class RedisClient {
   }
 
   async disconnect (): Promise<void> {
-    await this.client.disconnect();
+    this.disconnected = true;
+    await (await this.connection()).disconnect();
   }
 
+  // Creates the consumer group from the start of the stream, and the stream if missing, so entries added before the
+  // worker started are delivered too. An existing group is kept
+  async createConsumerGroup (streamKey: string): Promise<void> {
+    try {
+      await (await this.connection()).xGroupCreate(this.namespaced(streamKey), this.CONSUMER_GROUP, '0', { MKSTREAM: true });
+    } catch (err) {
+      if (!(err instanceof Error && err.message.startsWith('BUSYGROUP'))) {
+        throw err;
+      }
+    }
+  };
+
+  // Reads through the consumer group, `streamId` pages through the messages delivered before but not
+  // acknowledged while `UNDELIVERED_STREAM_ID` reads new ones. A group gone with its stream is created again.
   async getStreamMessages (
     streamKey: string,
-    streamId = this.SMALLEST_STREAM_ID,
+    streamId = this.UNDELIVERED_STREAM_ID,
     count = 1
   ): Promise<StreamMessage[] | null> {
-    const results = await this.client.xRead(
-      { key: streamKey, id: streamId },
-      { COUNT: count }
-    );
-
-    return results?.[0].messages as StreamMessage[];
+    try {
+      const results = await (await this.connection()).xReadGroup(
+        this.CONSUMER_GROUP,
+        this.CONSUMER,
+        { key: this.namespaced(streamKey), id: streamId },
+        { COUNT: count }
+      );
+
+      return results?.[0].messages as StreamMessage[];
+    } catch (err) {
+      if (!(err instanceof Error && err.message.startsWith('NOGROUP'))) {
+        throw err;
+      }
+      await this.createConsumerGroup(streamKey);
+      return await this.getStreamMessages(streamKey, streamId, count);
+    }
   };
 
-  async deleteStreamMessage (
+  async acknowledgeStreamMessage (
     streamKey: string,
     id: string,
   ): Promise<void> {
-    await this.client.xDel(streamKey, id);
+    await (await this.connection()).xAck(this.namespaced(streamKey), this.CONSUMER_GROUP, id);
   };
 
   async getUnprocessedStreamMessages (
     streamKey: string,
     startId = this.SMALLEST_STREAM_ID,
   ): Promise<StreamMessage[]> {
-    const results = await this.client.xRange(streamKey, startId, this.LARGEST_STREAM_ID);
+    const results = await (await this.connection()).xRange(this.namespaced(streamKey), startId, this.LARGEST_STREAM_ID);
 
     return results as StreamMessage[];
   };
 
   async getStreamStorage (streamKey: string): Promise<StreamStorage> {
     const storageKey = this.generateStorageKey(streamKey);
-    const results = await this.client.get(storageKey);
+    const results = await (await this.connection()).get(storageKey);
 
     if (results === null) {
       throw new Error(`${storageKey} does not have any data`);
@@ -85,10 +179,22 @@ export default class RedisClient {
   };
 
   async getStreams (): Promise<string[]> {
-    return await this.client.sMembers(this.STREAMS_SET_KEY);
+    return await (await this.connection()).sMembers(this.namespaced(this.STREAMS_SET_KEY));
   }
 
+  // The value is binary, the zstd frame of the JSON after the marker or plain JSON without it
   async getStreamerMessage (blockHeight: number): Promise<string | null> {
-    return await this.client.get(`${this.STREAMER_MESSAGE_HASH_KEY_BASE}${blockHeight}`);
+    const streamerMessage = await (await this.connection()).get(
+      commandOptions({ returnBuffers: true }),
+      this.namespaced(`${this.STREAMER_MESSAGE_HASH_KEY_BASE}${blockHeight}`)
+    );
+    if (streamerMessage === null) {
+      return null;
+    }
+    const marker = Buffer.from(this.ZSTD_STREAMER_MESSAGE_MARKER);
+    if (streamerMessage.subarray(0, marker.length).equals(marker)) {
+      return Buffer.from(decompress(streamerMessage.subarray(marker.length))).toString('utf8');
+    }
+    return streamerMessage.toString('utf8');
   }
 }
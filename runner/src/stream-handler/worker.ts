@@ -12,6 +12,7 @@ if (isMainThread) {
 }
 interface QueueMessage {
   block: Block
+  matches: unknown[]
   streamMessageId: string
 }
 type PrefetchQueue = Array<Promise<QueueMessage>>;
@@ -54,7 +55,9 @@ function incrementId (id: string): string {
 
 async function blockQueueProducer (workerContext: WorkerContext, streamKey: string): Promise<void> {
   const HISTORICAL_BATCH_SIZE = 100;
-  let streamMessageStartId = '0';
+  // messages delivered before a restart but never acknowledged come first, then undelivered ones
+  let streamMessageStartId = workerContext.redisClient.SMALLEST_STREAM_ID;
+  await workerContext.redisClient.createConsumerGroup(streamKey);
 
   while (true) {
     const preFetchCount = HISTORICAL_BATCH_SIZE - workerContext.queue.length;
@@ -63,7 +66,11 @@ async function blockQueueProducer (workerContext: WorkerContext, streamKey: stri
       continue;
     }
     const messages = await workerContext.redisClient.getStreamMessages(streamKey, streamMessageStartId, preFetchCount);
-    if (messages == null) {
+    if (messages == null || messages.length === 0) {
+      if (streamMessageStartId !== workerContext.redisClient.UNDELIVERED_STREAM_ID) {
+        streamMessageStartId = workerContext.redisClient.UNDELIVERED_STREAM_ID;
+        continue;
+      }
       await sleep(100);
       continue;
     }
@@ -71,10 +78,13 @@ async function blockQueueProducer (workerContext: WorkerContext, streamKey: stri
 
     for (const streamMessage of messages) {
       const { id, message } = streamMessage;
-      workerContext.queue.push(generateQueueMessage(workerContext, Number(message.block_height), id));
+      const matches = message.matches === undefined ? [] : JSON.parse(message.matches);
+      workerContext.queue.push(generateQueueMessage(workerContext, Number(message.block_height), matches, id));
     }
 
-    streamMessageStartId = incrementId(messages[messages.length - 1].id);
+    if (streamMessageStartId !== workerContext.redisClient.UNDELIVERED_STREAM_ID) {
+      streamMessageStartId = incrementId(messages[messages.length - 1].id);
+    }
   }
 }
 
@@ -112,9 +122,9 @@ async function blockQueueConsumer (workerContext: WorkerContext, streamKey: stri
         continue;
       }
       METRICS.BLOCK_WAIT_DURATION.labels({ indexer: indexerName, type: workerContext.streamType }).set(performance.now() - blockStartTime);
-      await indexer.runFunctions(block, functions, false, { provision: true });
+      await indexer.runFunctions(block, functions, false, { provision: true, matches: queueMessage.matches });
 
-      await workerContext.redisClient.deleteStreamMessage(streamKey, streamMessageId);
+      await workerContext.redisClient.acknowledgeStreamMessage(streamKey, streamMessageId);
       await workerContext.queue.shift();
 
       METRICS.EXECUTION_DURATION.labels({ indexer: indexerName, type: workerContext.streamType }).observe(performance.now() - startTime);
@@ -136,10 +146,11 @@ async function blockQueueConsumer (workerContext: WorkerContext, streamKey: stri
   }
 }
 
-async function generateQueueMessage (workerContext: WorkerContext, blockHeight: number, streamMessageId: string): Promise<QueueMessage> {
+async function generateQueueMessage (workerContext: WorkerContext, blockHeight: number, matches: unknown[], streamMessageId: string): Promise<QueueMessage> {
   const block = await workerContext.lakeClient.fetchBlock(blockHeight, workerContext.streamType === 'historical');
   return {
     block,
+    matches,
     streamMessageId
   };
 }